@@ -0,0 +1,469 @@
+// Command-line interface: subcommands, shared flags and the legacy mode flags
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use std::path::PathBuf;
+
+use crate::organizer::conflict::ConflictPolicy;
+use crate::organizer::date::{DateSource, Granularity};
+use crate::organizer::dedupe::{DedupeAction, KeepPolicy};
+use crate::organizer::transfer::TransferMode;
+use crate::organizer::watch::WatchMode;
+use crate::output::OutputFormat;
+
+const BANNER: &str = "\
+╔═══════════════════════════════════════════════════╗
+║                                                   ║
+║   ██╗  ██╗ ██████╗ ███╗   ██╗██████╗  ██████╗     ║
+║   ██║ ██╔╝██╔═══██╗████╗  ██║██╔══██╗██╔═══██╗    ║
+║   █████╔╝ ██║   ██║██╔██╗ ██║██║  ██║██║   ██║    ║
+║   ██╔═██╗ ██║   ██║██║╚██╗██║██║  ██║██║   ██║    ║
+║   ██║  ██╗╚██████╔╝██║ ╚████║██████╔╝╚██████╔╝    ║
+║   ╚═╝  ╚═╝ ╚═════╝ ╚═╝  ╚═══╝╚═════╝  ╚═════╝     ║
+║    ML-Powered • Blazingly Fast • Beautiful TUI    ║
+║                                                   ║
+╚═══════════════════════════════════════════════════╝";
+
+/// Mode flags from before subcommands existed, e.g. `kondo -c -nui ~/Downloads`
+const LEGACY_MODES: &[(&str, &str)] = &[
+    ("-c", "categorize"),
+    ("--categorize", "categorize"),
+    ("-f", "similar"),
+    ("--filename", "similar"),
+    ("-i", "cluster"),
+    ("--intelligent", "cluster"),
+    ("-d", "date"),
+    ("--date", "date"),
+    ("--dedupe", "dedupe"),
+];
+
+/// Options before the command that take their value as the next argument
+const VALUE_OPTIONS: &[&str] = &[
+    "--config",
+    "--profile",
+    "--output",
+    "--max-depth",
+    "--transfer",
+    "--dest",
+    "--on-conflict",
+    "--rename-template",
+    "--plan-out",
+];
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "kondo",
+    version,
+    before_help = BANNER,
+    about = "A smart file organizer that sorts files by category, name similarity, content or date",
+    after_help = "Without a command, the directories are organized with the default mode from \
+                  the config: cluster if enable_intelligent_grouping is set, similar if \
+                  enable_smart_grouping is set, otherwise categorize.\n\n\
+                  Exit codes: 0 success, 1 failure, 2 invalid command line, 3 some files failed.",
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[command(flatten)]
+    pub target: TargetArgs,
+
+    #[command(flatten)]
+    pub options: OrganizeOptions,
+
+    #[command(flatten)]
+    pub global: GlobalArgs,
+}
+
+/// Flags every command accepts, before or after the command name
+#[derive(Debug, Clone, Args)]
+pub struct GlobalArgs {
+    /// Show what would happen without changing any files
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Read settings from FILE instead of the default kondo.toml (also $KONDO_CONFIG)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Apply the [profiles.NAME] table of the config on top of the rest of it
+    #[arg(long, global = true, value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Organize straight away instead of opening the interactive UI (also -nui)
+    #[arg(long, global = true)]
+    pub no_ui: bool,
+
+    /// text, json (one document per run) or ndjson (one event per file as it happens)
+    #[arg(long, global = true, value_name = "FORMAT", default_value = "text")]
+    pub output: OutputFormat,
+
+    /// Same as --output json
+    #[arg(long, global = true, conflicts_with = "output")]
+    pub json: bool,
+
+    /// Also print log messages to stderr
+    #[arg(short, long, global = true)]
+    pub verbose: bool,
+}
+
+impl GlobalArgs {
+    pub fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output
+        }
+    }
+}
+
+/// Directories to organize, or a plan file to write instead
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct TargetArgs {
+    /// Directories to organize (default: the current directory)
+    #[arg(value_name = "DIR")]
+    pub dirs: Vec<PathBuf>,
+
+    /// Write the proposed moves to FILE (.json or .toml) instead of moving
+    #[arg(long, value_name = "FILE")]
+    pub plan_out: Option<PathBuf>,
+}
+
+/// Which files are collected and how they get to their folders
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct OrganizeOptions {
+    /// Also organize files in subdirectories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Recurse at most N levels deep (1 = top level only)
+    #[arg(long, value_name = "N", value_parser = parse_depth)]
+    pub max_depth: Option<usize>,
+
+    /// move, copy, hardlink or symlink into the folders
+    #[arg(long, value_name = "MODE")]
+    pub transfer: Option<TransferMode>,
+
+    /// Create the organized folders in DIR instead of the scanned directory
+    #[arg(long, value_name = "DIR")]
+    pub dest: Option<String>,
+
+    /// rename, skip, overwrite, keep-newer, keep-larger or delete-if-identical
+    #[arg(long, value_name = "POLICY")]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Name for renamed files, e.g. "{stem} ({n}){ext}"
+    #[arg(long, value_name = "TEMPLATE")]
+    pub rename_template: Option<String>,
+}
+
+/// The organize options that apply to `dedupe`: duplicates stay put or go to
+/// kondo-trash, so there is no transfer mode or destination to choose
+#[derive(Debug, Clone, Default, PartialEq, Args)]
+pub struct DedupeOptions {
+    /// Also look for duplicates in subdirectories
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Recurse at most N levels deep (1 = top level only)
+    #[arg(long, value_name = "N", value_parser = parse_depth)]
+    pub max_depth: Option<usize>,
+
+    /// What to do when a name in kondo-trash is taken while trashing
+    #[arg(long, value_name = "POLICY")]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// Name for duplicates renamed in kondo-trash, e.g. "{stem} ({n}){ext}"
+    #[arg(long, value_name = "TEMPLATE")]
+    pub rename_template: Option<String>,
+}
+
+impl From<DedupeOptions> for OrganizeOptions {
+    fn from(options: DedupeOptions) -> Self {
+        Self {
+            recursive: options.recursive,
+            max_depth: options.max_depth,
+            on_conflict: options.on_conflict,
+            rename_template: options.rename_template,
+            ..Self::default()
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Sort files into category folders (images, videos, documents, ...)
+    #[command(visible_alias = "c")]
+    Categorize {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        options: OrganizeOptions,
+        /// Detect file types from content: sorts extensionless files and flags mislabeled ones
+        #[arg(long)]
+        sniff: bool,
+    },
+
+    /// Group files with similar names into folders
+    #[command(visible_alias = "f", alias = "filename")]
+    Similar {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        options: OrganizeOptions,
+    },
+
+    /// Cluster files by name and content (TF-IDF) into folders
+    #[command(visible_alias = "i", alias = "intelligent")]
+    Cluster {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        options: OrganizeOptions,
+    },
+
+    /// Sort files into YYYY/MM folders by date
+    #[command(visible_alias = "d")]
+    Date {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        options: OrganizeOptions,
+        /// Date sources in priority order, e.g. exif,filename,modified,created
+        #[arg(long, value_name = "SOURCES", value_delimiter = ',')]
+        date_source: Vec<DateSource>,
+        /// Date folders per year, month or day
+        #[arg(long, value_name = "G")]
+        granularity: Option<Granularity>,
+    },
+
+    /// Find byte-identical files and report, trash or hard link the extra copies
+    Dedupe {
+        #[command(flatten)]
+        target: TargetArgs,
+        #[command(flatten)]
+        options: DedupeOptions,
+        /// report (default), trash or hardlink the duplicates; trash and hardlink can be undone
+        #[arg(long, alias = "dedupe-action", value_name = "ACTION")]
+        action: Option<DedupeAction>,
+        /// Copy to keep: shortest name (default), oldest or newest
+        #[arg(long, value_name = "POLICY")]
+        keep: Option<KeepPolicy>,
+    },
+
+    /// Organize new files as they arrive, until interrupted
+    Watch {
+        /// Directory to watch (default: the current directory)
+        #[arg(value_name = "DIR")]
+        dir: Option<PathBuf>,
+        #[command(flatten)]
+        options: OrganizeOptions,
+        /// categorize or date (default from the config)
+        #[arg(long, value_name = "MODE")]
+        mode: Option<WatchMode>,
+        /// Wait until a file has been unchanged for SECS seconds
+        #[arg(long, value_name = "SECS")]
+        settle: Option<u64>,
+    },
+
+    /// Execute a plan written with --plan-out
+    Apply {
+        #[arg(value_name = "PLAN_FILE")]
+        plan: PathBuf,
+    },
+
+    /// Show which rule or category `categorize` would sort FILE into
+    Explain {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        /// Destination root to resolve targets against
+        #[arg(long, value_name = "DIR")]
+        dest: Option<String>,
+    },
+
+    /// Move the files of the last (or given) run back
+    Undo {
+        #[arg(value_name = "RUN_ID")]
+        run_id: Option<String>,
+        /// List recorded runs instead
+        #[arg(long)]
+        list: bool,
+    },
+
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+}
+
+impl Command {
+    /// Name used in logs and JSON output
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Categorize { .. } => "categorize",
+            Command::Similar { .. } => "similar",
+            Command::Cluster { .. } => "cluster",
+            Command::Date { .. } => "date",
+            Command::Dedupe { .. } => "dedupe",
+            Command::Watch { .. } => "watch",
+            Command::Apply { .. } => "apply",
+            Command::Explain { .. } => "explain",
+            Command::Undo { .. } => "undo",
+            Command::Config { .. } => "config",
+        }
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print where the config file is read from
+    Path,
+    /// Print the config file
+    Show {
+        /// Print every setting as it applies to DIR instead, after the profile and
+        /// DIR's .kondo.toml, with where it came from
+        #[arg(long)]
+        effective: bool,
+        /// Directory whose .kondo.toml applies (default: the current directory)
+        #[arg(value_name = "DIR", requires = "effective")]
+        dir: Option<PathBuf>,
+    },
+    /// Write the commented default config, unless the file exists already
+    Init,
+    /// Report syntax errors, unknown keys, weights that do not sum to 1.0 and
+    /// extensions claimed by several categories; exits with 1 if anything is wrong
+    Check,
+    /// Upgrade the config file to the current format, keeping a backup of the old one;
+    /// with --dry-run, print the changes as a diff instead
+    Migrate,
+}
+
+impl Cli {
+    /// Parses the (normalized) arguments, exiting with usage on errors. Directories and
+    /// organize options belong to the command, so `kondo -r categorize DIR` is refused
+    /// rather than silently dropping `-r`.
+    pub fn parse_args(args: Vec<String>) -> Self {
+        let cli = Self::parse_from(args);
+        if cli.command.is_some()
+            && (cli.target != TargetArgs::default() || cli.options != OrganizeOptions::default())
+        {
+            Self::command()
+                .error(
+                    ErrorKind::ArgumentConflict,
+                    "directories and organize options go after the command, e.g. `kondo categorize -r ~/Downloads`",
+                )
+                .exit();
+        }
+        // `kondo categorise` would otherwise look like a directory that does not exist
+        if cli.command.is_none() {
+            let unknown = cli
+                .target
+                .dirs
+                .iter()
+                .find(|dir| dir.components().count() == 1 && !dir.exists());
+            if let Some(unknown) = unknown {
+                Self::command()
+                    .error(
+                        ErrorKind::InvalidSubcommand,
+                        format!(
+                            "'{}' is neither a command nor an existing directory",
+                            unknown.display()
+                        ),
+                    )
+                    .exit();
+            }
+        }
+        cli
+    }
+}
+
+fn parse_depth(value: &str) -> Result<usize, String> {
+    match value.parse::<usize>() {
+        Ok(depth) if depth > 0 => Ok(depth),
+        _ => Err(format!("expected a number of at least 1, got '{}'", value)),
+    }
+}
+
+/// Rewrites the old spellings so existing scripts keep working: the first mode flag
+/// among the options before any directory becomes the subcommand, and `-nui` becomes
+/// `--no-ui`
+pub fn normalize_args(args: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut args: Vec<String> = args
+        .into_iter()
+        .map(|arg| if arg == "-nui" { "--no-ui".to_string() } else { arg })
+        .collect();
+
+    let mut index = 1;
+    while let Some(arg) = args.get(index) {
+        if arg == "--" || !arg.starts_with('-') {
+            break;
+        }
+        if let Some((_, command)) = LEGACY_MODES.iter().find(|(flag, _)| flag == arg) {
+            // The options before it apply to the command just as well after it
+            args.remove(index);
+            args.insert(1, command.to_string());
+            break;
+        }
+        index += if VALUE_OPTIONS.contains(&arg.as_str()) { 2 } else { 1 };
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(normalize_args(args.iter().map(|a| a.to_string())))
+    }
+
+    #[test]
+    fn test_subcommands_legacy_flags_and_typos() {
+        let cli = parse(&["kondo", "-c", "-nui", "a", "b", "--dest", "out"]).unwrap();
+        assert!(cli.global.no_ui);
+        match cli.command {
+            Some(Command::Categorize { target, options, .. }) => {
+                assert_eq!(target.dirs, vec![PathBuf::from("a"), PathBuf::from("b")]);
+                assert_eq!(options.dest.as_deref(), Some("out"));
+            }
+            other => panic!("expected categorize, got {:?}", other),
+        }
+
+        for args in [
+            &["kondo", "-nui", "-c", "a"][..],
+            &["kondo", "--output", "json", "-c", "a"],
+        ] {
+            let cli = parse(args).unwrap();
+            assert!(matches!(cli.command, Some(Command::Categorize { .. })), "{:?}", args);
+        }
+        let cli = parse(&["kondo", "-r", "-nui", "--intelligent", "a"]).unwrap();
+        assert!(cli.global.no_ui);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Cluster { ref options, .. }) if options.recursive
+        ));
+        // Past the first directory `-c` is not a mode flag
+        assert!(parse(&["kondo", "a", "-c"]).is_err());
+
+        let cli = parse(&["kondo", "--dry-run", "date", "--date-source", "exif,modified", "x"]).unwrap();
+        assert!(cli.global.dry_run);
+        assert!(matches!(
+            cli.command,
+            Some(Command::Date { ref date_source, .. }) if date_source.len() == 2
+        ));
+
+        let cli = parse(&["kondo", "--no-ui", "-r", "~/Downloads"]).unwrap();
+        assert!(cli.command.is_none());
+        assert!(cli.options.recursive);
+
+        assert!(parse(&["kondo", "categorise", "--recursve"]).is_err());
+        assert!(parse(&["kondo", "categorize", "--on-conflict", "clobber"]).is_err());
+        assert!(parse(&["kondo", "categorize", "--max-depth", "0"]).is_err());
+
+        // Duplicates are never transferred or sent to another root
+        assert!(parse(&["kondo", "dedupe", "-r", "--on-conflict", "skip", "a"]).is_ok());
+        assert!(parse(&["kondo", "dedupe", "--transfer", "copy", "a"]).is_err());
+        assert!(parse(&["kondo", "dedupe", "--dest", "/tmp/x", "a"]).is_err());
+        assert!(parse(&["kondo", "--dedupe", "--dest", "/tmp/x", "a"]).is_err());
+    }
+}
@@ -0,0 +1,1192 @@
+// kondo.toml: the one schema every mode reads, profiles and per-directory .kondo.toml
+// files layered on top, its default template and `kondo config check`
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use toml_edit::{ImDocument, Item, TableLike};
+
+use crate::organizer::categorise::{self, CategoryConfig, FileOrganizerConfig};
+use crate::organizer::conflict::{self, ConflictPolicy};
+use crate::organizer::date::DateConfig;
+use crate::migrate::{self, CONFIG_VERSION};
+use crate::organizer::error::KondoError;
+use crate::organizer::filename::SimilarityConfig;
+use crate::organizer::ignore::{self, SkipMatcher};
+use crate::organizer::intelligent::IntelligentConfig;
+use crate::organizer::rules::{self, RuleConfig};
+use crate::organizer::transfer::TransferMode;
+use crate::organizer::walk;
+use crate::organizer::watch::WatchConfig;
+
+/// How far a pair of weights may be from summing to 1.0
+const WEIGHT_TOLERANCE: f64 = 1e-6;
+
+/// Settings a directory's .kondo.toml may change; the rest are personal and stay in kondo.toml
+const DIRECTORY_KEYS: &[&str] = &[
+    "skip_patterns",
+    "categories",
+    "rules",
+    "similarity_config",
+    "intelligent_config",
+    "date_config",
+];
+
+/// Prefix of the environment variables that set single values, with `__` between
+/// table and key: KONDO_TRANSFER_MODE=copy, KONDO_SIMILARITY_CONFIG__MIN_SIMILARITY_SCORE=0.8
+const ENV_PREFIX: &str = "KONDO_";
+
+/// Variables with the prefix that are not settings
+const ENV_RESERVED: &[&str] = &["KONDO_CONFIG"];
+
+/// Tables a profile or directory replaces as a whole instead of key by key, so it
+/// can bring its own set of categories
+const REPLACED_TABLES: &[&str] = &["categories"];
+
+/// Everything in kondo.toml
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct KondoConfig {
+    #[serde(default)]
+    pub log_file: Option<String>,
+
+    #[serde(default)]
+    pub enable_smart_grouping: bool,
+
+    #[serde(default)]
+    pub enable_intelligent_grouping: bool,
+
+    #[serde(default)]
+    pub similarity_config: SimilarityConfigToml,
+
+    #[serde(default)]
+    pub intelligent_config: IntelligentConfigToml,
+
+    #[serde(default)]
+    pub date_config: DateConfig,
+
+    #[serde(default)]
+    pub watch: WatchConfig,
+
+    /// move | copy | hardlink | symlink, overridden by `--transfer`
+    #[serde(default)]
+    pub transfer_mode: TransferMode,
+
+    /// Where organized folders are created instead of the scanned directory, overridden by `--dest`
+    #[serde(default)]
+    pub destination_root: Option<String>,
+
+    /// Detect formats from file content in categorize mode, enabled by `--sniff`
+    #[serde(default)]
+    pub sniff_content: bool,
+
+    /// What happens when a destination is taken, overridden by `--on-conflict`
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+
+    /// Name pattern for renamed files, overridden by `--rename-template`
+    #[serde(default)]
+    pub rename_template: Option<String>,
+
+    /// File names and patterns left alone by every mode
+    #[serde(default = "default_skip_patterns")]
+    pub skip_patterns: Vec<String>,
+
+    /// Categorize mode: extension lists keyed by category
+    #[serde(default = "categorise::create_default_categories")]
+    pub categories: HashMap<String, CategoryConfig>,
+
+    /// Categorize mode: checked in order before the categories; the first match wins
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<RuleConfig>,
+}
+
+/// TOML representation of similarity config
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SimilarityConfigToml {
+    #[serde(default = "default_levenshtein_threshold")]
+    pub levenshtein_threshold: f64,
+
+    #[serde(default = "default_jaccard_threshold")]
+    pub jaccard_threshold: f64,
+
+    #[serde(default = "default_levenshtein_weight")]
+    pub levenshtein_weight: f64,
+
+    #[serde(default = "default_jaccard_weight")]
+    pub jaccard_weight: f64,
+
+    #[serde(default = "default_min_similarity_score")]
+    pub min_similarity_score: f64,
+}
+
+/// TOML representation of intelligent config
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IntelligentConfigToml {
+    #[serde(default = "default_max_lines_to_read")]
+    pub max_lines_to_read: usize,
+
+    #[serde(default = "default_min_cluster_size")]
+    pub min_cluster_size: usize,
+
+    #[serde(default = "default_max_clusters")]
+    pub max_clusters: usize,
+
+    #[serde(default = "default_filename_similarity_weight")]
+    pub filename_similarity_weight: f64,
+
+    #[serde(default = "default_content_similarity_weight")]
+    pub content_similarity_weight: f64,
+
+    #[serde(default = "default_similarity_threshold")]
+    pub similarity_threshold: f64,
+
+    #[serde(default = "default_max_iterations")]
+    pub max_iterations: usize,
+}
+
+// Default functions for serde - Similarity Config
+fn default_levenshtein_threshold() -> f64 { 0.7 }
+fn default_jaccard_threshold() -> f64 { 0.5 }
+fn default_levenshtein_weight() -> f64 { 0.6 }
+fn default_jaccard_weight() -> f64 { 0.4 }
+fn default_min_similarity_score() -> f64 { 0.65 }
+
+// Default functions for serde - Intelligent Config
+fn default_max_lines_to_read() -> usize { 100 }
+fn default_min_cluster_size() -> usize { 2 }
+fn default_max_clusters() -> usize { 20 }
+fn default_filename_similarity_weight() -> f64 { 0.3 }
+fn default_content_similarity_weight() -> f64 { 0.7 }
+fn default_similarity_threshold() -> f64 { 0.65 }
+fn default_max_iterations() -> usize { 100 }
+
+fn default_skip_patterns() -> Vec<String> {
+    ignore::DEFAULT_PATTERNS.iter().map(|p| p.to_string()).collect()
+}
+
+impl Default for KondoConfig {
+    fn default() -> Self {
+        Self {
+            log_file: None,
+            enable_smart_grouping: false,
+            enable_intelligent_grouping: false,
+            similarity_config: SimilarityConfigToml::default(),
+            intelligent_config: IntelligentConfigToml::default(),
+            date_config: DateConfig::default(),
+            watch: WatchConfig::default(),
+            transfer_mode: TransferMode::default(),
+            destination_root: None,
+            sniff_content: false,
+            conflict_policy: ConflictPolicy::default(),
+            rename_template: None,
+            skip_patterns: default_skip_patterns(),
+            categories: categorise::create_default_categories(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+impl Default for SimilarityConfigToml {
+    fn default() -> Self {
+        Self {
+            levenshtein_threshold: 0.7,
+            jaccard_threshold: 0.5,
+            levenshtein_weight: 0.6,
+            jaccard_weight: 0.4,
+            min_similarity_score: 0.65,
+        }
+    }
+}
+
+impl Default for IntelligentConfigToml {
+    fn default() -> Self {
+        Self {
+            max_lines_to_read: 100,
+            min_cluster_size: 2,
+            max_clusters: 20,
+            filename_similarity_weight: 0.3,
+            content_similarity_weight: 0.7,
+            similarity_threshold: 0.65,
+            max_iterations: 100,
+        }
+    }
+}
+
+/// Convert TOML config to runtime config
+impl From<SimilarityConfigToml> for SimilarityConfig {
+    fn from(toml_config: SimilarityConfigToml) -> Self {
+        SimilarityConfig {
+            levenshtein_threshold: toml_config.levenshtein_threshold,
+            jaccard_threshold: toml_config.jaccard_threshold,
+            levenshtein_weight: toml_config.levenshtein_weight,
+            jaccard_weight: toml_config.jaccard_weight,
+            min_similarity_score: toml_config.min_similarity_score,
+        }
+    }
+}
+
+impl From<IntelligentConfigToml> for IntelligentConfig {
+    fn from(toml_config: IntelligentConfigToml) -> Self {
+        IntelligentConfig {
+            max_lines_to_read: toml_config.max_lines_to_read,
+            min_cluster_size: toml_config.min_cluster_size,
+            max_clusters: toml_config.max_clusters,
+            filename_similarity_weight: toml_config.filename_similarity_weight,
+            content_similarity_weight: toml_config.content_similarity_weight,
+            similarity_threshold: toml_config.similarity_threshold,
+            max_iterations: toml_config.max_iterations,
+        }
+    }
+}
+
+impl KondoConfig {
+    /// The part of the config categorize mode works from
+    pub fn categorize_config(&self) -> FileOrganizerConfig {
+        FileOrganizerConfig {
+            categories: self.categories.clone(),
+            transfer_mode: self.transfer_mode,
+            rules: self.rules.clone(),
+            sniff_content: self.sniff_content,
+        }
+    }
+
+    /// This config with the .kondo.toml of `dir` on top, and that file's path; None
+    /// if the directory has none
+    pub fn for_directory(&self, dir: &Path) -> Result<Option<(PathBuf, KondoConfig)>, KondoError> {
+        let Some((path, table)) = directory_file(dir)? else {
+            return Ok(None);
+        };
+        let mut layers = Layers::new(self)?;
+        layers.push(Source::Directory(path.clone()), table);
+        Ok(Some((path, layers.config()?)))
+    }
+}
+
+/// Where in the file a problem is, 1-based
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    fn of(content: &str, offset: usize) -> Self {
+        let before = &content[..offset.min(content.len())];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+/// One thing wrong with a config file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Problem {
+    #[serde(flatten)]
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.position {
+            Some(Position { line, column }) => {
+                write!(f, "line {}, column {}: {}", line, column, self.message)
+            }
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Where a setting came from, as shown by `kondo config show --effective`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// Nothing sets it
+    Default,
+    /// kondo.toml, or the file given with `--config`
+    File(PathBuf),
+    /// `[profiles.NAME]`, chosen with `--profile`
+    Profile(String),
+    /// A `KONDO_*` environment variable
+    Environment(String),
+    /// .kondo.toml in the directory being organized
+    Directory(PathBuf),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => f.write_str("default"),
+            Source::File(path) | Source::Directory(path) => write!(f, "{}", path.display()),
+            Source::Profile(name) => write!(f, "profile {}", name),
+            Source::Environment(variable) => write!(f, "${}", variable),
+        }
+    }
+}
+
+impl Serialize for Source {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// One value of the effective config and the layer that set it
+#[derive(Debug, Serialize)]
+pub struct Setting {
+    pub key: String,
+    pub value: toml::Value,
+    pub source: Source,
+}
+
+/// Config tables stacked on top of each other, later ones winning
+#[derive(Debug, Clone)]
+pub struct Layers {
+    table: toml::Table,
+    /// Dotted keys a layer set, and which layer that was
+    origins: BTreeMap<String, Source>,
+}
+
+impl Layers {
+    /// Starts from `config`, counting all of it as defaults
+    pub fn new(config: &KondoConfig) -> Result<Self, KondoError> {
+        let table = toml::Table::try_from(config)
+            .map_err(|e| KondoError::ConfigInvalid(e.to_string()))?;
+        Ok(Self {
+            table,
+            origins: BTreeMap::new(),
+        })
+    }
+
+    /// Puts `overlay` on top. Tables merge key by key; other values and the tables
+    /// in `REPLACED_TABLES` are replaced.
+    pub fn push(&mut self, source: Source, overlay: toml::Table) {
+        merge(&mut self.table, overlay, "", &source, &mut self.origins);
+    }
+
+    /// Puts the `KONDO_*` variables among `vars` on top, each read as the type of the
+    /// value it replaces. The prefix is shared with whatever else sets `KONDO_*`, so a
+    /// name that is not a setting is skipped with a warning, returned for the caller to
+    /// print; a bad value for a real setting is an error, like a bad flag.
+    pub fn push_env(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<Vec<String>, KondoError> {
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX) && !ENV_RESERVED.contains(&name.as_str()))
+            .collect();
+        vars.sort();
+
+        let mut ignored = Vec::new();
+        for (name, raw) in vars {
+            let invalid = |message: String| KondoError::InvalidInput(format!("{}: {}", name, message));
+            let path = name[ENV_PREFIX.len()..].to_lowercase().replace("__", ".");
+            let keys: Vec<&str> = path.split('.').collect();
+
+            let current = lookup(&self.table, &keys);
+            let value = match env_value(current, &raw) {
+                Ok(value) => value,
+                Err(message) if current.is_some() => return Err(invalid(message)),
+                Err(_) => {
+                    ignored.push(format!("{}: `{}` is not a setting, ignored", name, path));
+                    continue;
+                }
+            };
+            let overlay = keys.iter().rev().fold(value, |value, key| {
+                toml::Value::Table(toml::Table::from_iter([(key.to_string(), value)]))
+            });
+
+            let mut unknown = false;
+            let parsed: Result<KondoConfig, toml::de::Error> =
+                serde_ignored::deserialize(overlay.clone(), |_| unknown = true);
+            if unknown {
+                ignored.push(format!("{}: `{}` is not a setting, ignored", name, path));
+                continue;
+            }
+            if let Err(e) = parsed {
+                return Err(invalid(e.message().trim().replace('\n', "; ")));
+            }
+            if let toml::Value::Table(overlay) = overlay {
+                self.push(Source::Environment(name.clone()), overlay);
+            }
+        }
+        Ok(ignored)
+    }
+
+    /// The config the layers add up to
+    pub fn config(&self) -> Result<KondoConfig, KondoError> {
+        toml::Value::Table(self.table.clone())
+            .try_into()
+            .map_err(|e: toml::de::Error| KondoError::ConfigInvalid(e.message().trim().to_string()))
+    }
+
+    /// Every setting of the config the layers add up to, sorted by key, with the
+    /// layer it came from. Keys the schema does not know are left out.
+    pub fn effective(&self) -> Result<Vec<Setting>, KondoError> {
+        let table = toml::Table::try_from(self.config()?)
+            .map_err(|e| KondoError::ConfigInvalid(e.to_string()))?;
+        let mut settings = Vec::new();
+        collect_settings(&table, "", &self.origins, &mut settings);
+        Ok(settings)
+    }
+}
+
+fn merge(
+    table: &mut toml::Table,
+    overlay: toml::Table,
+    prefix: &str,
+    source: &Source,
+    origins: &mut BTreeMap<String, Source>,
+) {
+    for (key, value) in overlay {
+        let path = join(prefix, &key);
+        let replaced = REPLACED_TABLES.contains(&path.as_str());
+        let value = match (table.get_mut(&key), value) {
+            (Some(toml::Value::Table(existing)), toml::Value::Table(nested)) if !replaced => {
+                merge(existing, nested, &path, source, origins);
+                continue;
+            }
+            (_, value) => value,
+        };
+        let inside = format!("{}.", path);
+        origins.retain(|origin, _| !origin.starts_with(&inside));
+        origins.insert(path, source.clone());
+        table.insert(key, value);
+    }
+}
+
+/// The value at `keys` in nested tables
+fn lookup<'a>(table: &'a toml::Table, keys: &[&str]) -> Option<&'a toml::Value> {
+    let (last, tables) = keys.split_last()?;
+    tables
+        .iter()
+        .try_fold(table, |table, key| table.get(*key)?.as_table())?
+        .get(*last)
+}
+
+/// Reads an environment variable as the type of the value it replaces. Values the
+/// defaults leave unset, such as `destination_root`, are strings.
+fn env_value(current: Option<&toml::Value>, raw: &str) -> Result<toml::Value, String> {
+    match current {
+        Some(toml::Value::Boolean(_)) => match raw.to_lowercase().as_str() {
+            "true" | "1" | "yes" | "on" => Ok(toml::Value::Boolean(true)),
+            "false" | "0" | "no" | "off" => Ok(toml::Value::Boolean(false)),
+            _ => Err(format!("expected true or false, not `{}`", raw)),
+        },
+        Some(toml::Value::Integer(_)) => raw
+            .parse()
+            .map(toml::Value::Integer)
+            .map_err(|_| format!("expected a whole number, not `{}`", raw)),
+        Some(toml::Value::Float(_)) => raw
+            .parse()
+            .map(toml::Value::Float)
+            .map_err(|_| format!("expected a number, not `{}`", raw)),
+        Some(toml::Value::Array(_) | toml::Value::Table(_)) => {
+            Err("only single values can be set from the environment, lists and tables belong in kondo.toml".to_string())
+        }
+        _ => Ok(toml::Value::String(raw.to_string())),
+    }
+}
+
+fn collect_settings(
+    table: &toml::Table,
+    prefix: &str,
+    origins: &BTreeMap<String, Source>,
+    settings: &mut Vec<Setting>,
+) {
+    for (key, value) in table {
+        let path = join(prefix, key);
+        match value {
+            toml::Value::Table(nested) if !nested.is_empty() => {
+                collect_settings(nested, &path, origins, settings)
+            }
+            _ => settings.push(Setting {
+                source: origin(origins, &path),
+                key: path,
+                value: value.clone(),
+            }),
+        }
+    }
+}
+
+/// The layer that set `path`, or the closest table around it
+fn origin(origins: &BTreeMap<String, Source>, path: &str) -> Source {
+    let mut key = path;
+    loop {
+        if let Some(source) = origins.get(key) {
+            return source.clone();
+        }
+        match key.rfind('.') {
+            Some(end) => key = &key[..end],
+            None => return Source::Default,
+        }
+    }
+}
+
+fn join(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", prefix, key)
+    }
+}
+
+/// Turns dotted keys into positions in one file
+struct Locator<'a> {
+    content: &'a str,
+    document: Option<ImDocument<&'a str>>,
+}
+
+impl<'a> Locator<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            document: ImDocument::parse(content).ok(),
+        }
+    }
+
+    fn position(&self, path: &str) -> Option<Position> {
+        self.document
+            .as_ref()
+            .and_then(|document| locate(document, self.content, path))
+    }
+
+    fn at(&self, path: &str, message: String) -> Problem {
+        Problem {
+            position: self.position(path),
+            message,
+        }
+    }
+}
+
+/// Deserializes a file and collects the keys the schema does not know. `version` and
+/// `profiles` are left to `check`, which looks into every profile on its own.
+fn parse(content: &str) -> Result<(KondoConfig, Vec<String>), Problem> {
+    let mut unknown = Vec::new();
+    let config = serde_ignored::deserialize(toml::Deserializer::new(content), |path| {
+        let path = path.to_string();
+        if path != "profiles" && path != "version" {
+            unknown.push(path);
+        }
+    })
+    .map_err(|e| Problem {
+        position: e.span().map(|span| Position::of(content, span.start)),
+        message: e.message().trim().replace('\n', "; "),
+    })?;
+    Ok((config, unknown))
+}
+
+/// Parses a config and checks it: an outdated version, unknown keys, weights that do
+/// not sum to 1.0, extensions claimed by several categories, invalid templates, rules
+/// and date settings, and the same for every profile. Syntax and type errors fail
+/// outright.
+pub fn check(content: &str) -> Result<Vec<Problem>, Problem> {
+    let (config, unknown) = parse(content)?;
+    let locator = Locator::new(content);
+
+    let mut problems = Vec::new();
+    match migrate::version_of(content) {
+        Ok(version) if version < CONFIG_VERSION => problems.push(locator.at(
+            "version",
+            format!(
+                "this is a version {} config, `kondo config migrate` upgrades it to version {}",
+                version, CONFIG_VERSION
+            ),
+        )),
+        Ok(version) if version > CONFIG_VERSION => problems.push(locator.at(
+            "version",
+            format!("version {} is newer than this kondo understands ({})", version, CONFIG_VERSION),
+        )),
+        Ok(_) => {}
+        Err(message) => problems.push(locator.at("version", message)),
+    }
+    problems.extend(
+        unknown
+            .iter()
+            .map(|path| locator.at(path, format!("unknown key `{}`", path))),
+    );
+    let found = validate(&config);
+    problems.extend(found.iter().map(|(path, message)| locator.at(path, message.clone())));
+
+    let mut base: toml::Table = toml::from_str(content).unwrap_or_default();
+    match base.remove("profiles") {
+        Some(toml::Value::Table(profiles)) => {
+            for (name, profile) in profiles {
+                problems.extend(check_profile(&name, profile, &base, &found, &locator));
+            }
+        }
+        Some(_) => problems.push(locator.at(
+            "profiles",
+            "profiles must be tables such as [profiles.photos]".to_string(),
+        )),
+        None => {}
+    }
+
+    Ok(problems)
+}
+
+/// What a profile adds: its unknown keys, values of the wrong type, and whatever is
+/// wrong with the config it makes that the file without it does not already report
+fn check_profile(
+    name: &str,
+    profile: toml::Value,
+    base: &toml::Table,
+    base_found: &[(String, String)],
+    locator: &Locator,
+) -> Vec<Problem> {
+    let prefix = format!("profiles.{}", name);
+    let toml::Value::Table(profile) = profile else {
+        return vec![locator.at(&prefix, format!("profile `{}` must be a table", name))];
+    };
+
+    let mut unknown = Vec::new();
+    let parsed: Result<KondoConfig, toml::de::Error> =
+        serde_ignored::deserialize(toml::Value::Table(profile.clone()), |path| {
+            unknown.push(format!("{}.{}", prefix, path))
+        });
+    if let Err(e) = parsed {
+        let message = e.message().trim().replace('\n', "; ");
+        return vec![locator.at(&prefix, format!("profile `{}`: {}", name, message))];
+    }
+    let mut problems: Vec<Problem> = unknown
+        .iter()
+        .map(|path| locator.at(path, format!("unknown key `{}`", path)))
+        .collect();
+
+    let config = Layers::new(&KondoConfig::default()).and_then(|mut layers| {
+        layers.push(Source::Default, base.clone());
+        layers.push(Source::Profile(name.to_string()), profile);
+        layers.config()
+    });
+    let found = match config {
+        Ok(config) => validate(&config),
+        Err(e) => vec![(String::new(), e.to_string())],
+    };
+    for (path, message) in found {
+        if base_found.contains(&(path.clone(), message.clone())) {
+            continue;
+        }
+        // At the key in the profile if it sets it, otherwise at the profile
+        let position = locator
+            .position(&format!("{}.{}", prefix, path))
+            .or_else(|| locator.position(&prefix));
+        problems.push(Problem {
+            position,
+            message: format!("profile `{}`: {}", name, message),
+        });
+    }
+    problems
+}
+
+/// Checks that need the whole config, as the dotted key each is about and a message
+fn validate(config: &KondoConfig) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+
+    let similarity = &config.similarity_config;
+    if (similarity.levenshtein_weight + similarity.jaccard_weight - 1.0).abs() > WEIGHT_TOLERANCE {
+        found.push((
+            "similarity_config.levenshtein_weight".to_string(),
+            format!(
+                "levenshtein_weight + jaccard_weight must be 1.0, not {}",
+                rounded(similarity.levenshtein_weight + similarity.jaccard_weight)
+            ),
+        ));
+    }
+    let intelligent = &config.intelligent_config;
+    let content_weights = intelligent.filename_similarity_weight + intelligent.content_similarity_weight;
+    if (content_weights - 1.0).abs() > WEIGHT_TOLERANCE {
+        found.push((
+            "intelligent_config.filename_similarity_weight".to_string(),
+            format!(
+                "filename_similarity_weight + content_similarity_weight must be 1.0, not {}",
+                rounded(content_weights)
+            ),
+        ));
+    }
+
+    // Sorted, so the same category is reported as the second claimant every time
+    let categories: BTreeMap<_, _> = config.categories.iter().collect();
+    let mut claimed: HashMap<String, &str> = HashMap::new();
+    for (key, category) in categories {
+        for extension in &category.extensions {
+            match claimed.get(&extension.to_lowercase()) {
+                Some(first) if first != key => found.push((
+                    format!("categories.{}.extensions", key),
+                    format!(
+                        "extension `{}` is in both categories `{}` and `{}`",
+                        extension, first, key
+                    ),
+                )),
+                _ => {
+                    claimed.insert(extension.to_lowercase(), key);
+                }
+            }
+        }
+    }
+
+    if let Err(e) = config.categorize_config().path_templates() {
+        found.push(("categories".to_string(), e.to_string()));
+    }
+    for (i, rule) in config.rules.iter().enumerate() {
+        if let Err(e) = rules::compile_rules(std::slice::from_ref(rule)) {
+            found.push((format!("rules.{}", i), e));
+        }
+    }
+    for (i, pattern) in config.skip_patterns.iter().enumerate() {
+        if let Err(e) = SkipMatcher::new(Path::new(""), std::slice::from_ref(pattern)) {
+            found.push((format!("skip_patterns.{}", i), e));
+        }
+    }
+    if let Err(e) = config.date_config.validate() {
+        found.push(("date_config".to_string(), e));
+    }
+    if let Some(template) = &config.rename_template {
+        if let Err(e) = conflict::validate_rename_template(template) {
+            found.push(("rename_template".to_string(), e));
+        }
+    }
+
+    found
+}
+
+/// Drops float noise such as 1.2999999999999998 from messages
+fn rounded(value: f64) -> f64 {
+    (value * 1e6).round() / 1e6
+}
+
+/// Reads and checks a config file and stacks it on the defaults, with `[profiles.NAME]`
+/// on top if a profile is given. An older file is upgraded in memory, so it works as
+/// before until `kondo config migrate` rewrites it. Syntax and type errors and missing
+/// profiles are fatal; everything else comes back as problems so a run can warn about
+/// them and carry on.
+pub fn load_layers(path: &Path, profile: Option<&str>) -> Result<(Layers, Vec<Problem>), KondoError> {
+    let content = std::fs::read_to_string(path)?;
+    let invalid = |message: String| KondoError::ConfigInvalid(format!("{}: {}", path.display(), message));
+    let problems = check(&content).map_err(|problem| invalid(problem.to_string()))?;
+    let content = migrate::migrate(&content).map_or(content, |migrated| migrated.content);
+    let mut table: toml::Table = toml::from_str(&content).map_err(|e| invalid(e.message().to_string()))?;
+    let profiles = table.remove("profiles");
+
+    let mut layers = Layers::new(&KondoConfig::default())?;
+    layers.push(Source::File(path.to_path_buf()), table);
+    if let Some(name) = profile {
+        match profiles.as_ref().and_then(|profiles| profiles.get(name)) {
+            Some(toml::Value::Table(overlay)) => {
+                layers.push(Source::Profile(name.to_string()), overlay.clone());
+            }
+            _ => {
+                let names: Vec<&str> = profiles
+                    .as_ref()
+                    .and_then(toml::Value::as_table)
+                    .map(|profiles| profiles.keys().map(String::as_str).collect())
+                    .unwrap_or_default();
+                return Err(invalid(if names.is_empty() {
+                    format!("no profile `{}`, the file has no [profiles.NAME] tables", name)
+                } else {
+                    format!("no profile `{}`, choose one of: {}", name, names.join(", "))
+                }));
+            }
+        }
+    }
+
+    Ok((layers, problems))
+}
+
+/// Reads the .kondo.toml in `dir` if there is one. Unknown keys and settings only
+/// kondo.toml may hold are errors rather than warnings, as nobody would see a warning
+/// about a file in some directory they organize.
+pub fn directory_file(dir: &Path) -> Result<Option<(PathBuf, toml::Table)>, KondoError> {
+    let path = dir.join(walk::DIRECTORY_CONFIG);
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let content = std::fs::read_to_string(&path)?;
+    let invalid = |problem: Problem| KondoError::ConfigInvalid(format!("{}: {}", path.display(), problem));
+
+    let (_, unknown) = parse(&content).map_err(invalid)?;
+    let locator = Locator::new(&content);
+    if let Some(key) = unknown.first() {
+        return Err(invalid(locator.at(key, format!("unknown key `{}`", key))));
+    }
+    let table: toml::Table = toml::from_str(&content).unwrap_or_default();
+    if let Some(key) = table.keys().find(|key| !DIRECTORY_KEYS.contains(&key.as_str())) {
+        return Err(invalid(locator.at(
+            key,
+            format!("`{}` can only be set in kondo.toml, not per directory", key),
+        )));
+    }
+
+    Ok(Some((path, table)))
+}
+
+/// Position of the key at a dotted path such as `categories.images.extensions`.
+/// Numbers index arrays of tables; the `?` serde_ignored adds for options is skipped.
+fn locate(document: &ImDocument<&str>, content: &str, path: &str) -> Option<Position> {
+    let mut table: &dyn TableLike = document.as_table();
+    let mut segments = path.split('.').filter(|s| *s != "?").peekable();
+    let mut span = None;
+
+    while let Some(segment) = segments.next() {
+        let (key, item) = table.get_key_value(segment)?;
+        span = key.span();
+        if segments.peek().is_none() {
+            break;
+        }
+        table = match item {
+            Item::ArrayOfTables(array) => {
+                let index: usize = segments.next()?.parse().ok()?;
+                let entry = array.get(index)?;
+                span = entry.span().or(span);
+                entry
+            }
+            Item::Value(toml_edit::Value::Array(array)) => {
+                let index: usize = segments.next()?.parse().ok()?;
+                span = array.get(index)?.span().or(span);
+                break;
+            }
+            _ => item.as_table_like()?,
+        };
+    }
+
+    span.map(|span| Position::of(content, span.start))
+}
+
+/// The commented config written by `kondo config init`
+pub fn template(log_path: &str) -> String {
+    format!(
+        r#"# Kondo File Organizer Configuration
+
+# Format of this file, upgraded by `kondo config migrate`
+version = {}
+
+# Enable smart grouping using filename similarity detection
+# When enabled, files with similar names will be grouped together
+# even if they have different extensions
+enable_smart_grouping = false
+
+# Enable intelligent grouping using ML clustering with TF-IDF
+# This analyzes file content and uses advanced clustering algorithms
+# More powerful than smart grouping but slower
+enable_intelligent_grouping = false
+
+log_file = "{}"
+
+# How files get into their folders: "move", "copy", "hardlink" or "symlink"
+# Every mode except "move" leaves the original layout untouched
+transfer_mode = "move"
+
+# Create the organized folders somewhere else instead of inside the scanned folder
+# destination_root = "~/Archive"
+
+# Categorize mode: read the first bytes of files to sort extensionless files
+# and warn when an extension does not match the content (e.g. a PDF named .jpg)
+sniff_content = false
+
+# When a file with the same name is already at the destination:
+# "rename" (default), "skip", "overwrite", "keep-newer", "keep-larger"
+# or "delete-if-identical" (drop the incoming file when it is byte-identical)
+conflict_policy = "rename"
+
+# Name for renamed files; placeholders: {{stem}}, {{n}}, {{ext}}, {{timestamp}}
+# e.g. "{{stem}} ({{n}}){{ext}}" or "{{stem}}-{{timestamp}}{{ext}}"
+rename_template = "{{stem}}_{{n}}{{ext}}"
+
+# Files and folders every mode leaves alone, written like .gitignore lines: `*` and
+# `**` globs, `!` to bring a file back, a leading `/` to match only at the top.
+# A .kondoignore file in the organized directory adds more.
+skip_patterns = [
+    ".DS_Store",
+    "Thumbs.db",
+    ".git",
+    ".gitignore",
+    "desktop.ini",
+    ".localized",
+    "~$*"
+]
+
+# Smart grouping configuration (used in filename similarity mode)
+[similarity_config]
+# Levenshtein distance threshold (0.0 to 1.0)
+# Higher = stricter matching. Measures character-level similarity.
+levenshtein_threshold = 0.7
+
+# Jaccard similarity threshold (0.0 to 1.0)
+# Higher = stricter matching. Measures word/token overlap.
+jaccard_threshold = 0.5
+
+# Weight for Levenshtein distance in final score (0.0 to 1.0)
+levenshtein_weight = 0.6
+
+# Weight for Jaccard similarity in final score (0.0 to 1.0)
+# Note: levenshtein_weight + jaccard_weight must be 1.0
+jaccard_weight = 0.4
+
+# Minimum similarity score to group files together (0.0 to 1.0)
+# Higher = files must be more similar to be grouped
+# 0.65 is a good balance for most use cases
+min_similarity_score = 0.65
+
+# Intelligent grouping configuration (ML-based clustering)
+[intelligent_config]
+# Maximum number of lines to read from text files for content analysis
+max_lines_to_read = 100
+
+# Minimum number of files required to form a cluster/group
+min_cluster_size = 2
+
+# Maximum number of clusters to create
+# Higher values = more granular grouping
+max_clusters = 20
+
+# Weight for filename similarity (0.0 to 1.0)
+# How much the filename affects grouping decisions
+filename_similarity_weight = 0.3
+
+# Weight for content similarity (0.0 to 1.0)
+# How much file content affects grouping decisions (for text files)
+# Note: filename_similarity_weight + content_similarity_weight must be 1.0
+content_similarity_weight = 0.7
+
+# Minimum similarity threshold for grouping files (0.0 to 1.0)
+# Higher = files must be more similar to be grouped together
+similarity_threshold = 0.65
+
+# Maximum iterations for K-means clustering algorithm
+max_iterations = 100
+
+# Date mode (kondo date): files go into folders for their date
+[date_config]
+# Where the date comes from, first one found wins: "exif" (when a photo or video
+# was taken), "filename" (IMG_20240315.jpg, log-2024-03-15.txt), "modified" or "created"
+sources = ["exif", "filename", "modified"]
+
+# "year" (2024), "month" (2024/03) or "day" (2024/03/15)
+granularity = "month"
+
+# Or an explicit strftime-style layout, which overrides granularity
+# format = "%Y/%m-%B"
+
+# Watch mode (kondo watch DIR): organizes new files as they arrive
+[watch]
+# "categorize" (categories and rules) or "date"
+mode = "categorize"
+
+# Seconds a new file must stay unchanged before it is moved, so downloads
+# still in progress (.part, .crdownload) are left alone
+settle_seconds = 5
+
+# Define your custom categories (used when intelligent grouping is disabled)
+# Each category has:
+#   - extensions: list of file extensions (without dot); an extension belongs
+#     to one category only, check with: kondo config check
+#   - folder_name: optional custom folder name (defaults to category key)
+#   - destination: optional folder that overrides the location, e.g. "~/Pictures"
+#   - path_template: optional sub-path built per file, e.g. "{{folder}}/{{year}}/{{month}}"
+#     placeholders: {{category}} {{folder}} {{ext}} {{year}} {{month}} {{day}} {{created_year}}
+#     {{created_month}} {{created_day}} {{size}} {{initial}} {{parent}}
+#     photos and videos: {{camera}} {{exif_year}} {{exif_month}} {{exif_day}} {{gps}}
+#     audio tags: {{artist}} {{album_artist}} {{album}} {{title}} {{track}} {{disc}} {{genre}}
+#     numbers can be zero-padded, e.g. {{track:02}}; a template ending in ".{{ext}}" also
+#     names the file
+
+[categories.images]
+extensions = ["jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "tiff", "ico", "heic", "raw", "cr2", "nef", "orf", "sr2"]
+folder_name = "Images"
+
+[categories.videos]
+extensions = ["mp4", "avi", "mkv", "mov", "wmv", "flv", "webm", "m4v", "3gp", "mpg", "mpeg", "vob"]
+folder_name = "Videos"
+
+[categories.audio]
+extensions = ["mp3", "wav", "flac", "aac", "ogg", "wma", "m4a", "opus", "aiff", "ape", "alac"]
+folder_name = "Music"
+path_template = "Music/{{artist}}/{{album}}/{{track:02}} - {{title}}.{{ext}}"
+
+[categories.documents]
+extensions = ["pdf", "doc", "docx", "txt", "rtf", "odt", "pages", "tex", "md"]
+folder_name = "Documents"
+
+[categories.spreadsheets]
+extensions = ["xls", "xlsx", "csv", "ods", "numbers"]
+folder_name = "Spreadsheets"
+
+[categories.presentations]
+extensions = ["ppt", "pptx", "odp", "key"]
+folder_name = "Presentations"
+
+[categories.archives]
+extensions = ["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "iso"]
+folder_name = "Archives"
+
+[categories.code]
+extensions = ["rs", "py", "js", "ts", "jsx", "tsx", "html", "css", "scss", "sass", "cpp", "c", "h", "hpp", "java", "go", "php", "rb", "swift", "kt", "dart", "scala", "sh", "bat", "ps1", "r", "lua", "vim"]
+folder_name = "Code"
+
+[categories.data]
+extensions = ["json", "xml", "yaml", "yml", "toml", "ini", "cfg", "conf", "sql", "db", "sqlite", "mdb"]
+folder_name = "Data"
+
+[categories.executables]
+extensions = ["exe", "msi", "app", "deb", "rpm", "dmg", "pkg", "appimage", "run"]
+folder_name = "Applications"
+
+[categories.fonts]
+extensions = ["ttf", "otf", "woff", "woff2", "eot"]
+folder_name = "Fonts"
+
+[categories.ebooks]
+extensions = ["epub", "mobi", "azw", "azw3", "cbr", "cbz"]
+folder_name = "Ebooks"
+
+[categories.3d_models]
+extensions = ["obj", "fbx", "stl", "blend", "dae", "3ds", "max", "gltf", "glb"]
+folder_name = "3D Models"
+
+[categories.design]
+extensions = ["psd", "ai", "xd", "sketch", "fig", "indd", "cdr"]
+folder_name = "Design Files"
+
+# Add your custom categories below:
+# [categories.my_custom_category]
+# extensions = ["ext1", "ext2", "ext3"]
+# folder_name = "My Custom Folder"
+
+# Rules are checked in order before the categories; the first match wins.
+# Conditions: glob, regex, extensions, min_size/max_size ("2GB"),
+# min_age/max_age ("30d"), mime ("video/*"). Try them with: kondo explain <file>
+# [[rules]]
+# name = "Invoices"
+# glob = "invoice*"
+# extensions = ["pdf"]
+# destination = "Finance"
+#
+# [[rules]]
+# name = "Large videos"
+# mime = "video/*"
+# min_size = "2GB"
+# destination = "Large"
+
+"#,
+        CONFIG_VERSION, log_path
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_is_clean_and_problems_have_positions() {
+        assert_eq!(check(&template("/tmp/kondo.log")).unwrap(), Vec::new());
+        let (config, _) = parse(&template("/tmp/kondo.log")).unwrap();
+        assert_eq!(config.categories["executables"].folder_name.as_deref(), Some("Applications"));
+
+        let content = "batch_size = 100\n\
+                       [similarity_config]\n\
+                       levenshtein_weight = 0.9\n\
+                       [categories.archives]\n\
+                       extensions = [\"zip\", \"deb\"]\n\
+                       [categories.executables]\n\
+                       extensions = [\"DEB\"]\n";
+        let messages: Vec<String> = check(content)
+            .unwrap()
+            .iter()
+            .map(Problem::to_string)
+            .collect();
+        assert_eq!(
+            messages,
+            vec![
+                "this is a version 1 config, `kondo config migrate` upgrades it to version 3",
+                "line 1, column 1: unknown key `batch_size`",
+                "line 3, column 1: levenshtein_weight + jaccard_weight must be 1.0, not 1.3",
+                "line 7, column 1: extension `DEB` is in both categories `archives` and `executables`",
+            ]
+        );
+
+        let broken = check("[watch]\nsettle_seconds = \"soon\"\n").unwrap_err();
+        assert_eq!(broken.position, Some(Position { line: 2, column: 18 }));
+    }
+
+    #[test]
+    fn test_profiles_and_directory_files_layer_with_sources() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kondo.toml");
+        std::fs::write(
+            &path,
+            "version = 3\n\
+             skip_patterns = [\"Thumbs.db\"]\n\
+             [similarity_config]\n\
+             jaccard_threshold = 0.4\n\
+             [profiles.photos.similarity_config]\n\
+             min_similarity_score = 0.8\n\
+             [profiles.photos.categories.raw]\n\
+             extensions = [\"cr2\"]\n",
+        )
+        .unwrap();
+
+        let plain = load_layers(&path, None).unwrap().0.config().unwrap();
+        assert!(plain.categories.contains_key("images"));
+        let (mut layers, problems) = load_layers(&path, Some("photos")).unwrap();
+        assert_eq!(problems, Vec::new());
+        // Categories are replaced as a whole, other tables merge key by key
+        let photos = layers.config().unwrap();
+        assert_eq!(photos.categories.keys().collect::<Vec<_>>(), vec!["raw"]);
+        assert_eq!(photos.similarity_config.jaccard_threshold, 0.4);
+        assert_eq!(photos.similarity_config.min_similarity_score, 0.8);
+        assert!(load_layers(&path, Some("music")).is_err());
+
+        let vars = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        let ignored = layers
+            .push_env(vars(&[
+                ("KONDO_CONFIG", "/elsewhere.toml"),
+                ("KONDO_SNIFF_CONTENT", "yes"),
+                ("KONDO_SIMILARITY_CONFIG__MIN_SIMILARITY_SCORE", "0.9"),
+                ("KONDO_DESTINATION_ROOT", "~/Archive"),
+                ("KONDO_FOO", "1"),
+                ("KONDO_WATCH__SOON", "1"),
+                ("HOME", "/home/someone"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            ignored,
+            vec![
+                "KONDO_FOO: `foo` is not a setting, ignored",
+                "KONDO_WATCH__SOON: `watch.soon` is not a setting, ignored",
+            ]
+        );
+        let configured = layers.config().unwrap();
+        assert!(configured.sniff_content);
+        assert_eq!(configured.similarity_config.min_similarity_score, 0.9);
+        assert_eq!(configured.destination_root.as_deref(), Some("~/Archive"));
+        for (name, value) in [
+            ("KONDO_SKIP_PATTERNS", "*.tmp"),
+            ("KONDO_WATCH__SETTLE_SECONDS", "soon"),
+            ("KONDO_TRANSFER_MODE", "teleport"),
+        ] {
+            assert!(layers.clone().push_env(vars(&[(name, value)])).is_err(), "{}", name);
+        }
+
+        std::fs::write(dir.path().join(walk::DIRECTORY_CONFIG), "skip_patterns = []\n").unwrap();
+        let (directory, table) = directory_file(dir.path()).unwrap().unwrap();
+        layers.push(Source::Directory(directory.clone()), table);
+        let sources: HashMap<String, Source> = layers
+            .effective()
+            .unwrap()
+            .into_iter()
+            .map(|setting| (setting.key, setting.source))
+            .collect();
+        assert_eq!(sources["skip_patterns"], Source::Directory(directory));
+        assert_eq!(sources["similarity_config.jaccard_threshold"], Source::File(path.clone()));
+        assert_eq!(
+            sources["similarity_config.min_similarity_score"],
+            Source::Environment("KONDO_SIMILARITY_CONFIG__MIN_SIMILARITY_SCORE".to_string())
+        );
+        assert_eq!(sources["categories.raw.extensions"], Source::Profile("photos".to_string()));
+        assert_eq!(sources["watch.settle_seconds"], Source::Default);
+        assert_eq!(sources["sniff_content"], Source::Environment("KONDO_SNIFF_CONTENT".to_string()));
+
+        std::fs::write(dir.path().join(walk::DIRECTORY_CONFIG), "log_file = \"x.log\"\n").unwrap();
+        let refused = directory_file(dir.path()).unwrap_err().to_string();
+        assert!(refused.ends_with("line 1, column 1: `log_file` can only be set in kondo.toml, not per directory"));
+    }
+}
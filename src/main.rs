@@ -1,463 +1,382 @@
 use chrono::Local;
-use serde::Deserialize;
+use clap::CommandFactory;
 use std::env;
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 
-mod organizer;
-use organizer::categorise::{FileOrganizerConfig, TuiApp};
-use organizer::filename::{FilenameTuiApp, SimilarityConfig};
-use organizer::intelligent::{IntelligentConfig, IntelligentTuiApp};
-
-/// Main configuration structure that includes all settings
-#[derive(Debug, Clone, Deserialize)]
-pub struct KondoConfig {
-    #[serde(default)]
-    pub log_file: Option<String>,
-
-    #[serde(default)]
-    pub enable_smart_grouping: bool,
-
-    #[serde(default)]
-    pub enable_intelligent_grouping: bool,
+mod cli;
+mod config;
+mod migrate;
+use config::KondoConfig;
+use cli::{Cli, Command, ConfigCommand, GlobalArgs, OrganizeOptions, TargetArgs};
 
-    #[serde(default)]
-    pub similarity_config: SimilarityConfigToml,
-
-    #[serde(default)]
-    pub intelligent_config: IntelligentConfigToml,
+mod organizer;
+mod output;
+use output::{DedupeSummary, Document, OutputFormat, PlanSummary, Status};
+use organizer::categorise::{self, FileOrganizer, FileOrganizerConfig, TuiApp};
+use organizer::conflict::{self, ConflictOptions};
+use organizer::date::{self, DateSource};
+use organizer::dedupe::{self, DedupeAction, KeepPolicy};
+use organizer::error::KondoError;
+use organizer::filename::{self, organize_by_similarity, plan_by_similarity, FilenameTuiApp, SimilarityConfig};
+use organizer::intelligent::{
+    organize_files_intelligently, plan_group_moves, IntelligentConfig, IntelligentTuiApp,
+};
+use organizer::journal::{self, Journal};
+use organizer::plan::{self, Action, ExecuteOptions, Outcome, Plan, PlanOptions};
+use organizer::transfer::{self, TransferMode};
+use organizer::walk::WalkOptions;
+use organizer::watch::{self, WatchEvent, WatchMode};
+
+/// Config file given with `--config`, read instead of the default one
+static CONFIG_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Set by `--verbose` to echo log messages to stderr
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// An XDG base directory: the variable if it holds an absolute path (relative ones are
+/// ignored, as the spec asks), otherwise `fallback` in the home directory
+fn xdg_dir(variable: &str, fallback: &str) -> Option<PathBuf> {
+    env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|dir| dir.is_absolute())
+        .or_else(|| dirs::home_dir().map(|home| home.join(fallback)))
 }
 
-/// TOML representation of similarity config
-#[derive(Debug, Clone, Deserialize)]
-pub struct SimilarityConfigToml {
-    #[serde(default = "default_levenshtein_threshold")]
-    pub levenshtein_threshold: f64,
-
-    #[serde(default = "default_jaccard_threshold")]
-    pub jaccard_threshold: f64,
-
-    #[serde(default = "default_levenshtein_weight")]
-    pub levenshtein_weight: f64,
-
-    #[serde(default = "default_jaccard_weight")]
-    pub jaccard_weight: f64,
-
-    #[serde(default = "default_min_similarity_score")]
-    pub min_similarity_score: f64,
+fn no_home() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "Could not determine the home directory",
+    )
 }
 
-/// TOML representation of intelligent config
-#[derive(Debug, Clone, Deserialize)]
-pub struct IntelligentConfigToml {
-    #[serde(default = "default_max_lines_to_read")]
-    pub max_lines_to_read: usize,
-
-    #[serde(default = "default_min_cluster_size")]
-    pub min_cluster_size: usize,
-
-    #[serde(default = "default_max_clusters")]
-    pub max_clusters: usize,
-
-    #[serde(default = "default_filename_similarity_weight")]
-    pub filename_similarity_weight: f64,
+/// Gets the config directory: Windows: %APPDATA%\kondo, otherwise $XDG_CONFIG_HOME/kondo
+/// (~/.config/kondo). Nothing is created until a file is written there.
+fn get_config_dir() -> std::io::Result<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        dirs::config_dir()
+    } else {
+        xdg_dir("XDG_CONFIG_HOME", ".config")
+    };
+    base.map(|dir| dir.join("kondo")).ok_or_else(no_home)
+}
 
-    #[serde(default = "default_content_similarity_weight")]
-    pub content_similarity_weight: f64,
+/// Gets the directory for the log and the move journal: Windows: %APPDATA%\kondo,
+/// otherwise $XDG_STATE_HOME/kondo (~/.local/state/kondo)
+fn get_state_dir() -> std::io::Result<PathBuf> {
+    if cfg!(target_os = "windows") {
+        return get_config_dir();
+    }
+    xdg_dir("XDG_STATE_HOME", ".local/state")
+        .map(|dir| dir.join("kondo"))
+        .ok_or_else(no_home)
+}
 
-    #[serde(default = "default_similarity_threshold")]
-    pub similarity_threshold: f64,
+/// Gets the config file path: the `--config` file or `KONDO_CONFIG` if given,
+/// otherwise kondo.toml in the config directory
+fn get_config_path() -> std::io::Result<PathBuf> {
+    if let Some(path) = CONFIG_OVERRIDE.get() {
+        return Ok(path.clone());
+    }
+    let config_dir = get_config_dir()?;
+    Ok(config_dir.join("kondo.toml"))
+}
 
-    #[serde(default = "default_max_iterations")]
-    pub max_iterations: usize,
+/// Gets the default log file path: kondo.log in the state directory
+fn get_default_log_path() -> std::io::Result<PathBuf> {
+    let state_dir = get_state_dir()?;
+    Ok(state_dir.join("kondo.log"))
 }
 
-// Default functions for serde - Similarity Config
-fn default_levenshtein_threshold() -> f64 { 0.7 }
-fn default_jaccard_threshold() -> f64 { 0.5 }
-fn default_levenshtein_weight() -> f64 { 0.6 }
-fn default_jaccard_weight() -> f64 { 0.4 }
-fn default_min_similarity_score() -> f64 { 0.65 }
+/// Gets the move journal path: journal.jsonl in the state directory. A journal older
+/// versions kept in the config directory stays in use until the state directory has one.
+fn get_journal_path() -> std::io::Result<PathBuf> {
+    let journal = get_state_dir()?.join("journal.jsonl");
+    let legacy = get_config_dir()?.join("journal.jsonl");
+    if !journal.exists() && legacy.is_file() {
+        return Ok(legacy);
+    }
+    Ok(journal)
+}
 
-// Default functions for serde - Intelligent Config
-fn default_max_lines_to_read() -> usize { 100 }
-fn default_min_cluster_size() -> usize { 2 }
-fn default_max_clusters() -> usize { 20 }
-fn default_filename_similarity_weight() -> f64 { 0.3 }
-fn default_content_similarity_weight() -> f64 { 0.7 }
-fn default_similarity_threshold() -> f64 { 0.65 }
-fn default_max_iterations() -> usize { 100 }
+/// Opens the move journal for a new run. A missing journal never blocks organizing.
+fn open_journal(mode: &str, kondo_config: &KondoConfig) -> Option<Journal> {
+    let journal = get_journal_path().and_then(|path| Journal::start_run(&path, mode));
 
-impl Default for SimilarityConfigToml {
-    fn default() -> Self {
-        Self {
-            levenshtein_threshold: 0.7,
-            jaccard_threshold: 0.5,
-            levenshtein_weight: 0.6,
-            jaccard_weight: 0.4,
-            min_similarity_score: 0.65,
+    match journal {
+        Ok(journal) => {
+            log_to_file(
+                &kondo_config.log_file,
+                &format!("Journaling moves as run {}", journal.run_id()),
+            );
+            Some(journal)
+        }
+        Err(e) => {
+            eprintln!("!  Could not open move journal, undo will not be available: {}", e);
+            log_to_file(
+                &kondo_config.log_file,
+                &format!("Could not open move journal: {}", e),
+            );
+            None
         }
     }
 }
 
-impl Default for IntelligentConfigToml {
-    fn default() -> Self {
-        Self {
-            max_lines_to_read: 100,
-            min_cluster_size: 2,
-            max_clusters: 20,
-            filename_similarity_weight: 0.3,
-            content_similarity_weight: 0.7,
-            similarity_threshold: 0.65,
-            max_iterations: 100,
+/// Loads kondo.toml with the `--profile` table and `KONDO_*` variables on top; without
+/// the file the defaults apply, and nothing is written until `kondo config init`. A file
+/// that does not parse is an error rather than a silent fallback to defaults; other
+/// problems are warned about once and left to `kondo config check`.
+fn load_kondo_config(profile: Option<&str>) -> Result<KondoConfig, KondoError> {
+    let config_path = get_config_path()?;
+
+    let mut layers = if config_path.exists() {
+        let (layers, problems) = config::load_layers(&config_path, profile)?;
+        if !problems.is_empty() {
+            eprintln!(
+                "!  {} has {} problem(s), run `kondo config check` to see them",
+                config_path.display(),
+                problems.len()
+            );
         }
+        layers
+    } else if let Some(profile) = profile {
+        return Err(KondoError::ConfigInvalid(format!(
+            "no profile `{}`, {} does not exist yet",
+            profile,
+            config_path.display()
+        )));
+    } else {
+        let log_path = get_default_log_path()?.to_string_lossy().to_string();
+        config::Layers::new(&KondoConfig {
+            log_file: Some(log_path),
+            ..KondoConfig::default()
+        })?
+    };
+    for warning in layers.push_env(env::vars())? {
+        eprintln!("!  {}", warning);
     }
-}
 
-impl Default for KondoConfig {
-    fn default() -> Self {
-        Self {
-            log_file: None,
-            enable_smart_grouping: false,
-            enable_intelligent_grouping: false,
-            similarity_config: SimilarityConfigToml::default(),
-            intelligent_config: IntelligentConfigToml::default(),
+    let mut config = layers.config()?;
+    match config.log_file.as_deref() {
+        Some("none") | Some("") => config.log_file = None,
+        Some(log_file) => {
+            // A relative log path is relative to the config directory
+            if let Some(config_dir) = config_path.parent().filter(|_| Path::new(log_file).is_relative()) {
+                config.log_file = Some(config_dir.join(log_file).to_string_lossy().to_string());
+            }
         }
+        None => {}
     }
+    Ok(config)
 }
 
-/// Convert TOML config to runtime config
-impl From<SimilarityConfigToml> for SimilarityConfig {
-    fn from(toml_config: SimilarityConfigToml) -> Self {
-        SimilarityConfig {
-            levenshtein_threshold: toml_config.levenshtein_threshold,
-            jaccard_threshold: toml_config.jaccard_threshold,
-            levenshtein_weight: toml_config.levenshtein_weight,
-            jaccard_weight: toml_config.jaccard_weight,
-            min_similarity_score: toml_config.min_similarity_score,
-        }
+/// Log a message to the configured log file, and to stderr with `--verbose`
+fn log_to_file(log_path: &Option<String>, message: &str) {
+    if VERBOSE.load(Ordering::Relaxed) {
+        eprintln!("{}", message.trim_end());
     }
-}
 
-impl From<IntelligentConfigToml> for IntelligentConfig {
-    fn from(toml_config: IntelligentConfigToml) -> Self {
-        IntelligentConfig {
-            max_lines_to_read: toml_config.max_lines_to_read,
-            min_cluster_size: toml_config.min_cluster_size,
-            max_clusters: toml_config.max_clusters,
-            filename_similarity_weight: toml_config.filename_similarity_weight,
-            content_similarity_weight: toml_config.content_similarity_weight,
-            similarity_threshold: toml_config.similarity_threshold,
-            max_iterations: toml_config.max_iterations,
+    if let Some(path_str) = log_path {
+        let path = PathBuf::from(path_str);
+        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+        let log_message = format!("[{}] {}\n", timestamp, message);
+
+        // The log directory is only created once there is something to write
+        let mut file = OpenOptions::new().create(true).append(true).open(&path);
+        if file.as_ref().is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound) {
+            if let Some(parent) = path.parent() {
+                let _ = fs::create_dir_all(parent);
+            }
+            file = OpenOptions::new().create(true).append(true).open(&path);
+        }
+        if let Ok(mut file) = file {
+            let _ = file.write_all(log_message.as_bytes());
         }
     }
 }
 
-/// Gets the config directory path in a cross-platform way
-fn get_config_dir() -> std::io::Result<PathBuf> {
-    let config_dir = if cfg!(target_os = "windows") {
-        // Windows: Use %APPDATA%\kondo
-        let appdata = env::var("APPDATA").map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Could not determine APPDATA directory",
-            )
-        })?;
-        PathBuf::from(appdata).join("kondo")
-    } else {
-        // Unix/Linux/macOS: Use ~/.config/kondo
-        let home = env::var("HOME").map_err(|_| {
-            std::io::Error::new(
-                std::io::ErrorKind::NotFound,
-                "Could not determine HOME directory",
-            )
-        })?;
-        PathBuf::from(home).join(".config").join("kondo")
+fn invalid_input(message: String) -> KondoError {
+    KondoError::InvalidInput(message)
+}
+
+/// Resolves a target directory, defaulting to the current directory.
+/// Fails if it does not exist.
+fn resolve_target_dir(dir: Option<&Path>) -> Result<PathBuf, KondoError> {
+    let target_dir = match dir {
+        Some(path) => path.to_path_buf(),
+        None => env::current_dir()
+            .map_err(|e| KondoError::Io(format!("could not get the current directory: {}", e)))?,
     };
 
-    if !config_dir.exists() {
-        fs::create_dir_all(&config_dir)?;
-        println!("Created config directory: {}", config_dir.display());
+    if !target_dir.exists() {
+        return Err(invalid_input(format!(
+            "directory does not exist: {}",
+            target_dir.display()
+        )));
     }
 
-    Ok(config_dir)
+    Ok(target_dir)
 }
 
-/// Gets the config file path: Windows: %APPDATA%\kondo\kondo.toml, Unix: ~/.config/kondo/kondo.toml
-fn get_config_path() -> std::io::Result<PathBuf> {
-    let config_dir = get_config_dir()?;
-    Ok(config_dir.join("kondo.toml"))
+/// Applies `--transfer`, `--on-conflict` and `--rename-template` on top of the config
+fn apply_options(kondo_config: &mut KondoConfig, options: &OrganizeOptions) -> Result<(), KondoError> {
+    if let Some(mode) = options.transfer {
+        kondo_config.transfer_mode = mode;
+    }
+    if let Some(policy) = options.on_conflict {
+        kondo_config.conflict_policy = policy;
+    }
+    if let Some(template) = &options.rename_template {
+        kondo_config.rename_template = Some(template.clone());
+    }
+    if let Some(template) = &kondo_config.rename_template {
+        conflict::validate_rename_template(template).map_err(invalid_input)?;
+    }
+    Ok(())
 }
 
-/// Gets the default log file path: Windows: %APPDATA%\kondo\kondo.log, Unix: ~/.config/kondo/kondo.log
-fn get_default_log_path() -> std::io::Result<PathBuf> {
-    let config_dir = get_config_dir()?;
-    Ok(config_dir.join("kondo.log"))
+/// Builds planning options from `-r/--recursive`, `--max-depth N`, `--dest DIR`
+/// (or `destination_root`) and the conflict settings. Folders recorded in the journal and the destination
+/// root itself are treated as already organized.
+fn plan_options(options: &OrganizeOptions, kondo_config: &KondoConfig) -> PlanOptions {
+    let walk = match (options.recursive, options.max_depth) {
+        (false, None) => WalkOptions::default(),
+        (_, max_depth) => WalkOptions::recursive(max_depth),
+    };
+
+    let walk = match get_journal_path().and_then(|path| journal::created_dirs(&path)) {
+        Ok(dirs) => walk.with_organized_dirs(dirs),
+        Err(_) => walk,
+    };
+
+    let destination_root = options
+        .dest
+        .as_deref()
+        .or(kondo_config.destination_root.as_deref())
+        .map(|dest| {
+            let dest = plan::expand_home(dest);
+            std::path::absolute(&dest).unwrap_or(dest)
+        });
+
+    PlanOptions {
+        walk: walk
+            .with_organized_dirs(destination_root.clone())
+            .with_skip_patterns(&kondo_config.skip_patterns),
+        destination_root,
+        conflict: ConflictOptions {
+            policy: kondo_config.conflict_policy,
+            rename_template: kondo_config
+                .rename_template
+                .clone()
+                .unwrap_or_else(|| conflict::DEFAULT_RENAME_TEMPLATE.to_string()),
+        },
+    }
 }
 
-/// Load configuration from kondo.toml or create default
-fn load_kondo_config() -> KondoConfig {
-    let config_path = match get_config_path() {
-        Ok(path) => path,
-        Err(e) => {
-            eprintln!("Warning: Could not determine config path: {}", e);
-            return KondoConfig::default();
-        }
+/// Runs an organizing command for every target directory in turn. All directories are
+/// checked before the first one is touched, and a failing directory does not stop the
+/// others; the command fails if any of them did. Each directory gets the config with
+/// its .kondo.toml on top, then `configure` applies the command line.
+fn organize_dirs(
+    command: &str,
+    target: &TargetArgs,
+    options: &OrganizeOptions,
+    global: &GlobalArgs,
+    kondo_config: &KondoConfig,
+    configure: impl Fn(&mut KondoConfig) -> Result<(), KondoError>,
+    mut run: impl FnMut(PathBuf, &KondoConfig, PlanOptions) -> Result<Status, KondoError>,
+) -> Result<Status, KondoError> {
+    let format = global.format();
+    // Bad command line values fail here rather than once per directory
+    let mut configured = kondo_config.clone();
+    configure(&mut configured)?;
+
+    let dirs: Vec<PathBuf> = if target.dirs.is_empty() {
+        vec![resolve_target_dir(None)?]
+    } else {
+        target
+            .dirs
+            .iter()
+            .map(|dir| resolve_target_dir(Some(dir)))
+            .collect::<Result<_, _>>()?
     };
+    if dirs.len() > 1 && target.plan_out.is_some() {
+        return Err(invalid_input(
+            "--plan-out writes one plan, give a single directory".to_string(),
+        ));
+    }
 
-    if config_path.exists() {
-        // Try to read and parse config using proper TOML deserialization
-        match fs::read_to_string(&config_path) {
-            Ok(content) => {
-                match toml::from_str::<KondoConfig>(&content) {
-                    Ok(mut config) => {
-                        // Convert relative log path to absolute if needed
-                        if let Some(ref log_file) = config.log_file {
-                            if log_file != "none" && !log_file.is_empty() {
-                                let log_path = PathBuf::from(log_file);
-                                // If it's a relative path, make it absolute relative to config dir
-                                if log_path.is_relative() {
-                                    if let Ok(config_dir) = get_config_dir() {
-                                        config.log_file = Some(config_dir.join(log_path).to_string_lossy().to_string());
-                                    }
-                                }
-                            } else {
-                                config.log_file = None;
-                            }
-                        }
-
-                        // println!("✓ Loaded configuration from: {}", config_path.display());
-                        return config;
-                    }
-                    Err(e) => {
-                        eprintln!("Warning: Could not parse config file: {}", e);
-                        eprintln!("Using default configuration...");
-                    }
-                }
+    let mut run = |dir: &Path| -> Result<Status, KondoError> {
+        let dir_config = match kondo_config.for_directory(dir)? {
+            Some((path, mut dir_config)) => {
+                log_to_file(&kondo_config.log_file, &format!("Using {}", path.display()));
+                configure(&mut dir_config)?;
+                dir_config
             }
-            Err(e) => {
-                eprintln!("Warning: Could not read config file: {}", e);
+            None => configured.clone(),
+        };
+        let plan_options = plan_options(options, &dir_config);
+        run(dir.to_path_buf(), &dir_config, plan_options)
+    };
+    if dirs.len() == 1 && format.is_text() {
+        return run(&dirs[0]);
+    }
+
+    // Machine output reports a failed directory as a document of its own and carries on
+    let mut status = None;
+    let mut failed = 0;
+    for (i, dir) in dirs.iter().enumerate() {
+        if format.is_text() {
+            if i > 0 {
+                println!();
             }
+            println!("━━ {} ━━", dir.display());
         }
-    } else {
-        // Create default config file with logging enabled
-        let default_log_path = match get_default_log_path() {
-            Ok(path) => path,
+        let dir_status = match run(dir) {
+            Ok(dir_status) => dir_status,
             Err(e) => {
-                eprintln!("Warning: Could not determine log path: {}", e);
-                return KondoConfig::default();
+                if format.is_text() {
+                    eprintln!("✗ Error in {}: {}", dir.display(), e);
+                } else {
+                    let document = Document::failed(command, Some(dir), global.dry_run, e.clone());
+                    output::emit(format, &document);
+                }
+                log_to_file(
+                    &kondo_config.log_file,
+                    &format!("Error in {}: {}", dir.display(), e),
+                );
+                failed += 1;
+                Status::Failed
             }
         };
-
-        // Convert path to string with forward slashes for cross-platform TOML compatibility
-        let log_path_str = default_log_path
-            .to_string_lossy()
-            .replace('\\', "/");
-
-        let config_content = format!(
-            r#"# Kondo File Organizer Configuration
-batch_size = 100
-
-# Enable smart grouping using filename similarity detection
-# When enabled, files with similar names will be grouped together
-# even if they have different extensions
-enable_smart_grouping = false
-
-# Enable intelligent grouping using ML clustering with TF-IDF
-# This analyzes file content and uses advanced clustering algorithms
-# More powerful than smart grouping but slower
-enable_intelligent_grouping = false
-
-log_file = "{}"
-
-# Files/patterns to skip during organization
-skip_patterns = [
-    ".DS_Store",
-    "Thumbs.db",
-    ".git",
-    ".gitignore",
-    "desktop.ini",
-    ".localized"
-]
-
-# Smart grouping configuration (used in filename similarity mode)
-[similarity_config]
-# Levenshtein distance threshold (0.0 to 1.0)
-# Higher = stricter matching. Measures character-level similarity.
-levenshtein_threshold = 0.7
-
-# Jaccard similarity threshold (0.0 to 1.0)
-# Higher = stricter matching. Measures word/token overlap.
-jaccard_threshold = 0.5
-
-# Weight for Levenshtein distance in final score (0.0 to 1.0)
-levenshtein_weight = 0.6
-
-# Weight for Jaccard similarity in final score (0.0 to 1.0)
-# Note: levenshtein_weight + jaccard_weight should = 1.0
-jaccard_weight = 0.4
-
-# Minimum similarity score to group files together (0.0 to 1.0)
-# Higher = files must be more similar to be grouped
-# 0.65 is a good balance for most use cases
-min_similarity_score = 0.65
-
-# Intelligent grouping configuration (ML-based clustering)
-[intelligent_config]
-# Maximum number of lines to read from text files for content analysis
-max_lines_to_read = 100
-
-# Minimum number of files required to form a cluster/group
-min_cluster_size = 2
-
-# Maximum number of clusters to create
-# Higher values = more granular grouping
-max_clusters = 20
-
-# Weight for filename similarity (0.0 to 1.0)
-# How much the filename affects grouping decisions
-filename_similarity_weight = 0.3
-
-# Weight for content similarity (0.0 to 1.0)
-# How much file content affects grouping decisions (for text files)
-# Note: filename_similarity_weight + content_similarity_weight should = 1.0
-content_similarity_weight = 0.7
-
-# Minimum similarity threshold for grouping files (0.0 to 1.0)
-# Higher = files must be more similar to be grouped together
-similarity_threshold = 0.65
-
-# Maximum iterations for K-means clustering algorithm
-max_iterations = 100
-
-# Define your custom categories (used when intelligent grouping is disabled)
-# Each category has:
-#   - extensions: list of file extensions (without dot)
-#   - folder_name: optional custom folder name (defaults to category key)
-
-[categories.images]
-extensions = ["jpg", "jpeg", "png", "gif", "bmp", "svg", "webp", "tiff", "ico", "heic", "raw", "cr2", "nef", "orf", "sr2"]
-folder_name = "Images"
-
-[categories.videos]
-extensions = ["mp4", "avi", "mkv", "mov", "wmv", "flv", "webm", "m4v", "3gp", "mpg", "mpeg", "vob"]
-folder_name = "Videos"
-
-[categories.audio]
-extensions = ["mp3", "wav", "flac", "aac", "ogg", "wma", "m4a", "opus", "aiff", "ape", "alac"]
-folder_name = "Music"
-
-[categories.documents]
-extensions = ["pdf", "doc", "docx", "txt", "rtf", "odt", "pages", "tex", "md", "epub", "mobi"]
-folder_name = "Documents"
-
-[categories.spreadsheets]
-extensions = ["xls", "xlsx", "csv", "ods", "numbers"]
-folder_name = "Spreadsheets"
-
-[categories.presentations]
-extensions = ["ppt", "pptx", "odp", "key"]
-folder_name = "Presentations"
-
-[categories.archives]
-extensions = ["zip", "rar", "7z", "tar", "gz", "bz2", "xz", "dmg", "pkg", "deb", "rpm", "iso"]
-folder_name = "Archives"
-
-[categories.code]
-extensions = ["rs", "py", "js", "ts", "jsx", "tsx", "html", "css", "scss", "sass", "cpp", "c", "h", "hpp", "java", "go", "php", "rb", "swift", "kt", "dart", "scala", "sh", "bat", "ps1", "r", "lua", "vim"]
-folder_name = "Code"
-
-[categories.data]
-extensions = ["json", "xml", "yaml", "yml", "toml", "ini", "cfg", "conf", "sql", "db", "sqlite", "mdb"]
-folder_name = "Data"
-
-[categories.executables]
-extensions = ["exe", "msi", "app", "deb", "rpm", "dmg", "pkg", "appimage", "run"]
-folder_name = "Applications"
-
-[categories.fonts]
-extensions = ["ttf", "otf", "woff", "woff2", "eot"]
-folder_name = "Fonts"
-
-[categories.ebooks]
-extensions = ["epub", "mobi", "azw", "azw3", "cbr", "cbz"]
-folder_name = "Ebooks"
-
-[categories.3d_models]
-extensions = ["obj", "fbx", "stl", "blend", "dae", "3ds", "max", "gltf", "glb"]
-folder_name = "3D Models"
-
-[categories.design]
-extensions = ["psd", "ai", "xd", "sketch", "fig", "indd", "cdr"]
-folder_name = "Design Files"
-
-# Add your custom categories below:
-# [categories.my_custom_category]
-# extensions = ["ext1", "ext2", "ext3"]
-# folder_name = "My Custom Folder"
-
-"#,
-            log_path_str
-        );
-
-        if let Err(e) = fs::write(&config_path, config_content) {
-            eprintln!("!  Could not create config file: {}", e);
-        } else {
-            println!("✓ Created default config at: {}", config_path.display());
-        }
-
-        return KondoConfig {
-            log_file: Some(log_path_str),
-            enable_smart_grouping: false,
-            enable_intelligent_grouping: false,
-            similarity_config: SimilarityConfigToml::default(),
-            intelligent_config: IntelligentConfigToml::default(),
-        };
+        status = Some(status.map_or(dir_status, |s: Status| s.combine(dir_status)));
     }
 
-    KondoConfig::default()
-}
-
-/// Log a message to the configured log file
-fn log_to_file(log_path: &Option<String>, message: &str) {
-    if let Some(path_str) = log_path {
-        let path = PathBuf::from(path_str);
-        let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-        let log_message = format!("[{}] {}\n", timestamp, message);
-
-        if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(&path) {
-            let _ = file.write_all(log_message.as_bytes());
-        }
+    if failed > 0 && format.is_text() {
+        eprintln!("✗ {} of {} directories failed", failed, dirs.len());
     }
+    Ok(status.unwrap_or(Status::Success))
 }
 
-fn print_help() {
-    println!("╔═══════════════════════════════════════════════════╗");
-    println!("║                                                   ║");
-    println!("║   ██╗  ██╗ ██████╗ ███╗   ██╗██████╗  ██████╗     ║");
-    println!("║   ██║ ██╔╝██╔═══██╗████╗  ██║██╔══██╗██╔═══██╗    ║");
-    println!("║   █████╔╝ ██║   ██║██╔██╗ ██║██║  ██║██║   ██║    ║");
-    println!("║   ██╔═██╗ ██║   ██║██║╚██╗██║██║  ██║██║   ██║    ║");
-    println!("║   ██║  ██╗╚██████╔╝██║ ╚████║██████╔╝╚██████╔╝    ║");
-    println!("║   ╚═╝  ╚═╝ ╚═════╝ ╚═╝  ╚═══╝╚═════╝  ╚═════╝     ║");
-    println!("║    ML-Powered • Blazingly Fast • Beautiful TUI    ║");
-    println!("║                                                   ║");
-    println!("╚═══════════════════════════════════════════════════╝");
-    println!("USAGE:");
-    println!("    kondo [OPTIONS] [DIRECTORY]");
-    println!("\nOPTIONS:");
-    println!("    -c, --categorize    Organize files by category (images, videos, documents, etc.)");
-    println!("    -f, --filename      Group similar files based on filename patterns");
-    println!("    -i, --intelligent   Use ML-based clustering with TF-IDF content analysis");
-    println!("    -nui, --no-ui       Skip UI and automatically organize files");
-    println!("    -h, --help          Show this help message");
+/// The category config from kondo.toml, refused if its templates or rules are invalid
+fn load_categorize_config(kondo_config: &KondoConfig) -> Result<FileOrganizerConfig, KondoError> {
+    let config = kondo_config.categorize_config();
+    config.validate().map_err(KondoError::ConfigInvalid)?;
+    Ok(config)
 }
 
-fn run_categorize_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: bool) -> std::io::Result<()> {
-    let config_path = get_config_path()?;
-
+fn run_categorize_mode(
+    target_dir: PathBuf,
+    kondo_config: &KondoConfig,
+    no_ui: bool,
+    dry_run: bool,
+    plan_options: PlanOptions,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
     log_to_file(
         &kondo_config.log_file,
         &format!("=== Starting Kondo (Categorize Mode - No UI: {}) ===", no_ui),
@@ -467,53 +386,65 @@ fn run_categorize_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: b
         &format!("Target directory: {}", target_dir.display()),
     );
 
-    println!("Kondo - Categorize Mode");
+    if format.is_text() {
+        println!("Kondo - Categorize Mode");
+    }
 
-    // Load or create config
-    let config = if config_path.exists() {
-        match FileOrganizerConfig::load_from_file(&config_path) {
-            Ok(cfg) => {
-                log_to_file(&kondo_config.log_file, "Config loaded successfully");
-                cfg
-            }
-            Err(e) => {
-                eprintln!("!  Failed to load config: {}", e);
-                println!("Using default configuration...");
-                log_to_file(
-                    &kondo_config.log_file,
-                    &format!("Failed to load config: {}", e),
-                );
-                FileOrganizerConfig::default()
-            }
-        }
+    let config = load_categorize_config(kondo_config)?;
+    let journal = if dry_run {
+        None
     } else {
-        println!("ℹ  No config file found, creating default config...");
-        let default_config = FileOrganizerConfig::default();
+        open_journal("categorize", kondo_config)
+    };
+    let status = |result: &categorise::OrganizeResult| {
+        Status::from_counts(
+            result.files_organized + result.duplicates_deleted,
+            result.files_failed,
+        )
+    };
 
-        if let Err(e) = default_config.save_to_file(&config_path) {
-            eprintln!("! Could not save default config: {}", e);
-            log_to_file(
-                &kondo_config.log_file,
-                &format!("Could not save default config: {}", e),
-            );
-        } else {
-            println!("✓ Default config created at: {}", config_path.display());
-            println!("   Edit this file to customize categories!");
-            log_to_file(&kondo_config.log_file, "Created default config");
+    if !format.is_text() {
+        let target_dir = std::path::absolute(&target_dir)?;
+        let mut organizer = FileOrganizer::new(config).with_plan_options(plan_options);
+        if let Some(journal) = journal {
+            organizer = organizer.with_journal(journal);
+        }
+        if let Some(sink) = format.outcome_sink() {
+            organizer = organizer.with_outcome_sink(sink);
+        }
+        let result = organizer.organize_directory(&target_dir, dry_run)?;
+        let warnings = organizer.warnings();
+        for warning in &warnings {
+            log_to_file(&kondo_config.log_file, &format!("Warning: {}", warning));
         }
 
-        default_config
-    };
+        let status = status(&result);
+        output::emit(
+            format,
+            &Document::new("categorize", Some(&target_dir), dry_run, status, &result)
+                .with_warnings(warnings),
+        );
+        return Ok(status);
+    }
 
     // Launch TUI or auto-organize
-    let mut app = TuiApp::new(config, target_dir);
+    let mut app = TuiApp::new(config, target_dir)
+        .with_plan_options(plan_options)
+        .with_dry_run(dry_run);
+    if let Some(journal) = journal {
+        app = app.with_journal(journal);
+    }
 
     let result = if no_ui {
-        app.auto_organize()
+        app.auto_organize(dry_run)
     } else {
         app.run()
     };
 
+    for warning in app.warnings() {
+        log_to_file(&kondo_config.log_file, &format!("Warning: {}", warning));
+    }
+
     // Log completion
     match &result {
         Ok(_) => {
@@ -521,7 +452,9 @@ fn run_categorize_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: b
                 &kondo_config.log_file,
                 "Organization completed successfully",
             );
-            println!("\n✦ File organization complete!");
+            if !dry_run {
+                println!("\n✦ File organization complete!");
+            }
         }
         Err(e) => {
             log_to_file(
@@ -531,10 +464,18 @@ fn run_categorize_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: b
         }
     }
 
-    result
+    result?;
+    Ok(app.result().map(status).unwrap_or(Status::Success))
 }
 
-fn run_filename_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: bool) -> std::io::Result<()> {
+fn run_filename_mode(
+    target_dir: PathBuf,
+    kondo_config: &KondoConfig,
+    no_ui: bool,
+    dry_run: bool,
+    plan_options: PlanOptions,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
     log_to_file(
         &kondo_config.log_file,
         &format!("=== Starting Kondo (Filename Similarity Mode - No UI: {}) ===", no_ui),
@@ -544,7 +485,9 @@ fn run_filename_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: boo
         &format!("Target directory: {}", target_dir.display()),
     );
 
-    println!("Kondo - Filename Similarity Mode");
+    if format.is_text() {
+        println!("Kondo - Filename Similarity Mode");
+    }
 
     // Load similarity config from kondo.toml
     let similarity_config: SimilarityConfig = kondo_config.similarity_config.clone().into();
@@ -558,11 +501,54 @@ fn run_filename_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: boo
         ),
     );
 
+    let journal = if dry_run {
+        None
+    } else {
+        open_journal("filename", kondo_config)
+    };
+    let status = |result: &filename::OrganizeResult| {
+        Status::from_counts(
+            result.files_moved + result.duplicates_deleted,
+            result.errors.len(),
+        )
+    };
+
+    if !format.is_text() {
+        let target_dir = std::path::absolute(&target_dir)?;
+        let result = organize_by_similarity(
+            &target_dir,
+            &similarity_config,
+            false,
+            &plan_options,
+            &ExecuteOptions {
+                dry_run,
+                journal: journal.as_ref(),
+                transfer: kondo_config.transfer_mode,
+                progress: None,
+                on_outcome: format.outcome_sink(),
+            },
+            &mut |msg| log_to_file(&kondo_config.log_file, msg),
+        )?;
+
+        let status = status(&result);
+        output::emit(
+            format,
+            &Document::new("similar", Some(&target_dir), dry_run, status, &result),
+        );
+        return Ok(status);
+    }
+
     // Launch TUI or auto-organize
-    let mut app = FilenameTuiApp::new(target_dir, similarity_config);
+    let mut app = FilenameTuiApp::new(target_dir, similarity_config)
+        .with_plan_options(plan_options)
+        .with_transfer(kondo_config.transfer_mode)
+        .with_dry_run(dry_run);
+    if let Some(journal) = journal {
+        app = app.with_journal(journal);
+    }
 
     let result = if no_ui {
-        app.auto_organize()
+        app.auto_organize(dry_run)
     } else {
         app.run()
     };
@@ -582,7 +568,9 @@ fn run_filename_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: boo
                 &kondo_config.log_file,
                 "Organization completed successfully",
             );
-            println!("\n✦ File organization complete!");
+            if !dry_run {
+                println!("\n✦ File organization complete!");
+            }
         }
         Err(e) => {
             log_to_file(
@@ -592,10 +580,18 @@ fn run_filename_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: boo
         }
     }
 
-    result
+    result?;
+    Ok(app.result().map(status).unwrap_or(Status::Success))
 }
 
-fn run_intelligent_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui: bool) -> std::io::Result<()> {
+fn run_intelligent_mode(
+    target_dir: PathBuf,
+    kondo_config: &KondoConfig,
+    no_ui: bool,
+    dry_run: bool,
+    plan_options: PlanOptions,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
     log_to_file(
         &kondo_config.log_file,
         &format!("=== Starting Kondo (Intelligent ML Mode - No UI: {}) ===", no_ui),
@@ -605,7 +601,9 @@ fn run_intelligent_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui:
         &format!("Target directory: {}", target_dir.display()),
     );
 
-    println!("Kondo - Intelligent ML Mode");
+    if format.is_text() {
+        println!("Kondo - Intelligent ML Mode");
+    }
 
     // Load intelligent config from kondo.toml
     let intelligent_config: IntelligentConfig = kondo_config.intelligent_config.clone().into();
@@ -621,8 +619,33 @@ fn run_intelligent_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui:
         ),
     );
 
+    // Headless runs only analyze, so there is nothing to journal
+    if !format.is_text() {
+        let target_dir = std::path::absolute(&target_dir)?;
+        let result = organize_files_intelligently(&target_dir, &intelligent_config, &plan_options.walk, None)?;
+        if format == OutputFormat::Ndjson {
+            for group in &result.groups {
+                output::print_tagged("group", group);
+            }
+        }
+        output::emit(
+            format,
+            &Document::new("cluster", Some(&target_dir), dry_run, Status::Success, &result),
+        );
+        return Ok(Status::Success);
+    }
+
     // Launch TUI or auto-analyze
-    let mut app = IntelligentTuiApp::new(intelligent_config, target_dir);
+    let mut app = IntelligentTuiApp::new(intelligent_config, target_dir)
+        .with_plan_options(plan_options)
+        .with_transfer(kondo_config.transfer_mode)
+        .with_dry_run(dry_run);
+    // Only the TUI moves files; --no-ui stops at the analysis
+    if !dry_run && !no_ui {
+        if let Some(journal) = open_journal("intelligent", kondo_config) {
+            app = app.with_journal(journal);
+        }
+    }
 
     let result = if no_ui {
         app.auto_analyze()
@@ -647,224 +670,1181 @@ fn run_intelligent_mode(target_dir: PathBuf, kondo_config: &KondoConfig, no_ui:
         }
     }
 
-    result
+    result?;
+    Ok(app
+        .moves()
+        .map(|moves| Status::from_counts(moves.files_moved, moves.errors.len()))
+        .unwrap_or(Status::Success))
 }
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
+/// Plans a run without touching any files and writes the plan for review
+fn export_plan(
+    mode: &str,
+    target_dir: PathBuf,
+    plan_out: &Path,
+    plan_options: PlanOptions,
+    kondo_config: &KondoConfig,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
+    // Plans may be applied from another working directory later
+    let target_dir = std::path::absolute(&target_dir)?;
 
-    // Load configuration
-    let kondo_config = load_kondo_config();
+    log_to_file(
+        &kondo_config.log_file,
+        &format!("=== Planning {} run for {} ===", mode, target_dir.display()),
+    );
 
-    // No arguments - show help
-    if args.len() < 2 {
-        print_help();
-        process::exit(0);
+    let plan = match mode {
+        "categorize" => {
+            let config = load_categorize_config(kondo_config)?;
+            FileOrganizer::new(config)
+                .with_plan_options(plan_options)
+                .plan_directory(&target_dir)?
+        }
+        "similar" => {
+            let config: SimilarityConfig = kondo_config.similarity_config.clone().into();
+            plan_by_similarity(&target_dir, &config, false, &plan_options, &mut |msg| {
+                log_to_file(&kondo_config.log_file, msg)
+            })?
+        }
+        "date" => date::plan_by_date(&target_dir, &kondo_config.date_config, &plan_options)?,
+        _ => {
+            let config: IntelligentConfig = kondo_config.intelligent_config.clone().into();
+            let result = organize_files_intelligently(&target_dir, &config, &plan_options.walk, None)?;
+            plan_group_moves(&target_dir, &result, &plan_options)
+        }
+    };
+
+    let mut plan = plan;
+    plan.transfer = kondo_config.transfer_mode;
+    plan.save(plan_out)?;
+
+    let moves = plan
+        .actions
+        .iter()
+        .filter(|a| matches!(a, Action::Move { .. }))
+        .count();
+    let skips = plan
+        .actions
+        .iter()
+        .filter(|a| matches!(a, Action::Skip { .. }))
+        .count();
+    let deletes = plan
+        .actions
+        .iter()
+        .filter(|a| matches!(a, Action::DeleteDuplicate { .. }))
+        .count();
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!("Plan written to {} ({} moves, {} skips)", plan_out.display(), moves, skips),
+    );
+
+    if !format.is_text() {
+        let result = serde_json::json!({
+            "plan_file": plan_out,
+            "files_to_move": moves,
+            "files_skipped": skips,
+            "duplicates_to_delete": deletes,
+        });
+        output::emit(
+            format,
+            &Document::new(mode, Some(&target_dir), true, Status::Success, result),
+        );
+        return Ok(Status::Success);
+    }
+
+    println!("✓ Plan written to: {}", plan_out.display());
+    println!("   • Files to move: {}", moves);
+    println!("   • Files skipped: {}", skips);
+    if deletes > 0 {
+        println!("   • Duplicates to delete: {}", deletes);
     }
+    println!("\nReview or edit it, then run: kondo apply {}", plan_out.display());
 
-    // Check for -nui flag
-    let no_ui = args.contains(&"-nui".to_string()) || args.contains(&"--no-ui".to_string());
+    Ok(Status::Success)
+}
 
-    let mode = &args[1];
+fn run_date_mode(
+    target_dir: PathBuf,
+    kondo_config: &KondoConfig,
+    dry_run: bool,
+    plan_options: PlanOptions,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
+    let target_dir = std::path::absolute(&target_dir)?;
+    let config = &kondo_config.date_config;
+    let sources: Vec<String> = config.sources.iter().map(DateSource::to_string).collect();
 
-    // Parse arguments
-    match mode.as_str() {
-        "-h" | "--help" => {
-            print_help();
-            process::exit(0);
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "=== Starting Kondo (Date Mode - {} by {} - Dry run: {}) ===",
+            config.folder_format(),
+            sources.join(", "),
+            dry_run
+        ),
+    );
+    log_to_file(
+        &kondo_config.log_file,
+        &format!("Target directory: {}", target_dir.display()),
+    );
+
+    if format.is_text() {
+        println!("Kondo - Date Mode\n");
+    }
+
+    let mut plan = date::plan_by_date(&target_dir, config, &plan_options)?;
+    plan.transfer = kondo_config.transfer_mode;
+
+    let journal = if dry_run {
+        None
+    } else {
+        open_journal("date", kondo_config)
+    };
+    let report = plan::execute(
+        &plan,
+        &ExecuteOptions {
+            dry_run,
+            journal: journal.as_ref(),
+            transfer: plan.transfer,
+            progress: Some(transfer::print_progress),
+            on_outcome: format.outcome_sink(),
+        },
+    );
+    for line in report.log_lines().iter().chain(&report.warnings) {
+        log_to_file(&kondo_config.log_file, line);
+    }
+
+    let status = Status::of_report(&report);
+    if !format.is_text() {
+        let summary = PlanSummary::from(&report);
+        output::emit(
+            format,
+            &Document::new("date", Some(&target_dir), dry_run, status, summary)
+                .with_warnings(report.warnings.clone()),
+        );
+        return Ok(status);
+    }
+
+    for error in report.errors() {
+        eprintln!("✗ {}", error);
+    }
+
+    let mut folders: Vec<_> = report.group_counts().into_iter().collect();
+    folders.sort();
+
+    if dry_run {
+        println!("[DRY RUN] Summary:");
+        println!("   • Files that would be moved: {}", report.files_moved());
+    } else {
+        println!("✦ Organization Complete!\n");
+        println!("Summary:");
+        println!("   • Files moved:     {}", report.files_moved());
+    }
+    println!("   • Files skipped:   {}", report.files_skipped());
+    println!("   • Files failed:    {}", report.files_failed());
+    if report.files_renamed() > 0 {
+        println!("   • Renamed:         {}", report.files_renamed());
+    }
+
+    if !folders.is_empty() {
+        println!("\nFolders:");
+        for (folder, count) in &folders {
+            println!("    {:<20} → {} files", folder, count);
         }
-        "-c" | "--categorize" => {
-            // Find target directory (skip -nui flag if present)
-            let target_dir = if args.len() > 2 {
-                let mut path_arg = None;
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 1 && arg != "-nui" && arg != "--no-ui" {
-                        path_arg = Some(arg);
-                        break;
-                    }
-                }
+    }
+    if !dry_run && report.files_moved() > 0 {
+        println!("\nUndo with: kondo undo");
+    }
 
-                if let Some(path) = path_arg {
-                    PathBuf::from(path)
-                } else {
-                    match env::current_dir() {
-                        Ok(dir) => dir,
-                        Err(e) => {
-                            eprintln!("✗ Error: Could not get current directory: {}", e);
-                            log_to_file(
-                                &kondo_config.log_file,
-                                &format!("Error: Could not get current directory: {}", e),
-                            );
-                            process::exit(1);
-                        }
-                    }
+    Ok(status)
+}
+
+/// Finds byte-identical files and reports, trashes or hard links the extra copies
+fn run_dedupe_mode(
+    target_dir: PathBuf,
+    action: DedupeAction,
+    keep: KeepPolicy,
+    plan_out: Option<&Path>,
+    kondo_config: &KondoConfig,
+    global: &GlobalArgs,
+    plan_options: PlanOptions,
+) -> Result<Status, KondoError> {
+    let (dry_run, format) = (global.dry_run, global.format());
+    if plan_out.is_some() && action != DedupeAction::Trash {
+        return Err(invalid_input("--plan-out needs --action trash".to_string()));
+    }
+    let target_dir = std::path::absolute(&target_dir)?;
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "=== Starting Kondo (Dedupe Mode - {} - Dry run: {}) ===",
+            action, dry_run
+        ),
+    );
+    log_to_file(
+        &kondo_config.log_file,
+        &format!("Target directory: {}", target_dir.display()),
+    );
+
+    if format.is_text() {
+        println!("Kondo - Dedupe Mode\n");
+    }
+
+    let groups = dedupe::find_duplicates(&target_dir, &plan_options.walk, keep)?;
+    if format == OutputFormat::Ndjson {
+        for group in &groups {
+            output::print_tagged("duplicate_group", group);
+        }
+    }
+    for group in groups.iter().filter(|_| format.is_text()) {
+        println!(
+            "● {} copies of {} each (blake3 {})",
+            group.duplicates.len() + 1,
+            format_size(group.size),
+            &group.hash[..12]
+        );
+        println!("   keep  {}", group.keep.display());
+        for duplicate in &group.duplicates {
+            println!("   dup   {}", duplicate.display());
+        }
+    }
+
+    let duplicates: usize = groups.iter().map(|g| g.duplicates.len()).sum();
+    let wasted: u64 = groups.iter().map(|g| g.wasted()).sum();
+    let mut trashed = None;
+    let mut linked = Vec::new();
+    let mut errors = Vec::new();
+
+    match action {
+        DedupeAction::Report => {}
+        DedupeAction::Trash => {
+            let plan = dedupe::plan_trash(&target_dir, &groups, &plan_options.conflict);
+            if let Some(plan_out) = plan_out {
+                plan.save(plan_out)?;
+                if !format.is_text() {
+                    let result = serde_json::json!({
+                        "plan_file": plan_out,
+                        "duplicates_to_trash": duplicates,
+                    });
+                    output::emit(
+                        format,
+                        &Document::new("dedupe", Some(&target_dir), true, Status::Success, result),
+                    );
+                    return Ok(Status::Success);
                 }
+                println!("\n✓ Plan written to: {}", plan_out.display());
+                println!("Review or edit it, then run: kondo apply {}", plan_out.display());
+                return Ok(Status::Success);
+            }
+
+            let journal = if dry_run {
+                None
             } else {
-                match env::current_dir() {
-                    Ok(dir) => dir,
-                    Err(e) => {
-                        eprintln!("✗ Error: Could not get current directory: {}", e);
-                        log_to_file(
-                            &kondo_config.log_file,
-                            &format!("Error: Could not get current directory: {}", e),
-                        );
-                        process::exit(1);
-                    }
-                }
+                open_journal("dedupe", kondo_config)
             };
-
-            if !target_dir.exists() {
-                eprintln!(
-                    "✗ Error: Directory does not exist: {}",
-                    target_dir.display()
-                );
+            let report = plan::execute(
+                &plan,
+                &ExecuteOptions {
+                    dry_run,
+                    journal: journal.as_ref(),
+                    transfer: TransferMode::Move,
+                    progress: Some(transfer::print_progress),
+                    on_outcome: format.outcome_sink(),
+                },
+            );
+            for line in report.log_lines().iter().chain(&report.warnings) {
+                log_to_file(&kondo_config.log_file, line);
+            }
+            errors = report.errors();
+            trashed = Some(report);
+        }
+        DedupeAction::Hardlink => {
+            let journal = if dry_run {
+                None
+            } else {
+                open_journal("dedupe", kondo_config)
+            };
+            let report = dedupe::hardlink_duplicates(&groups, dry_run, journal.as_ref());
+            for warning in &report.warnings {
+                eprintln!("!  {}", warning);
+                log_to_file(&kondo_config.log_file, warning);
+            }
+            for path in &report.linked {
                 log_to_file(
                     &kondo_config.log_file,
-                    &format!("Error: Directory does not exist: {}", target_dir.display()),
+                    &format!("Replaced with hard link: {}", path.display()),
                 );
-                process::exit(1);
             }
-
-            if let Err(e) = run_categorize_mode(target_dir, &kondo_config, no_ui) {
-                eprintln!("✗ Error: {}", e);
-                log_to_file(&kondo_config.log_file, &format!("Fatal error: {}", e));
-                process::exit(1);
+            for failed in &report.failed {
+                log_to_file(&kondo_config.log_file, &format!("Could not hard link {}", failed));
             }
+            errors = report.failed;
+            linked = report.linked;
         }
-        "-f" | "--filename" => {
-            // Find target directory (skip -nui flag if present)
-            let target_dir = if args.len() > 2 {
-                let mut path_arg = None;
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 1 && arg != "-nui" && arg != "--no-ui" {
-                        path_arg = Some(arg);
-                        break;
-                    }
-                }
+    }
 
-                if let Some(path) = path_arg {
-                    PathBuf::from(path)
-                } else {
-                    match env::current_dir() {
-                        Ok(dir) => dir,
-                        Err(e) => {
-                            eprintln!("✗ Error: Could not get current directory: {}", e);
-                            log_to_file(
-                                &kondo_config.log_file,
-                                &format!("Error: Could not get current directory: {}", e),
-                            );
-                            process::exit(1);
-                        }
-                    }
-                }
-            } else {
-                match env::current_dir() {
-                    Ok(dir) => dir,
-                    Err(e) => {
-                        eprintln!("✗ Error: Could not get current directory: {}", e);
-                        log_to_file(
-                            &kondo_config.log_file,
-                            &format!("Error: Could not get current directory: {}", e),
-                        );
-                        process::exit(1);
-                    }
-                }
-            };
+    let status = match (&trashed, action) {
+        (Some(report), _) => Status::of_report(report),
+        (None, DedupeAction::Hardlink) => Status::from_counts(linked.len(), errors.len()),
+        (None, _) => Status::Success,
+    };
+    if !format.is_text() {
+        let summary = DedupeSummary {
+            action: action.to_string(),
+            groups: &groups,
+            duplicates,
+            bytes_reclaimable: wasted,
+            trash: trashed.as_ref().map(PlanSummary::from),
+            linked,
+            errors,
+        };
+        output::emit(
+            format,
+            &Document::new("dedupe", Some(&target_dir), dry_run, status, summary),
+        );
+        return Ok(status);
+    }
 
-            if !target_dir.exists() {
-                eprintln!(
-                    "✗ Error: Directory does not exist: {}",
-                    target_dir.display()
-                );
-                log_to_file(
-                    &kondo_config.log_file,
-                    &format!("Error: Directory does not exist: {}", target_dir.display()),
-                );
-                process::exit(1);
+    for error in &errors {
+        eprintln!("✗ {}", error);
+    }
+    let outcome = match (action, dry_run) {
+        (DedupeAction::Report, _) => "found",
+        (DedupeAction::Trash, true) => "would be moved to kondo-trash",
+        (DedupeAction::Trash, false) => "moved to kondo-trash",
+        (DedupeAction::Hardlink, true) => "would be hard linked",
+        (DedupeAction::Hardlink, false) => "hard linked",
+    };
+
+    println!("\nSummary:");
+    println!("   • Duplicate groups: {}", groups.len());
+    println!("   • Duplicates {}: {}", outcome, duplicates);
+    if action == DedupeAction::Report || dry_run {
+        println!("   • Space reclaimable: {}", format_size(wasted));
+    } else {
+        println!("   • Space freed: {}", format_size(wasted));
+    }
+    if action == DedupeAction::Trash && !dry_run && duplicates > 0 {
+        println!("\nUndo with: kondo undo");
+    }
+
+    Ok(status)
+}
+
+/// Human readable size, e.g. "1.5 MB"
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn run_apply(
+    plan_path: &Path,
+    dry_run: bool,
+    format: OutputFormat,
+    kondo_config: &KondoConfig,
+) -> Result<Status, KondoError> {
+    let plan = Plan::load(plan_path)?;
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "=== Applying {} plan {} (Dry run: {}) ===",
+            plan.mode,
+            plan_path.display(),
+            dry_run
+        ),
+    );
+
+    // Refuse the whole plan if anything changed since it was reviewed
+    let problems = plan.validate();
+    if !problems.is_empty() {
+        eprintln!("✗ The plan no longer matches the files on disk:");
+        for problem in &problems {
+            eprintln!("   • {}", problem);
+            log_to_file(&kondo_config.log_file, &format!("Stale plan entry: {}", problem));
+        }
+        return Err(KondoError::SourceChanged(format!(
+            "{} stale entries, nothing was changed",
+            problems.len()
+        )));
+    }
+
+    let journal = if dry_run {
+        None
+    } else {
+        open_journal(&plan.mode, kondo_config)
+    };
+    let report = plan::execute(
+        &plan,
+        &ExecuteOptions {
+            dry_run,
+            journal: journal.as_ref(),
+            transfer: plan.transfer,
+            progress: Some(transfer::print_progress),
+            on_outcome: format.outcome_sink(),
+        },
+    );
+
+    let status = Status::of_report(&report);
+    if !format.is_text() {
+        for line in report.log_lines().iter().chain(&report.warnings) {
+            log_to_file(&kondo_config.log_file, line);
+        }
+        output::emit(
+            format,
+            &Document::new("apply", Some(&plan.base_path), dry_run, status, PlanSummary::from(&report))
+                .with_warnings(report.warnings.clone()),
+        );
+        return Ok(status);
+    }
+
+    for line in report.log_lines().iter().chain(&report.warnings) {
+        println!("{}", line);
+        log_to_file(&kondo_config.log_file, line);
+    }
+
+    if dry_run {
+        println!("\n[DRY RUN] Plan {}\n", plan_path.display());
+        println!("   • Files that would be moved: {}", report.files_moved());
+    } else {
+        println!("\n✦ Plan {} applied!\n", plan_path.display());
+        println!("   • Folders created: {}", report.dirs_created());
+        println!("   • Files moved:     {}", report.files_moved());
+    }
+    println!("   • Files skipped:   {}", report.files_skipped());
+    println!("   • Files failed:    {}", report.files_failed());
+    if report.files_renamed() > 0 {
+        println!("   • Renamed:         {}", report.files_renamed());
+    }
+    if report.files_overwritten() > 0 {
+        println!("   • Overwritten:     {}", report.files_overwritten());
+    }
+    if report.duplicates_deleted() > 0 {
+        println!("   • Duplicates deleted: {}", report.duplicates_deleted());
+    }
+
+    Ok(status)
+}
+
+/// Organizes files as they arrive in `target_dir` until interrupted. Each batch of
+/// settled files is planned with the configured mode and journaled as its own run.
+fn run_watch(
+    target_dir: PathBuf,
+    kondo_config: &KondoConfig,
+    dry_run: bool,
+    plan_options: PlanOptions,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
+    let target_dir = std::path::absolute(&target_dir)?;
+    let config = &kondo_config.watch;
+    let settle = std::time::Duration::from_secs(config.settle_seconds);
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "=== Starting Kondo (Watch Mode - {}, settle {}s - Dry run: {}) ===",
+            config.mode, config.settle_seconds, dry_run
+        ),
+    );
+    log_to_file(
+        &kondo_config.log_file,
+        &format!("Watching directory: {}", target_dir.display()),
+    );
+
+    let organizer = match config.mode {
+        WatchMode::Categorize => {
+            let categories = load_categorize_config(kondo_config)?;
+            Some(FileOrganizer::new(categories).with_plan_options(plan_options.clone()))
+        }
+        WatchMode::Date => {
+            kondo_config
+                .date_config
+                .validate()
+                .map_err(KondoError::ConfigInvalid)?;
+            None
+        }
+    };
+
+    if format.is_text() {
+        println!(
+            "Kondo - Watching {} ({} mode, files settle after {}s{})",
+            target_dir.display(),
+            config.mode,
+            config.settle_seconds,
+            if dry_run { ", dry run" } else { "" }
+        );
+        println!("Press Ctrl+C to stop.\n");
+    }
+
+    watch::watch(&target_dir, settle, |event| {
+        let files = match event {
+            WatchEvent::Settled(files) => files,
+            WatchEvent::Error(e) => {
+                eprintln!("!  Watch error: {}", e);
+                log_to_file(&kondo_config.log_file, &format!("Watch error: {}", e));
+                return;
             }
+        };
 
-            if let Err(e) = run_filename_mode(target_dir, &kondo_config, no_ui) {
+        let planned = match &organizer {
+            Some(organizer) => organizer.plan_files(&target_dir, files),
+            None => date::plan_files_by_date(&target_dir, files, &kondo_config.date_config, &plan_options),
+        };
+        let mut plan = match planned {
+            Ok(plan) => plan,
+            Err(e) => {
                 eprintln!("✗ Error: {}", e);
-                log_to_file(&kondo_config.log_file, &format!("Fatal error: {}", e));
-                process::exit(1);
+                log_to_file(&kondo_config.log_file, &format!("Watch planning failed: {}", e));
+                return;
+            }
+        };
+        plan.transfer = kondo_config.transfer_mode;
+
+        let moves = plan.actions.iter().any(|a| matches!(a, Action::Move { .. }));
+        let journal = if dry_run || !moves {
+            None
+        } else {
+            open_journal("watch", kondo_config)
+        };
+        let report = plan::execute(
+            &plan,
+            &ExecuteOptions {
+                dry_run,
+                journal: journal.as_ref(),
+                transfer: plan.transfer,
+                progress: None,
+                on_outcome: format.outcome_sink(),
+            },
+        );
+
+        let timestamp = Local::now().format("%H:%M:%S");
+        for outcome in report.outcomes.iter().filter(|_| format.is_text()) {
+            if !matches!(outcome, Outcome::DirCreated { .. }) {
+                println!("[{}] {}", timestamp, outcome.describe(dry_run, plan.transfer));
             }
         }
-        "-i" | "--intelligent" => {
-            // Find target directory (skip -nui flag if present)
-            let target_dir = if args.len() > 2 {
-                let mut path_arg = None;
-                for (i, arg) in args.iter().enumerate() {
-                    if i > 1 && arg != "-nui" && arg != "--no-ui" {
-                        path_arg = Some(arg);
-                        break;
-                    }
-                }
+        for line in report.log_lines().iter().chain(&report.warnings) {
+            log_to_file(&kondo_config.log_file, line);
+        }
+    })?;
+    Ok(Status::Success)
+}
 
-                if let Some(path) = path_arg {
-                    PathBuf::from(path)
-                } else {
-                    match env::current_dir() {
-                        Ok(dir) => dir,
-                        Err(e) => {
-                            eprintln!("✗ Error: Could not get current directory: {}", e);
-                            log_to_file(
-                                &kondo_config.log_file,
-                                &format!("Error: Could not get current directory: {}", e),
-                            );
-                            process::exit(1);
-                        }
-                    }
+/// Shows which rule or category categorize mode would pick for one file
+fn run_explain(file: &Path, dest: Option<String>, kondo_config: &KondoConfig) -> std::io::Result<()> {
+    let options = OrganizeOptions {
+        dest,
+        ..OrganizeOptions::default()
+    };
+    // The file is explained as organizing its own folder would see it
+    let file = std::path::absolute(file)?;
+    let dir_config = match file.parent() {
+        Some(dir) => kondo_config.for_directory(dir)?.map(|(_, dir_config)| dir_config),
+        None => None,
+    };
+    let kondo_config = dir_config.as_ref().unwrap_or(kondo_config);
+    let config = load_categorize_config(kondo_config)?;
+    let organizer = FileOrganizer::new(config).with_plan_options(plan_options(&options, kondo_config));
+    for line in organizer.explain(&file)? {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn run_undo(
+    run_id: Option<&str>,
+    list: bool,
+    dry_run: bool,
+    format: OutputFormat,
+    kondo_config: &KondoConfig,
+) -> Result<Status, KondoError> {
+    let journal_path = get_journal_path()?;
+
+    if list {
+        let runs = journal::list_runs(&journal_path)?;
+        match format {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&runs)?),
+            OutputFormat::Ndjson => runs.iter().for_each(|run| output::print_tagged("run", run)),
+            OutputFormat::Text if runs.is_empty() => {
+                println!("No recorded runs in {}", journal_path.display())
+            }
+            OutputFormat::Text => {
+                println!("Recorded runs:");
+                for run in runs {
+                    println!(
+                        "   {}  {:12} {:5} files  {}{}",
+                        run.run_id,
+                        run.mode,
+                        run.files_moved,
+                        run.started,
+                        if run.undone { "  (undone)" } else { "" }
+                    );
                 }
+            }
+        }
+        return Ok(Status::Success);
+    }
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "=== Undo run {} (Dry run: {}) ===",
+            run_id.unwrap_or("latest"),
+            dry_run
+        ),
+    );
+
+    let result = journal::undo_run(&journal_path, run_id, dry_run)?;
+    let status = Status::from_counts(
+        result.files_restored + result.copies_removed + result.duplicates_restored,
+        result.errors.len(),
+    );
+
+    if !format.is_text() {
+        output::emit(format, &Document::new("undo", None, dry_run, status, &result));
+    } else if dry_run {
+        println!("\n[DRY RUN] Undo of run {}\n", result.run_id);
+        println!("   • Files that would be restored: {}", result.files_restored);
+        println!("   • Copies/links that would be removed: {}", result.copies_removed);
+        if result.duplicates_restored > 0 {
+            println!("   • Duplicates that would be restored: {}", result.duplicates_restored);
+        }
+        println!("   • Directories that would be removed: {}", result.dirs_removed);
+    } else {
+        println!("\n✦ Undo of run {} complete!\n", result.run_id);
+        println!("   • Files restored:      {}", result.files_restored);
+        println!("   • Copies removed:      {}", result.copies_removed);
+        if result.duplicates_restored > 0 {
+            println!("   • Duplicates restored: {}", result.duplicates_restored);
+        }
+        println!("   • Directories removed: {}", result.dirs_removed);
+    }
+
+    if format.is_text() && !result.changed.is_empty() {
+        println!("\n⚠️  Changed since the run (left in place):");
+        for path in &result.changed {
+            println!("   • {}", path.display());
+        }
+    }
+
+    if format.is_text() && !result.vanished.is_empty() {
+        println!("\n⚠️  Missing since the run:");
+        for path in &result.vanished {
+            println!("   • {}", path.display());
+        }
+    }
+
+    if format.is_text() && !result.errors.is_empty() {
+        println!("\n❌ Errors:");
+        for error in &result.errors {
+            println!("   • {}", error);
+        }
+        if !dry_run && !result.complete {
+            println!(
+                "\n   Run `kondo undo {}` again once these are fixed to restore the rest",
+                result.run_id
+            );
+        }
+    }
+
+    log_to_file(
+        &kondo_config.log_file,
+        &format!(
+            "Undo of run {}: {} restored, {} copies removed, {} duplicates restored, {} dirs removed, {} changed, {} vanished, {} errors",
+            result.run_id,
+            result.files_restored,
+            result.copies_removed,
+            result.duplicates_restored,
+            result.dirs_removed,
+            result.changed.len(),
+            result.vanished.len(),
+            result.errors.len()
+        ),
+    );
+
+    Ok(status)
+}
+
+fn run_config(action: ConfigCommand, global: &GlobalArgs) -> Result<Status, KondoError> {
+    let format = global.format();
+    let json = !format.is_text();
+    let config_path = get_config_path()?;
+
+    match action {
+        ConfigCommand::Path => {
+            if json {
+                println!("{}", serde_json::json!({ "path": config_path }));
+            } else {
+                println!("{}", config_path.display());
+            }
+        }
+        ConfigCommand::Show { effective: true, dir } => {
+            return show_effective(&config_path, global.profile.as_deref(), dir.as_deref(), format);
+        }
+        ConfigCommand::Show { .. } => {
+            let content = fs::read_to_string(&config_path)?;
+            if json {
+                let value: toml::Value = toml::from_str(&content).map_err(|e| {
+                    KondoError::ConfigInvalid(format!("{}: {}", config_path.display(), e))
+                })?;
+                println!("{}", serde_json::to_string_pretty(&value)?);
             } else {
-                match env::current_dir() {
-                    Ok(dir) => dir,
-                    Err(e) => {
-                        eprintln!("✗ Error: Could not get current directory: {}", e);
-                        log_to_file(
-                            &kondo_config.log_file,
-                            &format!("Error: Could not get current directory: {}", e),
-                        );
-                        process::exit(1);
-                    }
+                print!("{}", content);
+            }
+        }
+        ConfigCommand::Init => return init_config(&config_path, format),
+        ConfigCommand::Check => return check_config(&config_path, format),
+        ConfigCommand::Migrate => return migrate_config(&config_path, global.dry_run, format),
+    }
+
+    Ok(Status::Success)
+}
+
+/// `kondo config show --effective`: every setting as a run in `dir` would see it, after
+/// the profile, `KONDO_*` variables and the directory's .kondo.toml, with the layer it
+/// came from
+fn show_effective(
+    config_path: &Path,
+    profile: Option<&str>,
+    dir: Option<&Path>,
+    format: OutputFormat,
+) -> Result<Status, KondoError> {
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => env::current_dir()?,
+    };
+    let mut layers = if config_path.exists() {
+        config::load_layers(config_path, profile)?.0
+    } else if let Some(profile) = profile {
+        return Err(KondoError::ConfigInvalid(format!(
+            "no profile `{}`, {} does not exist yet",
+            profile,
+            config_path.display()
+        )));
+    } else {
+        config::Layers::new(&KondoConfig::default())?
+    };
+    for warning in layers.push_env(env::vars())? {
+        eprintln!("!  {}", warning);
+    }
+    if let Some((path, table)) = config::directory_file(&dir)? {
+        layers.push(config::Source::Directory(path), table);
+    }
+    let settings = layers.effective()?;
+
+    if format.is_text() {
+        for setting in &settings {
+            println!("{} = {}  # {}", setting.key, setting.value, setting.source);
+        }
+    } else {
+        let result = serde_json::json!({
+            "path": config_path,
+            "profile": profile,
+            "settings": settings,
+        });
+        output::emit(format, &Document::new("config", Some(&dir), false, Status::Success, result));
+    }
+
+    Ok(Status::Success)
+}
+
+/// `kondo config init`: writes the commented default config, creating its directory.
+/// An existing file is left alone.
+fn init_config(config_path: &Path, format: OutputFormat) -> Result<Status, KondoError> {
+    if config_path.exists() {
+        return Err(invalid_input(format!(
+            "{} already exists, `kondo config migrate` upgrades it",
+            config_path.display()
+        )));
+    }
+    // Forward slashes keep the path valid inside a TOML string on Windows
+    let log_path = get_default_log_path()?.to_string_lossy().replace('\\', "/");
+    if let Some(config_dir) = config_path.parent() {
+        fs::create_dir_all(config_dir)?;
+    }
+    fs::write(config_path, config::template(&log_path))?;
+
+    if format.is_text() {
+        println!("✓ Created default config at: {}", config_path.display());
+    } else {
+        let result = serde_json::json!({ "path": config_path });
+        output::emit(format, &Document::new("config", None, false, Status::Success, result));
+    }
+    Ok(Status::Success)
+}
+
+/// `kondo config check`: every problem in the config file, with its line and column
+fn check_config(config_path: &Path, format: OutputFormat) -> Result<Status, KondoError> {
+    if !config_path.exists() {
+        return Err(invalid_input(format!(
+            "{} does not exist yet, `kondo config init` writes it",
+            config_path.display()
+        )));
+    }
+    let content = fs::read_to_string(config_path)?;
+    let problems = match config::check(&content) {
+        Ok(problems) => problems,
+        Err(problem) => vec![problem],
+    };
+    let status = if problems.is_empty() {
+        Status::Success
+    } else {
+        Status::Failed
+    };
+
+    if !format.is_text() {
+        let result = serde_json::json!({ "path": config_path, "problems": problems });
+        output::emit(format, &Document::new("config", None, false, status, result));
+    } else if problems.is_empty() {
+        println!("✓ {} is valid", config_path.display());
+    } else {
+        println!("✗ {}: {} problem(s)", config_path.display(), problems.len());
+        for problem in &problems {
+            println!("   • {}", problem);
+        }
+    }
+
+    Ok(status)
+}
+
+/// `kondo config migrate`: upgrades the config file to the current format after copying
+/// it to kondo.toml.vN.bak, or prints the changes as a unified diff with `--dry-run`
+fn migrate_config(config_path: &Path, dry_run: bool, format: OutputFormat) -> Result<Status, KondoError> {
+    if !config_path.exists() {
+        return Err(invalid_input(format!(
+            "{} does not exist yet, `kondo config init` writes it",
+            config_path.display()
+        )));
+    }
+    let content = fs::read_to_string(config_path)?;
+    let migrated = migrate::migrate(&content).map_err(|message| {
+        KondoError::ConfigInvalid(format!("{}: {}", config_path.display(), message))
+    })?;
+    let to = migrate::CONFIG_VERSION;
+
+    let diff = similar::TextDiff::from_lines(&content, &migrated.content)
+        .unified_diff()
+        .header(
+            &config_path.display().to_string(),
+            &format!("{} (version {})", config_path.display(), to),
+        )
+        .to_string();
+    let backup = if dry_run || migrated.changes.is_empty() {
+        None
+    } else {
+        let backup = backup_path(config_path, migrated.from);
+        fs::copy(config_path, &backup)?;
+        fs::write(config_path, &migrated.content)?;
+        Some(backup)
+    };
+
+    if !format.is_text() {
+        let result = serde_json::json!({
+            "path": config_path,
+            "from": migrated.from,
+            "to": to,
+            "changes": migrated.changes,
+            "diff": diff,
+            "backup": backup,
+        });
+        output::emit(format, &Document::new("config", None, dry_run, Status::Success, result));
+    } else if migrated.changes.is_empty() {
+        println!("✓ {} is already version {}", config_path.display(), to);
+    } else {
+        match &backup {
+            Some(backup) => println!(
+                "✓ Upgraded {} from version {} to {}, the old file is at {}",
+                config_path.display(),
+                migrated.from,
+                to,
+                backup.display()
+            ),
+            None => println!(
+                "[DRY RUN] Would upgrade {} from version {} to {}:",
+                config_path.display(),
+                migrated.from,
+                to
+            ),
+        }
+        for change in &migrated.changes {
+            println!("   • {}", change);
+        }
+        if backup.is_none() {
+            print!("\n{}", diff);
+        }
+    }
+
+    Ok(Status::Success)
+}
+
+/// kondo.toml.v1.bak, or kondo.toml.v1.2.bak and so on if that is taken
+fn backup_path(config_path: &Path, version: i64) -> PathBuf {
+    let name = config_path
+        .file_name()
+        .map_or_else(|| "kondo.toml".into(), |name| name.to_string_lossy());
+    let mut backup = config_path.with_file_name(format!("{}.v{}.bak", name, version));
+    let mut n = 2;
+    while backup.exists() {
+        backup = config_path.with_file_name(format!("{}.v{}.{}.bak", name, version, n));
+        n += 1;
+    }
+    backup
+}
+
+/// The command run when only directories are given: cluster if `enable_intelligent_grouping`
+/// is set, similar if `enable_smart_grouping` is set, otherwise categorize
+fn default_command(kondo_config: &KondoConfig, target: TargetArgs, options: OrganizeOptions) -> Command {
+    if kondo_config.enable_intelligent_grouping {
+        Command::Cluster { target, options }
+    } else if kondo_config.enable_smart_grouping {
+        Command::Similar { target, options }
+    } else {
+        Command::Categorize {
+            target,
+            options,
+            sniff: false,
+        }
+    }
+}
+
+fn run_command(
+    command: Command,
+    global: &GlobalArgs,
+    mut kondo_config: KondoConfig,
+) -> Result<Status, KondoError> {
+    let dry_run = global.dry_run;
+    // Under --dry-run the interactive UIs only offer previews
+    let no_ui = global.no_ui;
+    let format = global.format();
+
+    match (&command, format) {
+        (Command::Explain { .. }, OutputFormat::Json | OutputFormat::Ndjson) => {
+            return Err(invalid_input("explain only prints text".to_string()));
+        }
+        (Command::Watch { .. }, OutputFormat::Json) => {
+            return Err(invalid_input(
+                "watch never finishes a run to report, use --output ndjson".to_string(),
+            ));
+        }
+        _ => {}
+    }
+
+    match command {
+        Command::Categorize {
+            target,
+            options,
+            sniff,
+        } => {
+            let configure = |config: &mut KondoConfig| -> Result<(), KondoError> {
+                apply_options(config, &options)?;
+                config.sniff_content |= sniff;
+                Ok(())
+            };
+            organize_dirs("categorize", &target, &options, global, &kondo_config, configure, |dir, cfg, plan_options| {
+                match &target.plan_out {
+                    Some(plan_out) => export_plan("categorize", dir, plan_out, plan_options, cfg, format),
+                    None => run_categorize_mode(dir, cfg, no_ui, dry_run, plan_options, format),
+                }
+            })
+        }
+        Command::Similar { target, options } => {
+            let configure = |config: &mut KondoConfig| apply_options(config, &options);
+            organize_dirs("similar", &target, &options, global, &kondo_config, configure, |dir, cfg, plan_options| {
+                match &target.plan_out {
+                    Some(plan_out) => export_plan("similar", dir, plan_out, plan_options, cfg, format),
+                    None => run_filename_mode(dir, cfg, no_ui, dry_run, plan_options, format),
+                }
+            })
+        }
+        Command::Cluster { target, options } => {
+            let configure = |config: &mut KondoConfig| apply_options(config, &options);
+            organize_dirs("cluster", &target, &options, global, &kondo_config, configure, |dir, cfg, plan_options| {
+                match &target.plan_out {
+                    Some(plan_out) => export_plan("cluster", dir, plan_out, plan_options, cfg, format),
+                    None => run_intelligent_mode(dir, cfg, no_ui, dry_run, plan_options, format),
+                }
+            })
+        }
+        Command::Date {
+            target,
+            options,
+            date_source,
+            granularity,
+        } => {
+            let configure = |config: &mut KondoConfig| -> Result<(), KondoError> {
+                apply_options(config, &options)?;
+                if !date_source.is_empty() {
+                    config.date_config.sources = date_source.clone();
+                }
+                if let Some(granularity) = granularity {
+                    config.date_config.granularity = granularity;
+                    // An explicit granularity on the command line beats a configured format
+                    config.date_config.format = None;
                 }
+                Ok(())
             };
-
-            if !target_dir.exists() {
-                eprintln!(
-                    "✗ Error: Directory does not exist: {}",
-                    target_dir.display()
-                );
-                log_to_file(
-                    &kondo_config.log_file,
-                    &format!("Error: Directory does not exist: {}", target_dir.display()),
-                );
-                process::exit(1);
+            organize_dirs("date", &target, &options, global, &kondo_config, configure, |dir, cfg, plan_options| {
+                match &target.plan_out {
+                    Some(plan_out) => export_plan("date", dir, plan_out, plan_options, cfg, format),
+                    None => run_date_mode(dir, cfg, dry_run, plan_options, format),
+                }
+            })
+        }
+        Command::Dedupe {
+            target,
+            options,
+            action,
+            keep,
+        } => {
+            let options = OrganizeOptions::from(options);
+            let configure = |config: &mut KondoConfig| apply_options(config, &options);
+            organize_dirs("dedupe", &target, &options, global, &kondo_config, configure, |dir, cfg, plan_options| {
+                run_dedupe_mode(
+                    dir,
+                    action.unwrap_or_default(),
+                    keep.unwrap_or_default(),
+                    target.plan_out.as_deref(),
+                    cfg,
+                    global,
+                    plan_options,
+                )
+            })
+        }
+        Command::Watch {
+            dir,
+            options,
+            mode,
+            settle,
+        } => {
+            let target_dir = resolve_target_dir(dir.as_deref())?;
+            if let Some((path, dir_config)) = kondo_config.for_directory(&target_dir)? {
+                log_to_file(&kondo_config.log_file, &format!("Using {}", path.display()));
+                kondo_config = dir_config;
             }
-
-            if let Err(e) = run_intelligent_mode(target_dir, &kondo_config, no_ui) {
-                eprintln!("✗ Error: {}", e);
-                log_to_file(&kondo_config.log_file, &format!("Fatal error: {}", e));
-                process::exit(1);
+            apply_options(&mut kondo_config, &options)?;
+            if let Some(mode) = mode {
+                kondo_config.watch.mode = mode;
             }
+            if let Some(settle) = settle {
+                kondo_config.watch.settle_seconds = settle;
+            }
+            let plan_options = plan_options(&options, &kondo_config);
+            run_watch(target_dir, &kondo_config, dry_run, plan_options, format)
         }
-        "-nui" | "--no-ui" => {
-            eprintln!("✗ Error: -nui flag must be used with -c, -f, or -i mode");
-            eprintln!("\nExamples:");
-            eprintln!("  kondo -c -nui /path/to/folder");
-            eprintln!("  kondo -f -nui /path/to/folder");
-            eprintln!("  kondo -i -nui /path/to/folder");
-            process::exit(1);
+        Command::Apply { plan } => run_apply(&plan, dry_run, format, &kondo_config),
+        Command::Explain { file, dest } => {
+            run_explain(&file, dest, &kondo_config)?;
+            Ok(Status::Success)
         }
-        _ => {
-            eprintln!("✗ Error: Unknown option '{}'", mode);
-            eprintln!("\nRun 'kondo --help' for usage information");
-            log_to_file(
-                &kondo_config.log_file,
-                &format!("Error: Unknown option '{}'", mode),
-            );
+        Command::Undo { run_id, list } => {
+            run_undo(run_id.as_deref(), list, dry_run, format, &kondo_config)
+        }
+        Command::Config { action } => run_config(action, global),
+    }
+}
+
+fn main() {
+    let args = cli::normalize_args(env::args());
+
+    // No arguments - show help
+    if args.len() < 2 {
+        let _ = Cli::command().print_help();
+        process::exit(0);
+    }
+
+    let cli = Cli::parse_args(args);
+    VERBOSE.store(cli.global.verbose, Ordering::Relaxed);
+
+    let config_file = cli.global.config.clone().or_else(|| {
+        env::var_os("KONDO_CONFIG")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+    });
+    if let Some(path) = &config_file {
+        if !path.is_file() {
+            eprintln!("✗ Error: Config file does not exist: {}", path.display());
             process::exit(1);
         }
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+        let _ = CONFIG_OVERRIDE.set(path);
+    }
+
+    // `kondo config` looks at the file itself, so it has to work when the file is broken
+    let loaded = match cli.command {
+        Some(Command::Config { .. }) => Ok(KondoConfig::default()),
+        _ => load_kondo_config(cli.global.profile.as_deref()),
+    };
+    let mut kondo_config = match loaded {
+        Ok(kondo_config) => kondo_config,
+        Err(e) => {
+            let format = cli.global.format();
+            if format.is_text() {
+                eprintln!("✗ Error: {}", e);
+                if matches!(e, KondoError::ConfigInvalid(_)) {
+                    eprintln!("   Run `kondo config check` for details");
+                }
+            } else {
+                let command_name = cli.command.as_ref().map_or("kondo", Command::name);
+                output::emit(format, &Document::failed(command_name, None, cli.global.dry_run, e));
+            }
+            process::exit(output::EXIT_FAILURE);
+        }
+    };
+    let command = match cli.command {
+        Some(command) => command,
+        None => default_command(&kondo_config, cli.target, cli.options),
+    };
+
+    // A dry run or a command that only looks leaves nothing behind, not even log lines;
+    // --verbose still prints them
+    let read_only = matches!(
+        command,
+        Command::Explain { .. } | Command::Undo { list: true, .. } | Command::Config { .. }
+    );
+    if cli.global.dry_run || read_only {
+        kondo_config.log_file = None;
     }
+    let log_file = kondo_config.log_file.clone();
+    let command_name = command.name();
+    let format = cli.global.format();
+    let failure = match command {
+        Command::Apply { .. } => "Apply failed",
+        Command::Explain { .. } => "Explain failed",
+        Command::Watch { .. } => "Watch failed",
+        Command::Undo { .. } => "Undo failed",
+        _ => "Fatal error",
+    };
 
-    log_to_file(&kondo_config.log_file, "=== Kondo session ended ===\n");
+    match run_command(command, &cli.global, kondo_config) {
+        Ok(status) => {
+            log_to_file(&log_file, "=== Kondo session ended ===\n");
+            if status != Status::Success {
+                process::exit(status.exit_code());
+            }
+        }
+        Err(e) => {
+            if format.is_text() {
+                eprintln!("✗ Error: {}", e);
+            } else {
+                let document = Document::failed(command_name, None, cli.global.dry_run, e.clone());
+                output::emit(format, &document);
+            }
+            log_to_file(&log_file, &format!("{}: {}", failure, e));
+            process::exit(output::EXIT_FAILURE);
+        }
+    }
 }
@@ -0,0 +1,270 @@
+// Config format versions and the upgrades between them: applied in memory whenever a
+// config is loaded, and written back by `kondo config migrate`
+use toml_edit::{value, Array, DocumentMut, ImDocument, Item};
+
+use crate::organizer::ignore;
+
+/// Format version of the config this build writes
+pub const CONFIG_VERSION: i64 = 3;
+
+/// Comment written above a `version` key that `kondo config migrate` adds
+const VERSION_COMMENT: &str = "# Format of this file, upgraded by `kondo config migrate`\n";
+
+/// Extensions the version 1 template listed in two categories, so which one won
+/// depended on hash order. They are removed from the first category when the second
+/// still lists them.
+const V1_DUPLICATES: &[(&str, &str, &[&str])] = &[
+    ("documents", "ebooks", &["epub", "mobi"]),
+    ("archives", "executables", &["dmg", "pkg", "deb", "rpm"]),
+];
+
+/// Upgrade from version N to N + 1 is at index N - 1
+const MIGRATIONS: &[fn(&mut DocumentMut, &mut Vec<String>)] = &[v1_to_v2, v2_to_v3];
+
+/// A config brought up to `CONFIG_VERSION`
+#[derive(Debug)]
+pub struct Migrated {
+    /// Version the file was at
+    pub from: i64,
+    /// The upgraded file, with its comments and layout kept
+    pub content: String,
+    /// What changed, one line each; empty if the file was already current
+    pub changes: Vec<String>,
+}
+
+/// Version of a config: its `version` key, or 1 for files from before the key existed
+pub fn version_of(content: &str) -> Result<i64, String> {
+    let document = ImDocument::parse(content).map_err(|e| e.message().trim().to_string())?;
+    read_version(document.get("version"))
+}
+
+fn read_version(item: Option<&Item>) -> Result<i64, String> {
+    match item {
+        None => Ok(1),
+        Some(item) => item
+            .as_integer()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| "`version` must be a whole number, such as 2".to_string()),
+    }
+}
+
+/// Upgrades a config to `CONFIG_VERSION`. Files written by a newer kondo are refused,
+/// as there is no knowing what their keys mean.
+pub fn migrate(content: &str) -> Result<Migrated, String> {
+    let mut document: DocumentMut = content.parse().map_err(|e: toml_edit::TomlError| e.message().trim().to_string())?;
+    let from = read_version(document.get("version"))?;
+    if from > CONFIG_VERSION {
+        return Err(format!(
+            "version {} is newer than this kondo understands ({})",
+            from, CONFIG_VERSION
+        ));
+    }
+    if from == CONFIG_VERSION {
+        return Ok(Migrated {
+            from,
+            content: content.to_string(),
+            changes: Vec::new(),
+        });
+    }
+
+    // Added first, so the comment opening the file stays on top when keys are removed
+    let mut changes = Vec::new();
+    set_version(&mut document, &mut changes);
+    for migration in &MIGRATIONS[(from - 1) as usize..] {
+        migration(&mut document, &mut changes);
+    }
+
+    Ok(Migrated {
+        from,
+        content: document.to_string(),
+        changes,
+    })
+}
+
+/// Sets `version` to the current one, adding it as the first key if it is missing.
+/// The comment opening the file, up to its first blank line, moves above it.
+fn set_version(document: &mut DocumentMut, changes: &mut Vec<String>) {
+    let root = document.as_table_mut();
+    changes.push(format!("set `version = {}`", CONFIG_VERSION));
+    if root.contains_key("version") {
+        root["version"] = value(CONFIG_VERSION);
+        return;
+    }
+
+    let first = root
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_string());
+    let mut header = String::new();
+    if let Some(mut key) = first.as_deref().and_then(|first| root.key_mut(first)) {
+        let prefix = key
+            .leaf_decor()
+            .prefix()
+            .and_then(|prefix| prefix.as_str())
+            .unwrap_or_default()
+            .to_string();
+        let rest = match prefix.split_once("\n\n") {
+            Some((head, rest)) => {
+                header = format!("{}\n\n", head);
+                rest.to_string()
+            }
+            None if prefix.trim_start().starts_with('#') => {
+                header = format!("{}\n", prefix);
+                String::new()
+            }
+            None => prefix,
+        };
+        key.leaf_decor_mut().set_prefix(format!("\n{}", rest));
+    }
+
+    root.insert("version", value(CONFIG_VERSION));
+    if let Some(mut key) = root.key_mut("version") {
+        key.leaf_decor_mut().set_prefix(format!("{}{}", header, VERSION_COMMENT));
+    }
+    root.sort_values_by(|a, _, b, _| (a.get() != "version").cmp(&(b.get() != "version")));
+}
+
+/// Version 1 had `batch_size`, which nothing read, and the duplicated extensions of
+/// `V1_DUPLICATES`
+fn v1_to_v2(document: &mut DocumentMut, changes: &mut Vec<String>) {
+    if document.as_table_mut().remove("batch_size").is_some() {
+        changes.push("removed `batch_size`, which was never used".to_string());
+    }
+
+    for (category, owner, extensions) in V1_DUPLICATES {
+        let owned: Vec<String> = document
+            .get("categories")
+            .and_then(|categories| categories.get(owner))
+            .and_then(|owner| owner.get("extensions"))
+            .and_then(Item::as_array)
+            .map(|array| array.iter().filter_map(|v| v.as_str()).map(str::to_lowercase).collect())
+            .unwrap_or_default();
+        // Looked up without `get_mut`, which would add the tables when they are missing
+        let Some(array) = document
+            .as_table_mut()
+            .get_mut("categories")
+            .and_then(Item::as_table_like_mut)
+            .and_then(|categories| categories.get_mut(category))
+            .and_then(Item::as_table_like_mut)
+            .and_then(|category| category.get_mut("extensions"))
+            .and_then(Item::as_array_mut)
+        else {
+            continue;
+        };
+
+        let mut removed = Vec::new();
+        array.retain(|v| match v.as_str() {
+            Some(extension) if extensions.contains(&extension) && owned.iter().any(|o| o == extension) => {
+                removed.push(extension.to_string());
+                false
+            }
+            _ => true,
+        });
+        if !removed.is_empty() {
+            changes.push(format!(
+                "removed {} from categories.{}, categories.{} has them",
+                removed.join(", "),
+                category,
+                owner
+            ));
+        }
+    }
+}
+
+/// Version 2 matched `skip_patterns` as substrings of the file name; version 3 reads
+/// them as .gitignore lines. Plain words other than the default names get `*` on both
+/// sides, so they keep matching what they did.
+fn v2_to_v3(document: &mut DocumentMut, changes: &mut Vec<String>) {
+    let profiles: Vec<String> = document
+        .get("profiles")
+        .and_then(Item::as_table_like)
+        .map(|profiles| profiles.iter().map(|(name, _)| name.to_string()).collect())
+        .unwrap_or_default();
+
+    let root = document.as_table_mut();
+    if let Some(array) = root.get_mut("skip_patterns").and_then(Item::as_array_mut) {
+        widen_skip_patterns(array, "skip_patterns", changes);
+    }
+    for name in profiles {
+        let Some(array) = root
+            .get_mut("profiles")
+            .and_then(Item::as_table_like_mut)
+            .and_then(|profiles| profiles.get_mut(&name))
+            .and_then(Item::as_table_like_mut)
+            .and_then(|profile| profile.get_mut("skip_patterns"))
+            .and_then(Item::as_array_mut)
+        else {
+            continue;
+        };
+        widen_skip_patterns(array, &format!("profiles.{}.skip_patterns", name), changes);
+    }
+}
+
+fn widen_skip_patterns(array: &mut Array, key: &str, changes: &mut Vec<String>) {
+    let plain = |pattern: &str| {
+        !pattern.is_empty()
+            && !pattern.contains(['*', '?', '[', '/', '\\'])
+            && !pattern.starts_with(['!', '#'])
+            && !ignore::DEFAULT_PATTERNS.contains(&pattern)
+    };
+    for i in 0..array.len() {
+        let Some(pattern) = array.get(i).and_then(|v| v.as_str()).filter(|p| plain(p)) else {
+            continue;
+        };
+        let widened = format!("*{}*", pattern);
+        changes.push(format!(
+            "{} are .gitignore patterns now: `{}` became `{}` to match as before",
+            key, pattern, widened
+        ));
+        array.replace(i, widened);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_1_upgrades_keeping_comments() {
+        let old = "# Kondo File Organizer Configuration\n\
+                   batch_size = 100\n\
+                   \n\
+                   # Group similar names\n\
+                   enable_smart_grouping = false\n\
+                   skip_patterns = [\".git\", \"keep-\"]\n\
+                   \n\
+                   [categories.documents]\n\
+                   extensions = [\"pdf\", \"epub\", \"mobi\"]\n\
+                   \n\
+                   [categories.ebooks]\n\
+                   extensions = [\"epub\", \"cbz\"]\n";
+
+        let migrated = migrate(old).unwrap();
+        assert_eq!(migrated.from, 1);
+        assert_eq!(
+            migrated.content,
+            "# Kondo File Organizer Configuration\n\
+             \n\
+             # Format of this file, upgraded by `kondo config migrate`\n\
+             version = 3\n\
+             \n\
+             # Group similar names\n\
+             enable_smart_grouping = false\n\
+             skip_patterns = [\".git\", \"*keep-*\"]\n\
+             \n\
+             [categories.documents]\n\
+             extensions = [\"pdf\", \"mobi\"]\n\
+             \n\
+             [categories.ebooks]\n\
+             extensions = [\"epub\", \"cbz\"]\n"
+        );
+        assert_eq!(migrated.changes.len(), 4);
+        assert_eq!(version_of(&migrated.content), Ok(3));
+
+        let again = migrate(&migrated.content).unwrap();
+        assert!(again.changes.is_empty());
+        assert_eq!(again.content, migrated.content);
+        assert!(migrate("version = 9\n").is_err());
+        assert!(version_of("version = \"two\"\n").is_err());
+    }
+}
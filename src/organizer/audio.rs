@@ -0,0 +1,348 @@
+// Artist, album and track details from ID3, Vorbis comment and MP4 tags
+use id3::TagLike;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use super::media::find_box;
+
+/// Comment headers larger than this are not worth reading; they hold cover art
+const MAX_COMMENT_LEN: usize = 16 * 1024 * 1024;
+
+/// Ogg pages read while looking for the comment header
+const MAX_OGG_PAGES: usize = 64;
+
+/// What an audio file's tags say about it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AudioTags {
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub track: Option<u32>,
+    pub disc: Option<u32>,
+    pub genre: Option<String>,
+}
+
+/// Reads ID3v2/ID3v1 (MP3, WAV, AIFF), Vorbis comments (FLAC, Ogg Vorbis, Opus) or
+/// MP4 atoms (M4A, ALAC). None for other files or when no tag is present.
+pub fn read_audio_tags(path: &Path) -> Option<AudioTags> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut header = [0u8; 12];
+    let read = reader.read(&mut header).ok()?;
+    reader.seek(SeekFrom::Start(0)).ok()?;
+    let header = &header[..read];
+
+    let tags = if header.starts_with(b"fLaC") {
+        flac_comments(&mut reader)
+    } else if header.starts_with(b"OggS") {
+        ogg_comments(&mut reader)
+    } else if header.get(4..8) == Some(b"ftyp") {
+        mp4_tags(&mut reader)
+    } else {
+        // Also finds the ID3 chunk of WAV and AIFF files
+        id3::v1v2::read_from(&mut reader).ok().map(|tag| from_id3(&tag))
+    }?;
+
+    (tags != AudioTags::default()).then_some(tags)
+}
+
+fn from_id3(tag: &id3::Tag) -> AudioTags {
+    // ID3v2.4 separates multiple values with NUL; the first one is enough for a path
+    let text = |value: Option<&str>| {
+        value
+            .and_then(|v| v.split('\0').next())
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+
+    AudioTags {
+        artist: text(tag.artist()),
+        album_artist: text(tag.album_artist()),
+        album: text(tag.album()),
+        title: text(tag.title()),
+        track: tag.track(),
+        disc: tag.disc(),
+        genre: tag.genre_parsed().and_then(|genre| text(Some(&genre))),
+    }
+}
+
+/// Applies `KEY=value` Vorbis comments; the first value of each field wins
+fn from_comments(data: &[u8]) -> Option<AudioTags> {
+    let mut rest = data;
+    let vendor_len = take_u32(&mut rest)? as usize;
+    rest = rest.get(vendor_len..)?;
+    let count = take_u32(&mut rest)?;
+
+    let mut tags = AudioTags::default();
+    for _ in 0..count {
+        let Some(len) = take_u32(&mut rest).map(|len| len as usize) else {
+            break;
+        };
+        let Some(comment) = rest.get(..len) else {
+            break;
+        };
+        rest = &rest[len..];
+
+        let comment = String::from_utf8_lossy(comment);
+        let Some((key, value)) = comment.split_once('=') else {
+            continue;
+        };
+        let value = value.trim();
+        if value.is_empty() {
+            continue;
+        }
+
+        let text = Some(value.to_string());
+        match key.to_uppercase().as_str() {
+            "ARTIST" => tags.artist = tags.artist.take().or(text),
+            "ALBUMARTIST" | "ALBUM ARTIST" => tags.album_artist = tags.album_artist.take().or(text),
+            "ALBUM" => tags.album = tags.album.take().or(text),
+            "TITLE" => tags.title = tags.title.take().or(text),
+            "TRACKNUMBER" => tags.track = tags.track.or(leading_number(value)),
+            "DISCNUMBER" => tags.disc = tags.disc.or(leading_number(value)),
+            "GENRE" => tags.genre = tags.genre.take().or(text),
+            _ => {}
+        }
+    }
+    Some(tags)
+}
+
+fn take_u32(data: &mut &[u8]) -> Option<u32> {
+    let (bytes, rest) = data.split_first_chunk::<4>()?;
+    *data = rest;
+    Some(u32::from_le_bytes(*bytes))
+}
+
+/// "3" or "3/12" → 3
+fn leading_number(value: &str) -> Option<u32> {
+    value.split('/').next()?.trim().parse().ok()
+}
+
+fn flac_comments<R: Read + Seek>(reader: &mut R) -> Option<AudioTags> {
+    reader.seek(SeekFrom::Start(4)).ok()?;
+    loop {
+        let mut header = [0u8; 4];
+        reader.read_exact(&mut header).ok()?;
+        let last = header[0] & 0x80 != 0;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+
+        // Block type 4 is VORBIS_COMMENT
+        if header[0] & 0x7f == 4 {
+            if len > MAX_COMMENT_LEN {
+                return None;
+            }
+            let mut block = vec![0u8; len];
+            reader.read_exact(&mut block).ok()?;
+            return from_comments(&block);
+        }
+        if last {
+            return None;
+        }
+        reader.seek(SeekFrom::Current(len as i64)).ok()?;
+    }
+}
+
+/// Reassembles the second packet of the first logical stream, which holds the
+/// comments in both Vorbis ("\x03vorbis") and Opus ("OpusTags") files
+fn ogg_comments<R: Read>(reader: &mut R) -> Option<AudioTags> {
+    let mut packets: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut serial = None;
+
+    for _ in 0..MAX_OGG_PAGES {
+        let mut header = [0u8; 27];
+        reader.read_exact(&mut header).ok()?;
+        if &header[..4] != b"OggS" {
+            return None;
+        }
+        let mut lacing = vec![0u8; header[26] as usize];
+        reader.read_exact(&mut lacing).ok()?;
+        let mut body = vec![0u8; lacing.iter().map(|&l| l as usize).sum()];
+        reader.read_exact(&mut body).ok()?;
+
+        // Pages of other streams in a multiplexed file are skipped
+        if *serial.get_or_insert(header[14..18].to_vec()) != header[14..18] {
+            continue;
+        }
+
+        let mut offset = 0;
+        for &len in &lacing {
+            let current = packets.last_mut()?;
+            current.extend_from_slice(&body[offset..offset + len as usize]);
+            offset += len as usize;
+            if current.len() > MAX_COMMENT_LEN {
+                return None;
+            }
+            if len < 255 {
+                if packets.len() == 2 {
+                    let packet = &packets[1];
+                    let data = packet
+                        .strip_prefix(b"\x03vorbis")
+                        .or_else(|| packet.strip_prefix(b"OpusTags"))?;
+                    return from_comments(data);
+                }
+                packets.push(Vec::new());
+            }
+        }
+    }
+    None
+}
+
+/// Reads `moov/udta/meta/ilst`, as written by iTunes and most taggers
+fn mp4_tags<R: Read + Seek>(reader: &mut R) -> Option<AudioTags> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    let (start, end) = find_box(reader, 0, end, b"moov")?;
+    let (start, end) = find_box(reader, start, end, b"udta")?;
+    let (mut start, end) = find_box(reader, start, end, b"meta")?;
+
+    // `meta` is a full box with four bytes of version and flags, except in some
+    // QuickTime files where its children follow straight away
+    reader.seek(SeekFrom::Start(start)).ok()?;
+    let mut flags = [0u8; 4];
+    reader.read_exact(&mut flags).ok()?;
+    if flags == [0; 4] {
+        start += 4;
+    }
+    let (start, end) = find_box(reader, start, end, b"ilst")?;
+
+    let mut tags = AudioTags::default();
+    let mut position = start;
+    while end.saturating_sub(position) >= 8 {
+        reader.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+        let size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        if size < 8 || size > end - position {
+            break;
+        }
+
+        let kind: [u8; 4] = header[4..8].try_into().ok()?;
+        if let Some((data_start, data_end)) = find_box(reader, position + 8, position + size, b"data") {
+            // Skip the type indicator and locale in front of the value
+            let len = data_end.saturating_sub(data_start + 8) as usize;
+            if len <= MAX_COMMENT_LEN {
+                let mut value = vec![0u8; len];
+                reader.seek(SeekFrom::Start(data_start + 8)).ok()?;
+                reader.read_exact(&mut value).ok()?;
+                apply_mp4_item(&mut tags, &kind, &value);
+            }
+        }
+        position += size;
+    }
+    Some(tags)
+}
+
+fn apply_mp4_item(tags: &mut AudioTags, kind: &[u8; 4], value: &[u8]) {
+    let text = || {
+        Some(String::from_utf8_lossy(value).trim().to_string()).filter(|v| !v.is_empty())
+    };
+    // Track and disc numbers are two padding bytes, then the number as a big-endian u16
+    let number = || {
+        value
+            .get(2..4)
+            .map(|n| u32::from(u16::from_be_bytes([n[0], n[1]])))
+            .filter(|&n| n > 0)
+    };
+
+    match kind {
+        b"\xa9ART" => tags.artist = text(),
+        b"aART" => tags.album_artist = text(),
+        b"\xa9alb" => tags.album = text(),
+        b"\xa9nam" => tags.title = text(),
+        b"\xa9gen" => tags.genre = text(),
+        b"trkn" => tags.track = number(),
+        b"disk" => tags.disc = number(),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comments(entries: &[&str]) -> Vec<u8> {
+        let mut data = 6u32.to_le_bytes().to_vec();
+        data.extend_from_slice(b"kondo!");
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            data.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            data.extend_from_slice(entry.as_bytes());
+        }
+        data
+    }
+
+    fn mp4_box(kind: &[u8], content: &[u8]) -> Vec<u8> {
+        let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(content);
+        data
+    }
+
+    #[test]
+    fn test_flac_and_mp4_tags() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let block = comments(&[
+            "ARTIST=Nina Simone",
+            "album=Pastel Blues",
+            "TRACKNUMBER=3/9",
+            "TITLE=Be My Husband",
+        ]);
+        let mut flac = b"fLaC".to_vec();
+        flac.extend_from_slice(&[0x00, 0, 0, 2, 0xaa, 0xbb]); // STREAMINFO stand-in
+        flac.push(0x84);
+        flac.extend_from_slice(&(block.len() as u32).to_be_bytes()[1..]);
+        flac.extend_from_slice(&block);
+        let path = dir.path().join("song.flac");
+        std::fs::write(&path, flac).unwrap();
+
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Nina Simone"));
+        assert_eq!(tags.album.as_deref(), Some("Pastel Blues"));
+        assert_eq!(tags.track, Some(3));
+
+        let item = |kind: &[u8], value: &[u8]| {
+            let mut data = vec![0u8; 8];
+            data.extend_from_slice(value);
+            mp4_box(kind, &mp4_box(b"data", &data))
+        };
+        let mut ilst = item(b"\xa9ART", b"Miles Davis");
+        ilst.extend(item(b"\xa9nam", b"So What"));
+        ilst.extend(item(b"trkn", &[0, 0, 0, 1, 0, 5, 0, 0]));
+        let mut meta = vec![0u8; 4];
+        meta.extend(mp4_box(b"ilst", &ilst));
+        let mut m4a = mp4_box(b"ftyp", b"M4A \0\0\0\0");
+        m4a.extend(mp4_box(b"moov", &mp4_box(b"udta", &mp4_box(b"meta", &meta))));
+        let path = dir.path().join("song.m4a");
+        std::fs::write(&path, m4a).unwrap();
+
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(tags.title.as_deref(), Some("So What"));
+        assert_eq!(tags.track, Some(1));
+        assert_eq!(tags.album, None);
+    }
+
+    #[test]
+    fn test_id3_tags() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("song.mp3");
+        let mut tag = id3::Tag::new();
+        tag.set_artist("Portishead");
+        tag.set_album("Dummy");
+        tag.set_track(4);
+        let mut data = Vec::new();
+        tag.write_to(&mut data, id3::Version::Id3v24).unwrap();
+        data.extend_from_slice(&[0xff, 0xfb, 0x90, 0x00]);
+        std::fs::write(&path, data).unwrap();
+
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Portishead"));
+        assert_eq!(tags.album.as_deref(), Some("Dummy"));
+        assert_eq!(tags.track, Some(4));
+        assert_eq!(tags.title, None);
+
+        std::fs::write(&path, [0xff, 0xfb, 0x90, 0x00]).unwrap();
+        assert_eq!(read_audio_tags(&path), None);
+    }
+}
@@ -7,18 +7,35 @@ use std::io;
 use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 
+use super::error::{FileError, KondoError};
+use super::ignore::SkipMatcher;
+use super::journal::Journal;
+use super::rules::{self, FileFacts, Rule, RuleConfig};
+use super::sniff::{self, Format};
+use super::template::{PathTemplate, TemplateContext, TemplateError};
+use super::plan::{
+    self, expand_home, ExecuteOptions, Outcome, OutcomeFn, Plan, PlanBuilder, PlanOptions,
+    SkipReason, SkippedFile,
+};
+use super::transfer::{self, ProgressFn, TransferMode};
+use super::walk::{self, Walk};
+
 // Configuration Structures
 
-#[derive(Debug, Deserialize, Serialize)]
+/// What categorize mode works from, taken from kondo.toml by `KondoConfig::categorize_config`
+#[derive(Debug)]
 pub struct FileOrganizerConfig {
-    #[serde(default)]
     pub categories: HashMap<String, CategoryConfig>,
 
-    #[serde(default = "default_batch_size")]
-    pub batch_size: usize,
+    /// move | copy | hardlink | symlink; every mode except move leaves the originals alone
+    pub transfer_mode: TransferMode,
 
-    #[serde(default)]
-    pub skip_patterns: Vec<String>,
+    /// Checked in order before the extension map; the first match wins
+    pub rules: Vec<RuleConfig>,
+
+    /// Read the first bytes of each file to classify extensionless files and
+    /// warn about extensions that do not match the content
+    pub sniff_content: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -27,29 +44,29 @@ pub struct CategoryConfig {
 
     #[serde(default)]
     pub folder_name: Option<String>,
-}
 
-fn default_batch_size() -> usize {
-    100
+    /// Overrides where the category goes, e.g. "~/Pictures"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<String>,
+
+    /// Sub-path built per file, e.g. "{folder}/{year}/{month}"; replaces `folder_name`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_template: Option<String>,
 }
 
 impl Default for FileOrganizerConfig {
     fn default() -> Self {
         Self {
             categories: create_default_categories(),
-            batch_size: 100,
-            skip_patterns: vec![
-                ".DS_Store".to_string(),
-                "Thumbs.db".to_string(),
-                ".git".to_string(),
-                ".gitignore".to_string(),
-                "desktop.ini".to_string(),
-            ],
+            transfer_mode: TransferMode::default(),
+            rules: Vec::new(),
+            sniff_content: false,
         }
     }
 }
 
-fn create_default_categories() -> HashMap<String, CategoryConfig> {
+/// Categories used when kondo.toml has none
+pub fn create_default_categories() -> HashMap<String, CategoryConfig> {
     let mut map = HashMap::new();
 
     map.insert(
@@ -62,6 +79,8 @@ fn create_default_categories() -> HashMap<String, CategoryConfig> {
             .map(String::from)
             .collect(),
             folder_name: Some("Images".to_string()),
+            destination: None,
+            path_template: None,
         },
     );
 
@@ -75,6 +94,8 @@ fn create_default_categories() -> HashMap<String, CategoryConfig> {
             .map(String::from)
             .collect(),
             folder_name: Some("Videos".to_string()),
+            destination: None,
+            path_template: None,
         },
     );
 
@@ -88,6 +109,8 @@ fn create_default_categories() -> HashMap<String, CategoryConfig> {
             .map(String::from)
             .collect(),
             folder_name: Some("Audio".to_string()),
+            destination: None,
+            path_template: None,
         },
     );
 
@@ -101,6 +124,8 @@ fn create_default_categories() -> HashMap<String, CategoryConfig> {
             .map(String::from)
             .collect(),
             folder_name: Some("Documents".to_string()),
+            destination: None,
+            path_template: None,
         },
     );
 
@@ -114,34 +139,59 @@ fn create_default_categories() -> HashMap<String, CategoryConfig> {
             .map(String::from)
             .collect(),
             folder_name: Some("Code".to_string()),
+            destination: None,
+            path_template: None,
         },
     );
 
     map
 }
 
-
 // Config Loading
 
 impl FileOrganizerConfig {
-    pub fn load_from_file(path: &Path) -> io::Result<Self> {
-        let content = fs::read_to_string(path)?;
-        toml::from_str(&content).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("TOML parse error: {}", e),
-            )
-        })
+    /// Checks path templates and rules so mistakes surface before anything is planned
+    pub fn validate(&self) -> Result<(), String> {
+        self.path_templates().map_err(|e| e.to_string())?;
+        rules::compile_rules(&self.rules)?;
+        Ok(())
     }
 
-    pub fn save_to_file(&self, path: &Path) -> io::Result<()> {
-        let content = toml::to_string_pretty(self).map_err(|e| {
-            io::Error::new(
-                io::ErrorKind::InvalidData,
-                format!("TOML serialize error: {}", e),
-            )
-        })?;
-        fs::write(path, content)
+    /// Parses every category's `path_template`, keyed by category
+    pub fn path_templates(&self) -> Result<HashMap<String, PathTemplate>, TemplateError> {
+        let mut templates = HashMap::new();
+        for (category_key, config) in &self.categories {
+            if let Some(template) = &config.path_template {
+                let parsed = PathTemplate::parse(template).map_err(|e| {
+                    TemplateError::new(format!("category '{}': {}", category_key, e))
+                })?;
+                templates.insert(category_key.clone(), parsed);
+            }
+        }
+        Ok(templates)
+    }
+
+    /// Folder a category is sorted into: its `destination` if configured (absolute, or
+    /// relative to the output root), otherwise `folder_name` below the output root
+    pub fn category_dir(&self, category_key: &str, folder_name: &str, output_root: &Path) -> PathBuf {
+        match self.destination(category_key) {
+            Some(destination) => output_root.join(expand_home(destination)),
+            None => output_root.join(folder_name),
+        }
+    }
+
+    /// Folder a category's `path_template` is expanded below
+    pub fn template_root(&self, category_key: &str, output_root: &Path) -> PathBuf {
+        match self.destination(category_key) {
+            Some(destination) => output_root.join(expand_home(destination)),
+            None => output_root.to_path_buf(),
+        }
+    }
+
+    fn destination(&self, category_key: &str) -> Option<&str> {
+        self.categories
+            .get(category_key)
+            .and_then(|c| c.destination.as_deref())
     }
 
     /// Build reverse lookup map: extension -> (category_key, folder_name)
@@ -167,37 +217,6 @@ impl FileOrganizerConfig {
     }
 }
 
-// Lazy Directory Manager (Reduces syscalls)
-
-pub struct LazyDirManager {
-    created_dirs: Arc<Mutex<HashMap<PathBuf, bool>>>,
-}
-
-impl LazyDirManager {
-    pub fn new() -> Self {
-        Self {
-            created_dirs: Arc::new(Mutex::new(HashMap::new())),
-        }
-    }
-
-    /// Only creates directory if not already created in this session
-    pub fn ensure_dir_exists(&self, path: &Path) -> io::Result<()> {
-        let mut cache = self.created_dirs.lock().unwrap();
-
-        if cache.contains_key(path) {
-            return Ok(());
-        }
-
-        // Check if exists before creating
-        if !path.exists() {
-            fs::create_dir_all(path)?;
-        }
-
-        cache.insert(path.to_path_buf(), true);
-        Ok(())
-    }
-}
-
 // Error-Safe Logger
 
 pub struct SafeLogger {
@@ -208,15 +227,13 @@ pub struct SafeLogger {
 pub struct LogEntry {
     pub level: LogLevel,
     pub message: String,
-    #[allow(dead_code)]
-    pub details: Option<String>,
+    pub error: Option<KondoError>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum LogLevel {
     Info,
     Success,
-    #[allow(dead_code)]
     Warning,
     Error,
 }
@@ -228,12 +245,12 @@ impl SafeLogger {
         }
     }
 
-    pub fn log(&self, level: LogLevel, message: String, details: Option<String>) {
+    pub fn log(&self, level: LogLevel, message: String, error: Option<KondoError>) {
         if let Ok(mut entries) = self.log_entries.lock() {
             entries.push(LogEntry {
                 level,
                 message,
-                details,
+                error,
             });
         }
     }
@@ -244,199 +261,475 @@ impl SafeLogger {
             .map(|e| e.clone())
             .unwrap_or_default()
     }
-
-    // pub fn clear(&self) {
-    //     if let Ok(mut entries) = self.log_entries.lock() {
-    //         entries.clear();
-    //     }
-    // }
 }
 
 // Fast File Organizer (with parallelization)
 
+/// Where a file belongs according to the rules or its extension
+struct Classification {
+    group: String,
+    reason: String,
+    target: PathBuf,
+}
+
+/// Everything classification needs, worked out once per run
+struct Classifier {
+    ext_map: HashMap<String, (String, String)>,
+    templates: HashMap<String, PathTemplate>,
+    rules: Vec<Rule>,
+    output_root: PathBuf,
+}
+
+/// Classification of a file, or why it stays put
+type Decision = Result<Classification, SkipReason>;
+
 pub struct FileOrganizer {
     config: FileOrganizerConfig,
-    dir_manager: LazyDirManager,
     logger: SafeLogger,
+    journal: Option<Journal>,
+    plan_options: PlanOptions,
+    /// Reports progress of large cross-filesystem copies
+    progress: Option<ProgressFn>,
+    on_outcome: Option<OutcomeFn>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct OrganizeResult {
     pub files_organized: usize,
     pub files_skipped: usize,
     pub files_failed: usize,
+    /// Organized under a new name because the destination was taken
+    pub files_renamed: usize,
+    /// Organized by replacing an existing file
+    pub files_overwritten: usize,
+    /// Deleted because an identical file was already at the destination
+    pub duplicates_deleted: usize,
     pub category_counts: HashMap<String, usize>,
+    pub skipped_details: Vec<SkippedFile>,
+    pub errors: Vec<FileError>,
+    pub dry_run: bool,
 }
 
 impl FileOrganizer {
     pub fn new(config: FileOrganizerConfig) -> Self {
         Self {
             config,
-            dir_manager: LazyDirManager::new(),
             logger: SafeLogger::new(),
+            journal: None,
+            plan_options: PlanOptions::default(),
+            progress: None,
+            on_outcome: None,
         }
     }
 
+    /// Records every real move in the given journal so the run can be undone
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Warnings logged so far, e.g. mislabeled files found by content sniffing
+    pub fn warnings(&self) -> Vec<String> {
+        self.logger
+            .get_logs()
+            .into_iter()
+            .filter(|log| log.level == LogLevel::Warning)
+            .map(|log| log.message)
+            .collect()
+    }
+
+    /// Calls `on_outcome` for every file as soon as it has been handled
+    pub fn with_outcome_sink(mut self, on_outcome: OutcomeFn) -> Self {
+        self.on_outcome = Some(on_outcome);
+        self
+    }
+
+    /// Controls which files are collected and where organized folders are created
+    pub fn with_plan_options(mut self, plan_options: PlanOptions) -> Self {
+        self.plan_options = plan_options;
+        self
+    }
+
     pub fn get_logger(&self) -> &SafeLogger {
         &self.logger
     }
 
-    pub fn organize_directory(
-        &self,
-        base_path: &Path,
-        dry_run: bool,
-    ) -> io::Result<OrganizeResult> {
-        let ext_map = self.config.build_extension_map();
+    fn classifier(&self, output_root: PathBuf) -> io::Result<Classifier> {
+        let invalid = |e: String| io::Error::from(KondoError::ConfigInvalid(e));
+        Ok(Classifier {
+            ext_map: self.config.build_extension_map(),
+            templates: self.config.path_templates().map_err(|e| invalid(e.to_string()))?,
+            rules: rules::compile_rules(&self.config.rules).map_err(invalid)?,
+            output_root,
+        })
+    }
 
-        // Collect all file entries
-        let entries: Vec<_> = fs::read_dir(base_path)?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_file())
-            .collect();
+    /// Sends a file to the first matching rule, otherwise to its extension's category
+    fn classify(&self, file_path: &Path, classifier: &Classifier) -> Decision {
+        let filename = match file_path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name,
+            None => return Err(SkipReason::InvalidName),
+        };
 
-        let category_counts = Arc::new(Mutex::new(HashMap::new()));
-        let files_organized = Arc::new(Mutex::new(0usize));
-        let files_skipped = Arc::new(Mutex::new(0usize));
-        let files_failed = Arc::new(Mutex::new(0usize));
-
-        // Process files in parallel for speed
-        entries.par_iter().for_each(|entry| {
-            let file_path = entry.path();
-            let filename = match file_path.file_name().and_then(|n| n.to_str()) {
-                Some(name) => name,
-                None => {
-                    *files_skipped.lock().unwrap() += 1;
-                    return;
-                }
-            };
+        // Only stat when a rule or template needs size or dates
+        let metadata = if classifier.rules.is_empty() && classifier.templates.is_empty() {
+            None
+        } else {
+            fs::metadata(file_path).ok()
+        };
 
-            // Skip system files
-            if self.should_skip_file(filename) {
-                self.logger
-                    .log(LogLevel::Info, format!("Skipped: {}", filename), None);
-                *files_skipped.lock().unwrap() += 1;
-                return;
+        let mut extension = file_path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_lowercase())
+            .unwrap_or_default();
+        let content = self.sniff(file_path);
+        let mut note = None;
+        match content {
+            // Extensionless files are classified by what they contain
+            Some(format) if extension.is_empty() => {
+                extension = sniffed_extension(format, classifier);
+                note = Some(format!("content looks like {}", format.name));
             }
+            Some(format) if !format.accepts(&extension) => {
+                let mismatch = format!("content looks like {}, not .{}", format.name, extension);
+                self.logger.log(
+                    LogLevel::Warning,
+                    format!("{}: {}", filename, mismatch),
+                    None,
+                );
+                note = Some(mismatch);
+            }
+            _ => {}
+        }
+        let with_note = |reason: String| match &note {
+            Some(note) => format!("{} ({})", reason, note),
+            None => reason,
+        };
 
-            // Get extension and category
-            let extension = file_path
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .map(|ext| ext.to_lowercase())
-                .unwrap_or_else(|| "unknown".to_string());
+        if !classifier.rules.is_empty() {
+            let facts = rule_facts(file_path, metadata.as_ref(), content, classifier);
+            if let Some(rule) = rules::first_match(&classifier.rules, &facts) {
+                let root = rule.destination_dir(&classifier.output_root);
+                let group = root
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| rule.name.clone());
+                let target = match &rule.template {
+                    Some(template) => root.join(template.destination(&TemplateContext {
+                        path: file_path,
+                        category: &rule.name,
+                        folder: &group,
+                        extension: &facts.extension,
+                        metadata: metadata.as_ref(),
+                    })),
+                    None => root.join(filename),
+                };
+                return Ok(Classification {
+                    reason: with_note(format!("rule '{}' → {}", rule.name, group)),
+                    group,
+                    target,
+                });
+            }
+        }
 
-            let (_category_key, folder_name) = ext_map
-                .get(&extension)
-                .cloned()
-                .unwrap_or_else(|| ("extras".to_string(), "Extras".to_string()));
-
-            let target_dir = base_path.join(&folder_name);
-            let target_path = target_dir.join(filename);
-
-            // Handle naming conflicts
-            let final_target = match self.handle_naming_conflict(&target_path) {
-                Ok(path) => path,
-                Err(e) => {
-                    self.logger.log(
-                        LogLevel::Error,
-                        format!("Naming conflict for: {}", filename),
-                        Some(e.to_string()),
-                    );
-                    *files_failed.lock().unwrap() += 1;
-                    return;
-                }
-            };
+        // Get extension and category
+        if extension.is_empty() {
+            extension = "unknown".to_string();
+        }
 
-            if !dry_run {
-                // Lazy create directory
-                if let Err(e) = self.dir_manager.ensure_dir_exists(&target_dir) {
-                    self.logger.log(
-                        LogLevel::Error,
-                        format!("Failed to create dir: {}", folder_name),
-                        Some(e.to_string()),
-                    );
-                    *files_failed.lock().unwrap() += 1;
-                    return;
-                }
+        let (category, folder) = classifier
+            .ext_map
+            .get(&extension)
+            .cloned()
+            .unwrap_or_else(|| ("extras".to_string(), "Extras".to_string()));
+
+        let output_root = &classifier.output_root;
+        let target = match classifier.templates.get(&category) {
+            Some(template) => {
+                let context = TemplateContext {
+                    path: file_path,
+                    category: &category,
+                    folder: &folder,
+                    extension: &extension,
+                    metadata: metadata.as_ref(),
+                };
+                self.config
+                    .template_root(&category, output_root)
+                    .join(template.destination(&context))
+            }
+            None => self
+                .config
+                .category_dir(&category, &folder, output_root)
+                .join(filename),
+        };
+
+        Ok(Classification {
+            reason: with_note(format!("extension .{} → {}", extension, folder)),
+            group: folder,
+            target,
+        })
+    }
 
-                // Move file
-                if let Err(e) = fs::rename(&file_path, &final_target) {
-                    self.logger.log(
-                        LogLevel::Error,
-                        format!("Failed to move: {}", filename),
-                        Some(e.to_string()),
-                    );
-                    *files_failed.lock().unwrap() += 1;
-                    return;
+    /// Reports how a single file would be categorized: its facts, every rule that
+    /// was tried in order and where the file ends up
+    pub fn explain(&self, file_path: &Path) -> io::Result<Vec<String>> {
+        let metadata = fs::metadata(file_path)?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("not a file: {}", file_path.display()),
+            ));
+        }
+
+        let base_path = file_path.parent().unwrap_or(Path::new("."));
+        let classifier = self.classifier(self.plan_options.output_root(base_path))?;
+        let content = self.sniff(file_path);
+        let facts = rule_facts(file_path, Some(&metadata), content, &classifier);
+
+        let mut lines = vec![
+            format!("File:      {}", file_path.display()),
+            format!("Extension: {}", if facts.extension.is_empty() { "(none)" } else { &facts.extension }),
+            format!("Size:      {} bytes", metadata.len()),
+            format!(
+                "Age:       {}",
+                facts
+                    .age
+                    .map(|age| format!("{} days", age.as_secs() / 86_400))
+                    .unwrap_or_else(|| "unknown".to_string())
+            ),
+            format!("MIME type: {}", facts.mime),
+        ];
+        if self.config.sniff_content {
+            let content = content.map_or("not recognised", |format| format.name);
+            lines.push(format!("Content:   {}", content));
+        }
+        lines.push(String::new());
+
+        if classifier.rules.is_empty() {
+            lines.push("No rules configured".to_string());
+        } else {
+            lines.push("Rules:".to_string());
+            for rule in &classifier.rules {
+                match rule.check(&facts) {
+                    Ok(()) => {
+                        lines.push(format!("  ✓ {}: matched", rule.name));
+                        break;
+                    }
+                    Err(why) => lines.push(format!("  ✗ {}: {}", rule.name, why)),
                 }
             }
+        }
+        lines.push(String::new());
 
-            self.logger.log(
-                LogLevel::Success,
-                format!("{} → {}", filename, folder_name),
-                None,
-            );
-
-            *files_organized.lock().unwrap() += 1;
-            *category_counts
-                .lock()
-                .unwrap()
-                .entry(folder_name)
-                .or_insert(0) += 1;
-        });
+        let skip = SkipMatcher::for_directory(base_path, &self.plan_options.walk.skip_patterns)?;
+        let decision = if skip.is_skipped(file_path, false) {
+            Err(SkipReason::SystemFile)
+        } else {
+            self.classify(file_path, &classifier)
+        };
+        match decision {
+            Ok(classification) => {
+                lines.push(format!("Decision:  {}", classification.reason));
+                lines.push(format!("Target:    {}", classification.target.display()));
+            }
+            Err(reason) => lines.push(format!("Decision:  skipped ({})", reason.describe())),
+        }
 
-        // Fix: Extract values before creating the result to avoid borrow issues
-        let organized_count = *files_organized.lock().unwrap();
-        let skipped_count = *files_skipped.lock().unwrap();
-        let failed_count = *files_failed.lock().unwrap();
-        let counts = category_counts.lock().unwrap().clone();
+        Ok(lines)
+    }
 
-        Ok(OrganizeResult {
-            files_organized: organized_count,
-            files_skipped: skipped_count,
-            files_failed: failed_count,
-            category_counts: counts,
-        })
+    /// Decides where every file in `base_path` goes without touching the filesystem
+    pub fn plan_directory(&self, base_path: &Path) -> io::Result<Plan> {
+        let classifier = self.classifier(self.plan_options.output_root(base_path))?;
+        let output_root = &classifier.output_root;
+
+        // Category and rule folders are never re-organized when walking recursively
+        let folder_names = classifier
+            .ext_map
+            .values()
+            .map(|(_, folder)| folder.clone())
+            .chain(["Extras".to_string()]);
+        let category_dirs = classifier
+            .ext_map
+            .values()
+            .map(|(category, folder)| match classifier.templates.get(category) {
+                Some(template) => self
+                    .config
+                    .template_root(category, output_root)
+                    .join(template.static_prefix()),
+                None => self.config.category_dir(category, folder, output_root),
+            })
+            .chain(classifier.rules.iter().map(|rule| rule.destination_dir(output_root)))
+            .filter(|dir| dir != output_root)
+            .chain([output_root.join("Extras")]);
+        let walk_options = self
+            .plan_options
+            .walk
+            .clone()
+            .with_organized_names(folder_names)
+            .with_organized_dirs(category_dirs);
+
+        // Collect all file entries
+        let walk = walk::collect_files(base_path, &walk_options)?;
+        Ok(self.plan_walk(base_path, &classifier, walk))
     }
 
-    fn should_skip_file(&self, filename: &str) -> bool {
-        self.config
-            .skip_patterns
-            .iter()
-            .any(|pattern| filename.contains(pattern))
+    /// Plans only the given files, e.g. ones watch mode saw arrive in `base_path`
+    pub fn plan_files(&self, base_path: &Path, files: Vec<PathBuf>) -> io::Result<Plan> {
+        let classifier = self.classifier(self.plan_options.output_root(base_path))?;
+        let walk = walk::split_files(base_path, files, &self.plan_options.walk)?;
+        Ok(self.plan_walk(base_path, &classifier, walk))
     }
 
-    fn handle_naming_conflict(&self, target_path: &Path) -> io::Result<PathBuf> {
-        if !target_path.exists() {
-            return Ok(target_path.to_path_buf());
+    fn plan_walk(&self, base_path: &Path, classifier: &Classifier, walk: Walk) -> Plan {
+        // Classify files in parallel for speed
+        let decisions: Vec<(PathBuf, Decision)> = walk
+            .files
+            .into_par_iter()
+            .map(|file_path| {
+                let decision = self.classify(&file_path, classifier);
+                (file_path, decision)
+            })
+            .collect();
+
+        // Resolve destinations sequentially so planned names never collide
+        let mut builder = PlanBuilder::new("categorize", base_path)
+            .with_conflict(self.plan_options.conflict.clone());
+
+        for file_path in walk.organized {
+            builder.add_skip(file_path, SkipReason::AlreadyOrganized, None);
+        }
+        for file_path in walk.skipped {
+            builder.add_skip(file_path, SkipReason::SystemFile, None);
         }
 
-        let parent = target_path.parent().unwrap();
-        let stem = target_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or("file");
-        let extension = target_path
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|e| format!(".{}", e))
-            .unwrap_or_default();
+        for (file_path, decision) in decisions {
+            let Classification {
+                group,
+                reason,
+                target,
+            } = match decision {
+                Ok(classification) => classification,
+                Err(reason) => {
+                    builder.add_skip(file_path, reason, None);
+                    continue;
+                }
+            };
+
+            builder.add_move(file_path, target, &group, reason, None, None);
+        }
+
+        builder.build()
+    }
+
+    pub fn organize_directory(
+        &self,
+        base_path: &Path,
+        dry_run: bool,
+    ) -> io::Result<OrganizeResult> {
+        let plan = self.plan_directory(base_path)?;
+        let report = plan::execute(
+            &plan,
+            &ExecuteOptions {
+                dry_run,
+                journal: self.journal.as_ref(),
+                transfer: self.config.transfer_mode,
+                progress: self.progress,
+                on_outcome: self.on_outcome,
+            },
+        );
 
-        for i in 1..1000 {
-            let new_name = format!("{}_{}{}", stem, i, extension);
-            let new_path = parent.join(new_name);
-            if !new_path.exists() {
-                return Ok(new_path);
+        for outcome in &report.outcomes {
+            match outcome {
+                Outcome::Moved {
+                    source,
+                    group,
+                    ..
+                } => self.logger.log(
+                    LogLevel::Success,
+                    format!("{} → {}", display_name(source), group),
+                    None,
+                ),
+                Outcome::Renamed {
+                    source,
+                    destination,
+                    group,
+                } => self.logger.log(
+                    LogLevel::Success,
+                    format!(
+                        "{} → {} as {}",
+                        display_name(source),
+                        group,
+                        display_name(destination)
+                    ),
+                    None,
+                ),
+                Outcome::Overwritten { source, group, .. } => self.logger.log(
+                    LogLevel::Warning,
+                    format!("{} → {} (existing file moved to kondo-trash)", display_name(source), group),
+                    None,
+                ),
+                Outcome::DeletedDuplicate {
+                    source,
+                    duplicate_of,
+                } => self.logger.log(
+                    LogLevel::Info,
+                    format!(
+                        "Deleted duplicate: {} (identical to {})",
+                        display_name(source),
+                        duplicate_of.display()
+                    ),
+                    None,
+                ),
+                Outcome::Skipped { source, .. } => self.logger.log(
+                    LogLevel::Info,
+                    format!("Skipped: {}", display_name(source)),
+                    None,
+                ),
+                Outcome::Failed { path, error } => self.logger.log(
+                    LogLevel::Error,
+                    format!("Failed to move: {}", display_name(path)),
+                    Some(error.clone()),
+                ),
+                Outcome::DirCreated { .. } => {}
             }
         }
 
-        Err(io::Error::new(
-            io::ErrorKind::AlreadyExists,
-            "Could not find available filename after 999 attempts",
-        ))
+        for warning in &report.warnings {
+            self.logger
+                .log(LogLevel::Warning, warning.clone(), None);
+        }
+
+        Ok(OrganizeResult {
+            files_organized: report.files_moved(),
+            files_skipped: report.files_skipped(),
+            files_failed: report.files_failed(),
+            files_renamed: report.files_renamed(),
+            files_overwritten: report.files_overwritten(),
+            duplicates_deleted: report.duplicates_deleted(),
+            category_counts: report.group_counts(),
+            skipped_details: report.skipped_details(),
+            errors: report.errors(),
+            dry_run,
+        })
+    }
+
+    /// Detected format when content sniffing is enabled
+    fn sniff(&self, file_path: &Path) -> Option<&'static Format> {
+        if !self.config.sniff_content {
+            return None;
+        }
+        sniff::sniff_file(file_path).ok().flatten()
     }
 }
 
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.display().to_string())
+}
+
 // TUI Implementation
 
 
@@ -461,6 +754,8 @@ pub struct TuiApp {
     organizer: FileOrganizer,
     base_path: PathBuf,
     state: AppState,
+    /// `--dry-run`: every run only previews, whichever key starts it
+    dry_run: bool,
 }
 
 enum AppState {
@@ -475,6 +770,37 @@ impl TuiApp {
             organizer: FileOrganizer::new(config),
             base_path,
             state: AppState::Ready,
+            dry_run: false,
+        }
+    }
+
+    /// Only offer previews, for `--dry-run`
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Journal real moves so the run can be undone with `kondo undo`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.organizer = self.organizer.with_journal(journal);
+        self
+    }
+
+    pub fn with_plan_options(mut self, plan_options: PlanOptions) -> Self {
+        self.organizer = self.organizer.with_plan_options(plan_options);
+        self
+    }
+
+    /// Warnings logged during the run, such as content that does not match its extension
+    pub fn warnings(&self) -> Vec<String> {
+        self.organizer.warnings()
+    }
+
+    /// Result of the finished run, if organizing got that far
+    pub fn result(&self) -> Option<&OrganizeResult> {
+        match &self.state {
+            AppState::Complete(result) => Some(result),
+            _ => None,
         }
     }
 
@@ -532,7 +858,7 @@ impl TuiApp {
         self.state = AppState::Organizing;
         let result = self
             .organizer
-            .organize_directory(&self.base_path, dry_run)?;
+            .organize_directory(&self.base_path, dry_run || self.dry_run)?;
         self.state = AppState::Complete(result);
         Ok(())
     }
@@ -575,7 +901,7 @@ impl TuiApp {
     fn draw_ready_state(&self, f: &mut ratatui::Frame, area: Rect) {
         let categories = self.organizer.config.categories.len();
 
-        let text = vec![
+        let mut text = vec![
             Line::from(""),
             Line::from(Span::styled(
                 " Ready to Organize Files",
@@ -604,15 +930,22 @@ impl TuiApp {
             Line::from("  • Creates folders only when needed"),
             Line::from("  • Handles naming conflicts automatically"),
             Line::from(""),
-            Line::from(Span::styled(
+        ];
+        if self.dry_run {
+            text.push(Line::from(Span::styled(
+                " Press 'd' for dry run (--dry-run: nothing is moved)",
+                Style::default().fg(Color::Yellow),
+            )));
+        } else {
+            text.push(Line::from(Span::styled(
                 " Press 's' to start organizing",
                 Style::default().fg(Color::Green),
-            )),
-            Line::from(Span::styled(
+            )));
+            text.push(Line::from(Span::styled(
                 " Press 'd' for dry run (preview only)",
                 Style::default().fg(Color::Yellow),
-            )),
-        ];
+            )));
+        }
 
         let widget =
             Paragraph::new(text).block(Block::default().borders(Borders::ALL).title(" Status "));
@@ -728,7 +1061,11 @@ impl TuiApp {
                     LogLevel::Warning => (Style::default().fg(Color::Yellow), "⚠"),
                     LogLevel::Info => (Style::default().fg(Color::Gray), "ℹ"),
                 };
-                ListItem::new(format!("{} {}", icon, log.message)).style(style)
+                let text = match &log.error {
+                    Some(error) => format!("{} {}: {}", icon, log.message, error),
+                    None => format!("{} {}", icon, log.message),
+                };
+                ListItem::new(text).style(style)
             })
             .collect();
 
@@ -742,6 +1079,7 @@ impl TuiApp {
 
     fn draw_controls(&self, f: &mut ratatui::Frame, area: Rect) {
         let controls = match &self.state {
+            AppState::Ready if self.dry_run => " 'd' Dry Run | 'q' Quit",
             AppState::Ready => " 's' Start | 'd' Dry Run | 'q' Quit",
             AppState::Organizing => " Organizing... Please wait",
             AppState::Complete(_) => " 'q' Quit (or press any key to exit)",
@@ -754,20 +1092,36 @@ impl TuiApp {
     }
 
     /// Auto-organize files without UI interaction
-    /// Automatically starts organization (equivalent to pressing 's')
-    pub fn auto_organize(&mut self) -> io::Result<()> {
+    /// Automatically starts organization (equivalent to pressing 's', or 'd' for a dry run)
+    pub fn auto_organize(&mut self, dry_run: bool) -> io::Result<()> {
         // println!("📂 Scanning directory...");
 
-        // Start organization (non-dry-run mode)
-        self.start_organization(false)?;
+        // No TUI is drawn, so long copies can report on the terminal
+        self.organizer.progress = Some(transfer::print_progress);
+
+        self.start_organization(dry_run)?;
 
         // Display results
         if let AppState::Complete(result) = &self.state {
-            println!("\n✦ Organization Complete!\n");
-            println!("Summary:");
-            println!("   • Files organized: {}", result.files_organized);
+            if dry_run {
+                println!("\n[DRY RUN] Summary:");
+                println!("   • Files that would be organized: {}", result.files_organized);
+            } else {
+                println!("\n✦ Organization Complete!\n");
+                println!("Summary:");
+                println!("   • Files organized: {}", result.files_organized);
+            }
             println!("   • Files skipped:   {}", result.files_skipped);
             println!("   • Files failed:    {}", result.files_failed);
+            if result.files_renamed > 0 {
+                println!("   • Renamed:         {}", result.files_renamed);
+            }
+            if result.files_overwritten > 0 {
+                println!("   • Overwritten:     {}", result.files_overwritten);
+            }
+            if result.duplicates_deleted > 0 {
+                println!("   • Duplicates deleted: {}", result.duplicates_deleted);
+            }
 
             if !result.category_counts.is_empty() {
                 println!("\nCategories:");
@@ -792,8 +1146,16 @@ impl TuiApp {
                 }
             }
 
+            let warnings = self.warnings();
+            if !warnings.is_empty() {
+                println!("\nWarnings:");
+                for warning in &warnings {
+                    println!("   ⚠ {}", warning);
+                }
+            }
+
             // Show recent logs
-            let logs = self.organizer.get_logger().get_logs();
+            // let logs = self.organizer.get_logger().get_logs();
             // if !logs.is_empty() {
                 // println!("\n Recent activity:");
                 // for log in logs.iter().rev().take(5) {
@@ -812,6 +1174,33 @@ impl TuiApp {
     }
 }
 
+/// Extension an extensionless file is sorted by when its content is `format`: the
+/// first of the format's extensions that has a category
+fn sniffed_extension(format: &Format, classifier: &Classifier) -> String {
+    format
+        .extensions
+        .iter()
+        .find(|ext| classifier.ext_map.contains_key(**ext))
+        .unwrap_or(&format.extensions[0])
+        .to_string()
+}
+
+/// What rules are matched against. An extensionless file whose content was recognised
+/// gets the extension and MIME type of that format.
+fn rule_facts(
+    file_path: &Path,
+    metadata: Option<&fs::Metadata>,
+    content: Option<&Format>,
+    classifier: &Classifier,
+) -> FileFacts {
+    let mut facts = FileFacts::new(file_path, metadata);
+    if let Some(format) = content.filter(|_| facts.extension.is_empty()) {
+        facts.extension = sniffed_extension(format, classifier);
+        facts.mime = format.mime.to_string();
+    }
+    facts
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -819,7 +1208,7 @@ mod tests {
     #[test]
     fn test_config_serialization() {
         let config = FileOrganizerConfig::default();
-        let toml_str = toml::to_string(&config).unwrap();
+        let toml_str = toml::to_string(&config.categories).unwrap();
         assert!(toml_str.contains("images"));
         assert!(toml_str.contains("jpg"));
     }
@@ -833,4 +1222,34 @@ mod tests {
         assert!(ext_map.contains_key("mp4"));
         assert!(ext_map.contains_key("pdf"));
     }
+
+    #[test]
+    fn test_category_destination_overrides_output_root() {
+        let mut config = FileOrganizerConfig::default();
+        config.categories.get_mut("images").unwrap().destination = Some("/srv/photos".to_string());
+        let root = Path::new("/archive");
+
+        assert_eq!(config.category_dir("images", "Images", root), Path::new("/srv/photos"));
+        assert_eq!(config.category_dir("documents", "Documents", root), Path::new("/archive/Documents"));
+    }
+
+    #[test]
+    fn test_explain_matches_rules_on_sniffed_content() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("scan");
+        fs::write(&file, b"%PDF-1.7\n").unwrap();
+
+        let mut config = FileOrganizerConfig::default();
+        config.rules.push(rules::RuleConfig {
+            name: "pdfs".to_string(),
+            extensions: vec!["pdf".to_string()],
+            destination: "Papers".to_string(),
+            ..Default::default()
+        });
+        config.sniff_content = true;
+
+        let lines = FileOrganizer::new(config).explain(&file).unwrap();
+        assert!(lines.contains(&"  ✓ pdfs: matched".to_string()), "{:?}", lines);
+        assert!(lines.iter().any(|line| line.contains("Papers")), "{:?}", lines);
+    }
 }
@@ -0,0 +1,187 @@
+// What to do when a file's destination is already taken, shared by every mode
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Default pattern for renamed files, e.g. `report_1.pdf`
+pub const DEFAULT_RENAME_TEMPLATE: &str = "{stem}_{n}{ext}";
+
+/// Placeholders a rename template may use
+const RENAME_PLACEHOLDERS: &[&str] = &["stem", "n", "ext", "timestamp"];
+
+/// Highest counter tried before a file is skipped instead
+const MAX_ATTEMPTS: usize = 9999;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictPolicy {
+    /// Give the incoming file a free name built from the rename template
+    #[default]
+    Rename,
+    /// Leave the incoming file where it is
+    Skip,
+    /// Replace the existing file, which is moved into kondo-trash so undo can restore it
+    Overwrite,
+    /// Replace the existing file only if the incoming one was modified later
+    KeepNewer,
+    /// Replace the existing file only if the incoming one is larger
+    KeepLarger,
+    /// Delete the incoming file when it is byte-identical to the existing one,
+    /// otherwise rename it
+    DeleteIfIdentical,
+}
+
+impl fmt::Display for ConflictPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::KeepNewer => "keep-newer",
+            ConflictPolicy::KeepLarger => "keep-larger",
+            ConflictPolicy::DeleteIfIdentical => "delete-if-identical",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "rename" => Ok(ConflictPolicy::Rename),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "keep-newer" => Ok(ConflictPolicy::KeepNewer),
+            "keep-larger" => Ok(ConflictPolicy::KeepLarger),
+            "delete-if-identical" => Ok(ConflictPolicy::DeleteIfIdentical),
+            other => Err(format!(
+                "unknown conflict policy '{}' (expected rename, skip, overwrite, keep-newer, keep-larger or delete-if-identical)",
+                other
+            )),
+        }
+    }
+}
+
+/// Conflict policy plus the pattern used when files are renamed. The template is
+/// expected to have passed `validate_rename_template`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictOptions {
+    pub policy: ConflictPolicy,
+    pub rename_template: String,
+}
+
+impl Default for ConflictOptions {
+    fn default() -> Self {
+        Self {
+            policy: ConflictPolicy::default(),
+            rename_template: DEFAULT_RENAME_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl ConflictOptions {
+    /// First free alternative for `path` according to the rename template
+    pub fn free_name(&self, path: &Path, is_taken: &dyn Fn(&Path) -> bool) -> Option<PathBuf> {
+        if !is_taken(path) {
+            return Some(path.to_path_buf());
+        }
+
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let ext = path
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+        let timestamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let has_counter = self.rename_template.contains("{n}");
+
+        (1..=MAX_ATTEMPTS)
+            .map(|n| {
+                let mut name = self
+                    .rename_template
+                    .replace("{stem}", &stem)
+                    .replace("{n}", &n.to_string())
+                    .replace("{ext}", &ext)
+                    .replace("{timestamp}", &timestamp);
+                // Templates without a counter still need one once the first name is taken
+                if !has_counter && n > 1 {
+                    name = match name.strip_suffix(ext.as_str()).filter(|_| !ext.is_empty()) {
+                        Some(base) => format!("{}_{}{}", base, n, ext),
+                        None => format!("{}_{}", name, n),
+                    };
+                }
+                path.with_file_name(name)
+            })
+            .find(|candidate| !is_taken(candidate))
+    }
+}
+
+/// Checks placeholders and that the template cannot escape the destination folder
+pub fn validate_rename_template(template: &str) -> Result<(), String> {
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in rename template '{}'", template))?;
+        let name = &rest[start + 1..start + end];
+        if !RENAME_PLACEHOLDERS.contains(&name) {
+            return Err(format!(
+                "unknown placeholder {{{}}} in rename template '{}' (expected one of: {})",
+                name,
+                template,
+                RENAME_PLACEHOLDERS.join(", ")
+            ));
+        }
+        rest = &rest[start + end + 1..];
+    }
+
+    if template.contains(['/', '\\']) {
+        return Err(format!(
+            "rename template '{}' must not contain path separators",
+            template
+        ));
+    }
+    if !template.contains("{n}") && !template.contains("{timestamp}") {
+        return Err(format!(
+            "rename template '{}' needs {{n}} or {{timestamp}} to make names unique",
+            template
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_templates() {
+        let taken = |p: &Path| {
+            let name = p.file_name().unwrap().to_string_lossy();
+            name == "report.pdf" || name == "report (1).pdf"
+        };
+        let options = ConflictOptions {
+            policy: ConflictPolicy::Rename,
+            rename_template: "{stem} ({n}){ext}".to_string(),
+        };
+        assert_eq!(
+            options.free_name(Path::new("/x/report.pdf"), &taken).unwrap(),
+            Path::new("/x/report (2).pdf")
+        );
+
+        let default = ConflictOptions::default();
+        assert_eq!(
+            default.free_name(Path::new("/x/notes"), &|p| p.ends_with("notes")).unwrap(),
+            Path::new("/x/notes_1")
+        );
+
+        assert!(validate_rename_template("{stem}-{timestamp}{ext}").is_ok());
+        assert!(validate_rename_template("{stem}{ext}").is_err());
+        assert!(validate_rename_template("{stem}_{count}{ext}").is_err());
+        assert!(validate_rename_template("../{stem}_{n}{ext}").is_err());
+        assert_eq!("keep_newer".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::KeepNewer);
+    }
+}
@@ -0,0 +1,371 @@
+// Files items into dated folders such as "2024/03"
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Write};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+use super::error::KondoError;
+use super::media;
+use super::plan::{Plan, PlanBuilder, PlanOptions, SkipReason};
+use super::walk::{self, Walk};
+
+/// Where a file's date is taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DateSource {
+    /// When a photo or video was taken, from EXIF or the MP4/MOV header
+    Exif,
+    /// A date in the file name, e.g. `IMG_20240315_101500.jpg` or `log-2024-03-15.txt`
+    Filename,
+    /// Last modification time
+    Modified,
+    /// Creation time, where the filesystem records it
+    Created,
+}
+
+impl fmt::Display for DateSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DateSource::Exif => "exif",
+            DateSource::Filename => "filename",
+            DateSource::Modified => "modified",
+            DateSource::Created => "created",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for DateSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "exif" | "taken" => Ok(DateSource::Exif),
+            "filename" | "name" => Ok(DateSource::Filename),
+            "modified" | "mtime" => Ok(DateSource::Modified),
+            "created" | "ctime" => Ok(DateSource::Created),
+            other => Err(format!(
+                "unknown date source '{}' (expected exif, filename, modified or created)",
+                other
+            )),
+        }
+    }
+}
+
+/// How finely files are split up when no explicit `format` is set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    Year,
+    #[default]
+    Month,
+    Day,
+}
+
+impl Granularity {
+    fn format(&self) -> &'static str {
+        match self {
+            Granularity::Year => "%Y",
+            Granularity::Month => "%Y/%m",
+            Granularity::Day => "%Y/%m/%d",
+        }
+    }
+}
+
+impl FromStr for Granularity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "year" => Ok(Granularity::Year),
+            "month" => Ok(Granularity::Month),
+            "day" => Ok(Granularity::Day),
+            other => Err(format!(
+                "unknown granularity '{}' (expected year, month or day)",
+                other
+            )),
+        }
+    }
+}
+
+/// `[date_config]` in kondo.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateConfig {
+    /// Date sources in priority order; the first one a file has wins
+    #[serde(default = "default_sources")]
+    pub sources: Vec<DateSource>,
+
+    #[serde(default)]
+    pub granularity: Granularity,
+
+    /// strftime-style folder layout such as "%Y/%m-%B"; overrides `granularity`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+fn default_sources() -> Vec<DateSource> {
+    vec![DateSource::Exif, DateSource::Filename, DateSource::Modified]
+}
+
+impl Default for DateConfig {
+    fn default() -> Self {
+        Self {
+            sources: default_sources(),
+            granularity: Granularity::default(),
+            format: None,
+        }
+    }
+}
+
+impl DateConfig {
+    pub fn folder_format(&self) -> &str {
+        self.format.as_deref().unwrap_or(self.granularity.format())
+    }
+
+    /// Checks that there is a date source and that the format only uses date fields
+    /// and stays inside the destination
+    pub fn validate(&self) -> Result<(), String> {
+        if self.sources.is_empty() {
+            return Err("date_config.sources must list at least one date source".to_string());
+        }
+
+        let format = self.folder_format();
+        let sample = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap_or_default();
+        let mut expanded = String::new();
+        if format.trim().is_empty() || write!(expanded, "{}", sample.format(format)).is_err() {
+            return Err(format!(
+                "date format '{}' is invalid or uses fields other than the date",
+                format
+            ));
+        }
+        let escapes = Path::new(&expanded).is_absolute()
+            || expanded.starts_with(['/', '\\'])
+            || Path::new(&expanded)
+                .components()
+                .any(|c| matches!(c, Component::ParentDir));
+        if escapes {
+            return Err(format!(
+                "date format '{}' must stay inside the destination folder",
+                format
+            ));
+        }
+        Ok(())
+    }
+
+    /// Relative folder for `date`. The format must have passed `validate`.
+    pub fn folder(&self, date: NaiveDate) -> PathBuf {
+        date.format(self.folder_format())
+            .to_string()
+            .split(['/', '\\'])
+            .map(str::trim)
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect()
+    }
+}
+
+/// Length of the date-like run starting at `chars[start]`: four digits, at least four
+/// more characters, then any further digits, `-` or `_`. Filename grouping strips these
+/// runs from folder names and date mode reads dates from them.
+pub fn date_pattern_len(chars: &[char], start: usize) -> Option<usize> {
+    if start + 7 >= chars.len() || !chars[start..start + 4].iter().all(|c| c.is_numeric()) {
+        return None;
+    }
+
+    let mut end = start + 8;
+    while end < chars.len() && (chars[end].is_numeric() || chars[end] == '-' || chars[end] == '_') {
+        end += 1;
+    }
+    Some(end - start)
+}
+
+/// First valid date in a file name: `YYYYMMDD`, `YYYY-MM-DD` or `YYYY_MM_DD`, possibly
+/// followed by a time as in `IMG_20240315_101500`
+pub fn date_from_name(name: &str) -> Option<NaiveDate> {
+    let chars: Vec<char> = name.chars().collect();
+    (0..chars.len()).find_map(|start| {
+        let len = date_pattern_len(&chars, start)?;
+        parse_date_run(&chars[start..start + len].iter().collect::<String>())
+    })
+}
+
+fn parse_date_run(run: &str) -> Option<NaiveDate> {
+    let parts: Vec<&str> = run.split(['-', '_']).filter(|p| !p.is_empty()).collect();
+    if !parts.iter().all(|p| p.chars().all(|c| c.is_ascii_digit())) {
+        return None;
+    }
+
+    let (year, month, day) = match parts.as_slice() {
+        [digits, ..] if digits.len() >= 8 => (&digits[..4], &digits[4..6], &digits[6..8]),
+        [year, month, day, ..] if year.len() == 4 && month.len() <= 2 && day.len() <= 2 => {
+            (*year, *month, *day)
+        }
+        _ => return None,
+    };
+
+    let year: i32 = year.parse().ok()?;
+    if !(1900..=2100).contains(&year) {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)
+}
+
+/// The date of a file from the first source in `sources` that has one
+pub fn file_date(
+    path: &Path,
+    metadata: Option<&fs::Metadata>,
+    sources: &[DateSource],
+) -> Option<(NaiveDate, DateSource)> {
+    let local = |time: SystemTime| DateTime::<Local>::from(time).date_naive();
+
+    sources.iter().find_map(|source| {
+        let date = match source {
+            DateSource::Exif => media::read_media_info(path)
+                .and_then(|m| m.captured)
+                .map(|taken| taken.date()),
+            DateSource::Filename => path
+                .file_name()
+                .and_then(|name| date_from_name(&name.to_string_lossy())),
+            DateSource::Modified => metadata.and_then(|m| m.modified().ok()).map(local),
+            DateSource::Created => metadata.and_then(|m| m.created().ok()).map(local),
+        };
+        date.map(|date| (date, *source))
+    })
+}
+
+/// Plans moving every file into the folder for its date below the output root
+pub fn plan_by_date(base_path: &Path, config: &DateConfig, plan_options: &PlanOptions) -> io::Result<Plan> {
+    config.validate().map_err(KondoError::ConfigInvalid)?;
+
+    let walk = walk::collect_files(base_path, &plan_options.walk)?;
+    Ok(plan_walk(base_path, walk, config, plan_options))
+}
+
+/// Plans only the given files, e.g. ones watch mode saw arrive in `base_path`
+pub fn plan_files_by_date(
+    base_path: &Path,
+    files: Vec<PathBuf>,
+    config: &DateConfig,
+    plan_options: &PlanOptions,
+) -> io::Result<Plan> {
+    config.validate().map_err(KondoError::ConfigInvalid)?;
+
+    let walk = walk::split_files(base_path, files, &plan_options.walk)?;
+    Ok(plan_walk(base_path, walk, config, plan_options))
+}
+
+fn plan_walk(base_path: &Path, walk: Walk, config: &DateConfig, plan_options: &PlanOptions) -> Plan {
+    let output_root = plan_options.output_root(base_path);
+    let mut builder =
+        PlanBuilder::new("date", base_path).with_conflict(plan_options.conflict.clone());
+
+    for path in walk.organized {
+        builder.add_skip(path, SkipReason::AlreadyOrganized, None);
+    }
+    for path in walk.skipped {
+        builder.add_skip(path, SkipReason::SystemFile, None);
+    }
+
+    for path in walk.files {
+        let Some(name) = path.file_name().map(|n| n.to_os_string()) else {
+            continue;
+        };
+
+        let metadata = fs::metadata(&path).ok();
+        let Some((date, source)) = file_date(&path, metadata.as_ref(), &config.sources) else {
+            builder.add_skip(path, SkipReason::NoDate, None);
+            continue;
+        };
+
+        let folder = config.folder(date);
+        let target_dir = output_root.join(&folder);
+        if path.parent() == Some(target_dir.as_path()) {
+            builder.add_skip(path, SkipReason::AlreadyOrganized, None);
+            continue;
+        }
+
+        let reason = match source {
+            DateSource::Exif => format!("taken {}", date),
+            DateSource::Filename => format!("date in name {}", date),
+            other => format!("{} {}", other, date),
+        };
+        builder.add_move(
+            path,
+            target_dir.join(name),
+            &folder.to_string_lossy(),
+            reason,
+            None,
+            None,
+        );
+    }
+
+    builder.build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::plan::Action;
+
+    #[test]
+    fn test_dates_in_file_names() {
+        let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d);
+        assert_eq!(date_from_name("IMG_20240315_101500.jpg"), date(2024, 3, 15));
+        assert_eq!(date_from_name("server-log-2023-12-01.txt"), date(2023, 12, 1));
+        assert_eq!(date_from_name("scan_2022_7_4.pdf"), date(2022, 7, 4));
+        assert_eq!(date_from_name("v12024-01-31 notes.md"), date(2024, 1, 31));
+        assert_eq!(date_from_name("invoice 12345678.pdf"), None);
+        assert_eq!(date_from_name("2024 report.pdf"), None);
+    }
+
+    #[test]
+    fn test_plan_uses_sources_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::write(base.join("IMG_20240315_101500.jpg"), b"x").unwrap();
+        fs::write(base.join("notes.txt"), b"x").unwrap();
+
+        let config = DateConfig {
+            sources: vec![DateSource::Filename],
+            granularity: Granularity::Month,
+            format: None,
+        };
+        let plan = plan_by_date(base, &config, &PlanOptions::default()).unwrap();
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            Action::Move { destination, .. }
+                if *destination == base.join("2024/03/IMG_20240315_101500.jpg")
+        )));
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            Action::Skip { reason: SkipReason::NoDate, .. }
+        )));
+
+        let with_mtime = DateConfig {
+            sources: vec![DateSource::Filename, DateSource::Modified],
+            format: Some("%Y/%B".to_string()),
+            ..config
+        };
+        let plan = plan_by_date(base, &with_mtime, &PlanOptions::default()).unwrap();
+        assert_eq!(plan.actions.iter().filter(|a| matches!(a, Action::Move { .. })).count(), 2);
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            Action::Move { destination, .. }
+                if *destination == base.join("2024/March/IMG_20240315_101500.jpg")
+        )));
+
+        let invalid = DateConfig {
+            format: Some("%Y/%H".to_string()),
+            ..Default::default()
+        };
+        assert!(invalid.validate().is_err());
+        let escaping = DateConfig {
+            format: Some("../%Y".to_string()),
+            ..Default::default()
+        };
+        assert!(escaping.validate().is_err());
+    }
+}
@@ -0,0 +1,353 @@
+// Finds byte-identical files and keeps a single copy of each
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::conflict::ConflictOptions;
+use super::error::{FileError, KondoError};
+use super::journal::Journal;
+use super::plan::{Plan, PlanBuilder, SkipReason};
+use super::transfer;
+use super::walk::{self, WalkOptions, TRASH_DIR};
+
+/// What happens to the copies that are not kept
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DedupeAction {
+    /// Only list the duplicates
+    #[default]
+    Report,
+    /// Move them into `kondo-trash`, recorded in the journal so undo restores them
+    Trash,
+    /// Replace them with hard links to the kept copy, recorded in the journal so undo
+    /// gives them their own content back
+    Hardlink,
+}
+
+impl fmt::Display for DedupeAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DedupeAction::Report => "report",
+            DedupeAction::Trash => "trash",
+            DedupeAction::Hardlink => "hardlink",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for DedupeAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "report" => Ok(DedupeAction::Report),
+            "trash" => Ok(DedupeAction::Trash),
+            "hardlink" => Ok(DedupeAction::Hardlink),
+            other => Err(format!(
+                "unknown dedupe action '{}' (expected report, trash or hardlink)",
+                other
+            )),
+        }
+    }
+}
+
+/// Which copy of a duplicate group survives
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepPolicy {
+    /// Shortest file name, so `report.pdf` wins over `report_1.pdf`
+    #[default]
+    Shortest,
+    Oldest,
+    Newest,
+}
+
+impl FromStr for KeepPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "shortest" => Ok(KeepPolicy::Shortest),
+            "oldest" => Ok(KeepPolicy::Oldest),
+            "newest" => Ok(KeepPolicy::Newest),
+            other => Err(format!(
+                "unknown keep policy '{}' (expected shortest, oldest or newest)",
+                other
+            )),
+        }
+    }
+}
+
+/// Files with identical content
+#[derive(Debug, Clone, Serialize)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub hash: String,
+    pub keep: PathBuf,
+    pub duplicates: Vec<PathBuf>,
+}
+
+impl DuplicateGroup {
+    /// Bytes freed once only the kept copy remains
+    pub fn wasted(&self) -> u64 {
+        self.size * self.duplicates.len() as u64
+    }
+}
+
+/// Groups files below `base` by size, then by content hash. Empty files, files in
+/// `kondo-trash` and extra hard links to an already seen file are ignored.
+pub fn find_duplicates(
+    base: &Path,
+    walk_options: &WalkOptions,
+    keep: KeepPolicy,
+) -> io::Result<Vec<DuplicateGroup>> {
+    let walk = walk::collect_files(base, walk_options)?;
+    let trash = base.join(TRASH_DIR);
+
+    let mut seen_ids = HashSet::new();
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in walk.files.into_iter().chain(walk.organized) {
+        if path.starts_with(&trash) {
+            continue;
+        }
+        let metadata = match fs::metadata(&path) {
+            Ok(metadata) if metadata.len() > 0 => metadata,
+            _ => continue,
+        };
+        if file_id(&metadata).is_some_and(|id| !seen_ids.insert(id)) {
+            continue;
+        }
+        by_size.entry(metadata.len()).or_default().push(path);
+    }
+
+    // Only files sharing a size can be identical, so only those are hashed
+    let candidates: Vec<(u64, PathBuf)> = by_size
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .flat_map(|(size, paths)| paths.into_iter().map(move |path| (size, path)))
+        .collect();
+
+    let hashed: Vec<(u64, String, PathBuf)> = candidates
+        .into_par_iter()
+        .filter_map(|(size, path)| {
+            let hash = transfer::hash_file(&path).ok()?;
+            Some((size, hash.to_hex().to_string(), path))
+        })
+        .collect();
+
+    let mut by_hash: HashMap<(u64, String), Vec<PathBuf>> = HashMap::new();
+    for (size, hash, path) in hashed {
+        by_hash.entry((size, hash)).or_default().push(path);
+    }
+
+    let mut groups: Vec<DuplicateGroup> = by_hash
+        .into_iter()
+        .filter(|(_, paths)| paths.len() > 1)
+        .map(|((size, hash), mut paths)| {
+            paths.sort();
+            let keep = paths.remove(keep_index(&paths, keep));
+            DuplicateGroup {
+                size,
+                hash,
+                keep,
+                duplicates: paths,
+            }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| b.wasted().cmp(&a.wasted()).then_with(|| a.keep.cmp(&b.keep)));
+    Ok(groups)
+}
+
+fn keep_index(paths: &[PathBuf], keep: KeepPolicy) -> usize {
+    let modified = |path: &PathBuf| fs::metadata(path).and_then(|m| m.modified()).ok();
+    let candidates = paths.iter().enumerate();
+
+    let chosen = match keep {
+        KeepPolicy::Shortest => candidates.min_by_key(|(_, path)| {
+            path.file_name().map(|n| n.to_string_lossy().chars().count())
+        }),
+        KeepPolicy::Oldest => candidates.min_by_key(|(_, path)| modified(path)),
+        KeepPolicy::Newest => candidates.max_by_key(|(_, path)| modified(path)),
+    };
+    chosen.map(|(index, _)| index).unwrap_or(0)
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Plans moving every duplicate into `kondo-trash`, keeping its relative path. Nothing
+/// in the trash is ever replaced; taken names are renamed with the rename template.
+pub fn plan_trash(base: &Path, groups: &[DuplicateGroup], conflict: &ConflictOptions) -> Plan {
+    let mut builder = PlanBuilder::new("dedupe", base).with_conflict(conflict.clone());
+    let trash = base.join(TRASH_DIR);
+
+    for group in groups {
+        for duplicate in &group.duplicates {
+            let relative = duplicate
+                .strip_prefix(base)
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|_| duplicate.file_name().unwrap_or_default().into());
+            let Some(target) = builder.free_name(&trash.join(relative)) else {
+                builder.add_skip(duplicate.clone(), SkipReason::NameConflict, None);
+                continue;
+            };
+
+            builder.add_move(
+                duplicate.clone(),
+                target,
+                "Duplicates",
+                format!("duplicate of {}", group.keep.display()),
+                None,
+                None,
+            );
+        }
+    }
+
+    builder.build()
+}
+
+/// Result of replacing duplicates with hard links
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    pub linked: Vec<PathBuf>,
+    pub failed: Vec<FileError>,
+    /// Links made but not journaled, which undo cannot revert
+    pub warnings: Vec<String>,
+}
+
+/// Replaces each duplicate with a hard link to its group's kept copy. Both files are
+/// hashed again first, so one rewritten since the scan is never replaced. The link is
+/// created under a temporary name and renamed over the duplicate, so a failure never
+/// leaves the duplicate missing. Real replacements are recorded in `journal`.
+pub fn hardlink_duplicates(groups: &[DuplicateGroup], dry_run: bool, journal: Option<&Journal>) -> LinkReport {
+    let results: Vec<(PathBuf, &Path, io::Result<()>)> = groups
+        .par_iter()
+        .flat_map(|group| {
+            group.duplicates.par_iter().map(move |duplicate| {
+                let result = replace_with_link(group, duplicate, dry_run);
+                (duplicate.clone(), group.keep.as_path(), result)
+            })
+        })
+        .collect();
+
+    let mut report = LinkReport::default();
+    for (path, keep, result) in results {
+        match result {
+            Ok(()) => {
+                if let Some(journal) = journal.filter(|_| !dry_run) {
+                    if let Err(e) = journal.record_linked_duplicate(&path, keep) {
+                        report.warnings.push(format!("Failed to write journal entry: {}", e));
+                    }
+                }
+                report.linked.push(path);
+            }
+            Err(e) => report.failed.push(FileError { path, error: e.into() }),
+        }
+    }
+    report
+}
+
+fn replace_with_link(group: &DuplicateGroup, duplicate: &Path, dry_run: bool) -> io::Result<()> {
+    // The file may have changed since it was hashed
+    if fs::metadata(duplicate)?.len() != group.size {
+        return Err(KondoError::SourceChanged("file changed since it was scanned".to_string()).into());
+    }
+    if dry_run {
+        return Ok(());
+    }
+    // Same size is not enough: a file rewritten in place would lose its new content
+    if transfer::hash_file(duplicate)?.to_hex().as_str() != group.hash {
+        return Err(KondoError::SourceChanged("file changed since it was scanned".to_string()).into());
+    }
+    if transfer::hash_file(&group.keep)?.to_hex().as_str() != group.hash {
+        return Err(KondoError::NotIdentical(group.keep.clone()).into());
+    }
+
+    let name = duplicate.file_name().unwrap_or_default().to_string_lossy();
+    let temp = duplicate.with_file_name(format!(".{}.kondo-link", name));
+    fs::hard_link(&group.keep, &temp)?;
+    fs::rename(&temp, duplicate).inspect_err(|_| {
+        let _ = fs::remove_file(&temp);
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organizer::plan::Action;
+
+    #[test]
+    fn test_finds_identical_files_and_keeps_shortest_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::create_dir(base.join("sub")).unwrap();
+        fs::write(base.join("report.pdf"), b"same content").unwrap();
+        fs::write(base.join("report_1.pdf"), b"same content").unwrap();
+        fs::write(base.join("sub/report_2.pdf"), b"same content").unwrap();
+        fs::write(base.join("other.pdf"), b"same length!").unwrap();
+        fs::write(base.join("empty_a"), b"").unwrap();
+        fs::write(base.join("empty_b"), b"").unwrap();
+
+        let groups = find_duplicates(base, &WalkOptions::recursive(None), KeepPolicy::Shortest).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].keep, base.join("report.pdf"));
+        assert_eq!(
+            groups[0].duplicates,
+            vec![base.join("report_1.pdf"), base.join("sub/report_2.pdf")]
+        );
+        assert_eq!(groups[0].wasted(), 24);
+
+        let plan = plan_trash(base, &groups, &ConflictOptions::default());
+        assert!(plan.actions.iter().any(|a| matches!(
+            a,
+            Action::Move { destination, .. }
+                if *destination == base.join(TRASH_DIR).join("sub/report_2.pdf")
+        )));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_hardlinked_copies_are_rechecked_and_undone() {
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("files");
+        fs::create_dir(&base).unwrap();
+        for name in ["a.bin", "b.bin", "c.bin"] {
+            fs::write(base.join(name), b"data").unwrap();
+        }
+
+        let groups = find_duplicates(&base, &WalkOptions::default(), KeepPolicy::Shortest).unwrap();
+        // Rewritten with the same length after the scan: must not be replaced
+        fs::write(base.join("c.bin"), b"DATA").unwrap();
+
+        let journal_path = dir.path().join("journal.jsonl");
+        let journal = Journal::start_run(&journal_path, "dedupe").unwrap();
+        let report = hardlink_duplicates(&groups, false, Some(&journal));
+        assert_eq!(report.linked, vec![base.join("b.bin")]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(fs::read(base.join("c.bin")).unwrap(), b"DATA");
+        assert_eq!(fs::read(base.join("b.bin")).unwrap(), b"data");
+
+        let again = find_duplicates(&base, &WalkOptions::default(), KeepPolicy::Shortest).unwrap();
+        assert!(again.is_empty());
+
+        let undone = crate::organizer::journal::undo_run(&journal_path, None, false).unwrap();
+        assert_eq!(undone.duplicates_restored, 1);
+        let ino = |name: &str| fs::metadata(base.join(name)).unwrap().ino();
+        assert_ne!(ino("a.bin"), ino("b.bin"));
+        assert_eq!(fs::read(base.join("b.bin")).unwrap(), b"data");
+    }
+}
@@ -0,0 +1,165 @@
+// Why a file or a whole run failed, in a form scripts can tell apart
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Every failure kondo reports. Functions returning `io::Result` carry it as the
+/// payload of the `io::Error`, so it survives `?` and can be recovered with `From`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum KondoError {
+    /// The OS refused to read, write or delete a file or folder
+    PermissionDenied(String),
+    /// A hard link or rename cannot cross file systems
+    CrossDevice(String),
+    /// Every free name for the destination was taken
+    ConflictExhausted(PathBuf),
+    /// The config file cannot be parsed or holds invalid values
+    ConfigInvalid(String),
+    /// The source file disappeared before it could be handled
+    SourceVanished,
+    /// The source changed size or was modified since the plan was made
+    SourceChanged(String),
+    /// A folder sits where the file should go
+    DestinationIsDir(PathBuf),
+    /// The file a duplicate matched was changed before the duplicate was deleted
+    NotIdentical(PathBuf),
+    /// A bad command line value or a missing file or folder to work on
+    InvalidInput(String),
+    /// Any other I/O failure
+    Io(String),
+}
+
+impl KondoError {
+    /// Stable name of the variant, reported as `kind` in JSON output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            KondoError::PermissionDenied(_) => "permission_denied",
+            KondoError::CrossDevice(_) => "cross_device",
+            KondoError::ConflictExhausted(_) => "conflict_exhausted",
+            KondoError::ConfigInvalid(_) => "config_invalid",
+            KondoError::SourceVanished => "source_vanished",
+            KondoError::SourceChanged(_) => "source_changed",
+            KondoError::DestinationIsDir(_) => "destination_is_dir",
+            KondoError::NotIdentical(_) => "not_identical",
+            KondoError::InvalidInput(_) => "invalid_input",
+            KondoError::Io(_) => "io",
+        }
+    }
+
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            KondoError::PermissionDenied(_) => io::ErrorKind::PermissionDenied,
+            KondoError::CrossDevice(_) => io::ErrorKind::CrossesDevices,
+            KondoError::ConflictExhausted(_) => io::ErrorKind::AlreadyExists,
+            KondoError::ConfigInvalid(_) => io::ErrorKind::InvalidData,
+            KondoError::SourceVanished => io::ErrorKind::NotFound,
+            KondoError::InvalidInput(_) => io::ErrorKind::InvalidInput,
+            _ => io::ErrorKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for KondoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KondoError::PermissionDenied(message)
+            | KondoError::CrossDevice(message)
+            | KondoError::SourceChanged(message)
+            | KondoError::InvalidInput(message)
+            | KondoError::Io(message) => f.write_str(message),
+            KondoError::ConfigInvalid(message) => write!(f, "invalid config: {}", message),
+            KondoError::ConflictExhausted(path) => {
+                write!(f, "no free name left for {}", path.display())
+            }
+            KondoError::SourceVanished => f.write_str("source no longer exists"),
+            KondoError::DestinationIsDir(path) => {
+                write!(f, "destination is a directory: {}", path.display())
+            }
+            KondoError::NotIdentical(path) => {
+                write!(f, "{} is no longer identical", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for KondoError {}
+
+impl Serialize for KondoError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut error = serializer.serialize_struct("KondoError", 2)?;
+        error.serialize_field("kind", self.kind())?;
+        error.serialize_field("message", &self.to_string())?;
+        error.end()
+    }
+}
+
+impl From<io::Error> for KondoError {
+    fn from(error: io::Error) -> Self {
+        if let Some(inner) = error.get_ref().and_then(|inner| inner.downcast_ref::<KondoError>()) {
+            return inner.clone();
+        }
+        match error.kind() {
+            io::ErrorKind::PermissionDenied => KondoError::PermissionDenied(error.to_string()),
+            io::ErrorKind::CrossesDevices => KondoError::CrossDevice(error.to_string()),
+            io::ErrorKind::InvalidInput => KondoError::InvalidInput(error.to_string()),
+            _ => KondoError::Io(error.to_string()),
+        }
+    }
+}
+
+impl From<KondoError> for io::Error {
+    fn from(error: KondoError) -> Self {
+        io::Error::new(error.io_kind(), error)
+    }
+}
+
+impl From<serde_json::Error> for KondoError {
+    fn from(error: serde_json::Error) -> Self {
+        KondoError::Io(error.to_string())
+    }
+}
+
+/// A file that could not be organized, and why
+#[derive(Debug, Clone, Serialize)]
+pub struct FileError {
+    pub path: PathBuf,
+    #[serde(flatten)]
+    pub error: KondoError,
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kind_survives_io_error() {
+        let error = KondoError::ConfigInvalid("weights must sum to 1.0".to_string());
+        let io_error = io::Error::from(error.clone());
+        assert_eq!(io_error.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(KondoError::from(io_error), error);
+
+        let denied = io::Error::from(io::ErrorKind::PermissionDenied);
+        assert_eq!(KondoError::from(denied).kind(), "permission_denied");
+
+        let failed = FileError {
+            path: PathBuf::from("/in/a.txt"),
+            error: KondoError::SourceVanished,
+        };
+        assert_eq!(
+            serde_json::to_value(&failed).unwrap(),
+            serde_json::json!({
+                "path": "/in/a.txt",
+                "kind": "source_vanished",
+                "message": "source no longer exists",
+            })
+        );
+    }
+}
@@ -1,25 +1,17 @@
 // Advanced filename operations with ML-based similarity detection and file organization
-use std::collections::HashSet;
-use std::fs;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::io;
 use std::path::{Path, PathBuf};
 
-/// Checks if a file should be skipped based on common system file patterns
-pub fn should_skip_file(filename: &str) -> bool {
-    let skip_patterns = [
-        ".DS_Store",
-        "Thumbs.db",
-        ".git",
-        ".gitignore",
-        "desktop.ini",
-        ".localized",
-        "~$",
-    ];
+use super::date::date_pattern_len;
+use super::error::FileError;
+use super::journal::Journal;
+use super::plan::{self, ExecuteOptions, Plan, PlanBuilder, PlanOptions};
+use super::transfer::{self, ProgressFn, TransferMode};
+use super::walk;
 
-    skip_patterns
-        .iter()
-        .any(|pattern| filename.contains(pattern))
-}
+pub use super::plan::{SkipReason, SkippedFile};
 
 // ML-Based Similarity Detection
 
@@ -64,27 +56,20 @@ pub struct FileGroup {
 }
 
 /// Result of organizing files by similarity
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct OrganizeResult {
     pub files_moved: usize,
     pub folders_created: usize,
     pub files_skipped: usize,
+    /// Moved under a new name because the destination was taken
+    pub files_renamed: usize,
+    /// Moved by replacing an existing file
+    pub files_overwritten: usize,
+    /// Deleted because an identical file was already at the destination
+    pub duplicates_deleted: usize,
     pub skipped_details: Vec<SkippedFile>,
-    pub errors: Vec<String>,
-}
-
-/// Information about a skipped file
-#[derive(Debug, Clone)]
-pub struct SkippedFile {
-    pub filename: String,
-    pub reason: SkipReason,
-}
-
-#[derive(Debug, Clone)]
-pub enum SkipReason {
-    SingleFile,       // Only one file in its group
-    SystemFile,       // System file pattern detected
-    AlreadyOrganized, // Already in a subfolder
+    pub errors: Vec<FileError>,
+    pub dry_run: bool,
 }
 
 /// Calculates Levenshtein distance between two strings
@@ -103,11 +88,11 @@ pub fn levenshtein_distance(s1: &str, s2: &str) -> usize {
     let mut matrix = vec![vec![0; len2 + 1]; len1 + 1];
 
     // Initialize first row and column
-    for i in 0..=len1 {
-        matrix[i][0] = i;
+    for (i, row) in matrix.iter_mut().enumerate() {
+        row[0] = i;
     }
-    for j in 0..=len2 {
-        matrix[0][j] = j;
+    for (j, cell) in matrix[0].iter_mut().enumerate() {
+        *cell = j;
     }
 
     let s1_chars: Vec<char> = s1.chars().collect();
@@ -445,13 +430,9 @@ fn remove_date_patterns(s: &str) -> String {
     let mut i = 0;
 
     while i < chars.len() {
-        // Check for date-like patterns (4 digits followed by more digits)
-        if i + 7 < chars.len() && chars[i..i + 4].iter().all(|c| c.is_numeric()) {
-            // Skip potential date
-            i += 8;
-            while i < chars.len() && (chars[i].is_numeric() || chars[i] == '-' || chars[i] == '_') {
-                i += 1;
-            }
+        // Skip date-like patterns (4 digits followed by more digits)
+        if let Some(len) = date_pattern_len(&chars, i) {
+            i += len;
         } else {
             cleaned.push(chars[i]);
             i += 1;
@@ -496,33 +477,36 @@ fn remove_number_suffixes(s: &str) -> String {
     result
 }
 
-/// Organizes files by similarity, moving them into appropriate folders
-pub fn organize_by_similarity(
+/// Decides how files are grouped by similarity without touching the filesystem
+pub fn plan_by_similarity(
     base_path: &Path,
     config: &SimilarityConfig,
     move_skipped: bool,
+    plan_options: &PlanOptions,
     logger: &mut dyn FnMut(&str),
-) -> io::Result<OrganizeResult> {
+) -> io::Result<Plan> {
     logger(&format!(
         "Starting organization in: {}",
         base_path.display()
     ));
 
     // Read all files
-    let entries: Vec<_> = fs::read_dir(base_path)?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.path().is_file())
-        .collect();
+    let walk = walk::collect_files(base_path, &plan_options.walk)?;
 
-    logger(&format!("Found {} files to process", entries.len()));
+    logger(&format!("Found {} files to process", walk.files.len()));
 
-    let filenames: Vec<String> = entries
+    // Grouping works on names; remember which path(s) each name came from
+    let mut paths_by_name: HashMap<String, VecDeque<PathBuf>> = HashMap::new();
+    let filenames: Vec<String> = walk
+        .files
         .iter()
-        .filter_map(|e| {
-            e.path()
-                .file_name()
-                .and_then(|n| n.to_str())
-                .map(|s| s.to_string())
+        .filter_map(|path| {
+            let name = path.file_name().and_then(|n| n.to_str())?.to_string();
+            paths_by_name
+                .entry(name.clone())
+                .or_default()
+                .push_back(path.clone());
+            Some(name)
         })
         .collect();
 
@@ -531,163 +515,105 @@ pub fn organize_by_similarity(
     let groups = group_similar_files(&filenames, config);
     logger(&format!("Identified {} file groups", groups.len()));
 
-    let mut files_moved = 0;
-    let mut folders_created = 0;
-    let mut files_skipped = 0;
-    let mut skipped_details = Vec::new();
-    let mut errors = Vec::new();
-
-    // Prepare skip folder if needed
-    let skip_folder = if move_skipped {
-        let skip_dir = base_path.join("kondo-skip");
-        if !skip_dir.exists() {
-            match fs::create_dir(&skip_dir) {
-                Ok(_) => {
-                    logger(&format!("Created skip folder: {}", skip_dir.display()));
-                    Some(skip_dir)
-                }
-                Err(e) => {
-                    let err_msg = format!("Failed to create skip folder: {}", e);
-                    logger(&err_msg);
-                    errors.push(err_msg);
-                    None
-                }
-            }
-        } else {
-            Some(skip_dir)
-        }
-    } else {
-        None
+    let mut builder =
+        PlanBuilder::new("filename", base_path).with_conflict(plan_options.conflict.clone());
+
+    for path in walk.organized {
+        builder.add_skip(path, SkipReason::AlreadyOrganized, None);
+    }
+    for path in walk.skipped {
+        builder.add_skip(path, SkipReason::SystemFile, None);
+    }
+
+    let output_root = plan_options.output_root(base_path);
+
+    // Skipped files are parked here if requested
+    let skip_dir = output_root.join(walk::SKIP_DIR);
+
+    let mut source_of = |name: &str| {
+        paths_by_name
+            .get_mut(name)
+            .and_then(VecDeque::pop_front)
+            .unwrap_or_else(|| base_path.join(name))
     };
 
-    // Process each group
     for group in groups {
         // Handle single files
         if group.files.len() < 2 {
             for filename in &group.files {
-                // Check if it's a system file
-                if should_skip_file(filename) {
-                    skipped_details.push(SkippedFile {
-                        filename: filename.clone(),
-                        reason: SkipReason::SystemFile,
-                    });
-                    logger(&format!("Skipped system file: {}", filename));
+                let parked = if move_skipped {
+                    builder.free_name(&skip_dir.join(filename))
                 } else {
-                    skipped_details.push(SkippedFile {
-                        filename: filename.clone(),
-                        reason: SkipReason::SingleFile,
-                    });
-                    logger(&format!("Skipped single file: {}", filename));
-                }
-                files_skipped += 1;
-
-                // Move to skip folder if enabled
-                if let Some(ref skip_dir) = skip_folder {
-                    let source = base_path.join(filename);
-                    let dest = skip_dir.join(filename);
-
-                    if let Err(e) = fs::rename(&source, &dest) {
-                        let err_msg =
-                            format!("Failed to move '{}' to skip folder: {}", filename, e);
-                        logger(&err_msg);
-                        errors.push(err_msg);
-                    } else {
-                        logger(&format!("Moved to skip folder: {}", filename));
-                    }
-                }
+                    None
+                };
+
+                builder.add_skip(source_of(filename), SkipReason::SingleFile, parked);
             }
             continue;
         }
 
         let folder_name = suggest_folder_name(&group);
-        let target_dir = base_path.join(&folder_name);
-
-        // Create folder if it doesn't exist
-        if !target_dir.exists() {
-            match fs::create_dir(&target_dir) {
-                Ok(_) => {
-                    folders_created += 1;
-                    logger(&format!("Created folder: {}", folder_name));
-                }
-                Err(e) => {
-                    let err_msg = format!("Failed to create folder '{}': {}", folder_name, e);
-                    logger(&err_msg);
-                    errors.push(err_msg);
-                    continue;
-                }
-            }
-        }
+        let target_dir = output_root.join(&folder_name);
 
-        // Move files into the folder
         for filename in &group.files {
-            let source = base_path.join(filename);
-            let dest = target_dir.join(filename);
-
-            // Handle naming conflicts
-            let final_dest = if dest.exists() {
-                match handle_naming_conflict(&dest) {
-                    Ok(path) => path,
-                    Err(e) => {
-                        let err_msg = format!("Naming conflict for '{}': {}", filename, e);
-                        logger(&err_msg);
-                        errors.push(err_msg);
-                        continue;
-                    }
-                }
-            } else {
-                dest
-            };
+            let source = source_of(filename);
 
-            match fs::rename(&source, &final_dest) {
-                Ok(_) => {
-                    files_moved += 1;
-                    logger(&format!("Moved: {} -> {}", filename, folder_name));
-                }
-                Err(e) => {
-                    let err_msg = format!("Failed to move '{}': {}", filename, e);
-                    logger(&err_msg);
-                    errors.push(err_msg);
-                }
-            }
+            builder.add_move(
+                source,
+                target_dir.join(filename),
+                &folder_name,
+                format!(
+                    "similar to {} other file(s), {:.0}% similarity",
+                    group.files.len() - 1,
+                    group.avg_similarity * 100.0
+                ),
+                Some(&folder_name),
+                Some(group.avg_similarity),
+            );
         }
     }
 
-    logger(&format!(
-        "Organization complete: {} files moved, {} folders created, {} files skipped",
-        files_moved, folders_created, files_skipped
-    ));
-
-    Ok(OrganizeResult {
-        files_moved,
-        folders_created,
-        files_skipped,
-        skipped_details,
-        errors,
-    })
+    Ok(builder.build())
 }
 
-/// Handles naming conflicts by appending a number
-fn handle_naming_conflict(path: &Path) -> io::Result<PathBuf> {
-    let parent = path.parent().unwrap();
-    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("file");
-    let extension = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .map(|e| format!(".{}", e))
-        .unwrap_or_default();
-
-    for i in 1..1000 {
-        let new_name = format!("{}_{}{}", stem, i, extension);
-        let new_path = parent.join(new_name);
-        if !new_path.exists() {
-            return Ok(new_path);
-        }
+/// Organizes files by similarity, moving them into appropriate folders.
+/// Every move and created folder is recorded in the journal of `options` when one is given.
+pub fn organize_by_similarity(
+    base_path: &Path,
+    config: &SimilarityConfig,
+    move_skipped: bool,
+    plan_options: &PlanOptions,
+    options: &ExecuteOptions,
+    logger: &mut dyn FnMut(&str),
+) -> io::Result<OrganizeResult> {
+    let plan = plan_by_similarity(base_path, config, move_skipped, plan_options, logger)?;
+    let report = plan::execute(&plan, options);
+
+    for line in report.log_lines() {
+        logger(&line);
+    }
+    for warning in &report.warnings {
+        logger(warning);
     }
 
-    Err(io::Error::new(
-        io::ErrorKind::AlreadyExists,
-        "Could not find available filename",
-    ))
+    let result = OrganizeResult {
+        files_moved: report.files_moved(),
+        folders_created: report.dirs_created(),
+        files_skipped: report.files_skipped(),
+        files_renamed: report.files_renamed(),
+        files_overwritten: report.files_overwritten(),
+        duplicates_deleted: report.duplicates_deleted(),
+        skipped_details: report.skipped_details(),
+        errors: report.errors(),
+        dry_run: options.dry_run,
+    };
+
+    logger(&format!(
+        "Organization complete: {} files moved, {} folders created, {} files skipped",
+        result.files_moved, result.folders_created, result.files_skipped
+    ));
+
+    Ok(result)
 }
 
 // TUI Implementation for Filename Organization
@@ -704,6 +630,12 @@ pub struct FilenameTuiApp {
     groups: Vec<FileGroup>,
     scroll_offset: usize,
     log_messages: Arc<Mutex<Vec<String>>>,
+    journal: Option<Journal>,
+    plan_options: PlanOptions,
+    transfer: TransferMode,
+    progress: Option<ProgressFn>,
+    /// `--dry-run`: every run only previews, whichever key starts it
+    dry_run: bool,
 }
 
 enum FilenameAppState {
@@ -724,9 +656,38 @@ impl FilenameTuiApp {
             groups: Vec::new(),
             scroll_offset: 0,
             log_messages: Arc::new(Mutex::new(Vec::new())),
+            journal: None,
+            plan_options: PlanOptions::default(),
+            transfer: TransferMode::default(),
+            progress: None,
+            dry_run: false,
         }
     }
 
+    /// Only offer previews, for `--dry-run`
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Journal real moves so the run can be undone with `kondo undo`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// Controls which files are collected and where groups are created
+    pub fn with_plan_options(mut self, plan_options: PlanOptions) -> Self {
+        self.plan_options = plan_options;
+        self
+    }
+
+    /// Copy or link files into their groups instead of moving them
+    pub fn with_transfer(mut self, transfer: TransferMode) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
     fn log(&self, message: &str) {
         if let Ok(mut logs) = self.log_messages.lock() {
             logs.push(message.to_string());
@@ -794,8 +755,19 @@ impl FilenameTuiApp {
                             }
                         }
                         KeyCode::Char('s') => {
+                            let previewed = matches!(
+                                &self.state,
+                                FilenameAppState::Complete(result) if result.dry_run
+                            );
+                            let allowed = !self.dry_run
+                                && (matches!(self.state, FilenameAppState::ReviewGroups) || previewed);
+                            if allowed {
+                                self.start_organization(false)?;
+                            }
+                        }
+                        KeyCode::Char('d') => {
                             if matches!(self.state, FilenameAppState::ReviewGroups) {
-                                self.start_organization()?;
+                                self.start_organization(true)?;
                             }
                         }
                         KeyCode::Char('k') => {
@@ -816,9 +788,7 @@ impl FilenameTuiApp {
                             }
                         }
                         KeyCode::Up => {
-                            if self.scroll_offset > 0 {
-                                self.scroll_offset -= 1;
-                            }
+                            self.scroll_offset = self.scroll_offset.saturating_sub(1);
                         }
                         KeyCode::Down => {
                             self.scroll_offset += 1;
@@ -842,18 +812,14 @@ impl FilenameTuiApp {
         self.log("Starting file analysis");
 
         // Read directory
-        let entries: Vec<_> = fs::read_dir(&self.base_path)?
-            .filter_map(|e| e.ok())
-            .filter(|e| e.path().is_file())
-            .collect();
+        let entries = walk::collect_files(&self.base_path, &self.plan_options.walk)?.files;
 
         self.log(&format!("Found {} files", entries.len()));
 
         let filenames: Vec<String> = entries
             .iter()
-            .filter_map(|e| {
-                e.path()
-                    .file_name()
+            .filter_map(|path| {
+                path.file_name()
                     .and_then(|n| n.to_str())
                     .map(|s| s.to_string())
             })
@@ -866,9 +832,14 @@ impl FilenameTuiApp {
         Ok(())
     }
 
-    fn start_organization(&mut self) -> io::Result<()> {
+    fn start_organization(&mut self, dry_run: bool) -> io::Result<()> {
+        let dry_run = dry_run || self.dry_run;
         self.state = FilenameAppState::Organizing;
-        self.log("Starting organization");
+        self.log(if dry_run {
+            "Starting dry run"
+        } else {
+            "Starting organization"
+        });
 
         let log_messages = Arc::clone(&self.log_messages);
         let mut logger = |msg: &str| {
@@ -882,6 +853,14 @@ impl FilenameTuiApp {
             &self.base_path,
             &self.config,
             self.move_skipped_to_folder,
+            &self.plan_options,
+            &ExecuteOptions {
+                dry_run,
+                journal: self.journal.as_ref(),
+                transfer: self.transfer,
+                progress: self.progress,
+                on_outcome: None,
+            },
             &mut logger,
         )?;
 
@@ -1146,7 +1125,11 @@ impl FilenameTuiApp {
 
         let mut lines = vec![
             Line::from(Span::styled(
-                "✦ Organization Complete!",
+                if result.dry_run {
+                    "✦ Dry Run Complete - nothing was moved"
+                } else {
+                    "✦ Organization Complete!"
+                },
                 Style::default()
                     .fg(Color::Green)
                     .add_modifier(Modifier::BOLD),
@@ -1198,8 +1181,15 @@ impl FilenameTuiApp {
             for skip in visible_skipped {
                 let (icon, reason_text) = match skip.reason {
                     SkipReason::SingleFile => ("", "No similar matches found"),
-                    SkipReason::SystemFile => ("", "System file"),
+                    SkipReason::SystemFile => ("", "Matches a skip pattern"),
                     SkipReason::AlreadyOrganized => ("✓", "Already organized"),
+                    SkipReason::InvalidName => ("", "Invalid filename"),
+                    SkipReason::NameConflict => ("", "No free destination name"),
+                    SkipReason::DestinationExists => ("", "Destination already exists"),
+                    SkipReason::ExistingNewer => ("", "Existing file is newer"),
+                    SkipReason::ExistingLarger => ("", "Existing file is larger"),
+                    SkipReason::IdenticalExists => ("=", "Identical file already there"),
+                    SkipReason::NoDate => ("", "No date found"),
                 };
                 lines.push(Line::from(vec![
                     Span::raw(format!("  {} ", icon)),
@@ -1251,8 +1241,15 @@ impl FilenameTuiApp {
 
         let controls = match &self.state {
             FilenameAppState::Ready => "'a' Analyze | 'q' Quit",
+            FilenameAppState::ReviewGroups if self.dry_run => {
+                "'d' Dry Run | 'k' Toggle Skip Folder | ↑↓ Scroll | 'q' Quit"
+            }
+            FilenameAppState::Complete(_) if self.dry_run => "'r' Reset | ↑↓ Scroll | 'q' Quit",
             FilenameAppState::ReviewGroups => {
-                "'s' Start Organization | 'k' Toggle Skip Folder | ↑↓ Scroll | 'q' Quit"
+                "'s' Start Organization | 'd' Dry Run | 'k' Toggle Skip Folder | ↑↓ Scroll | 'q' Quit"
+            }
+            FilenameAppState::Complete(result) if result.dry_run => {
+                "'s' Start Organization | 'r' Reset | ↑↓ Scroll | 'q' Quit"
             }
             FilenameAppState::Complete(_) => "'r' Reset | ↑↓ Scroll | 'q' Quit",
             FilenameAppState::Organizing => " Organizing... Please wait",
@@ -1272,9 +1269,17 @@ impl FilenameTuiApp {
             Vec::new()
         }
     }
+    /// Result of the finished run, if organizing got that far
+    pub fn result(&self) -> Option<&OrganizeResult> {
+        match &self.state {
+            FilenameAppState::Complete(result) => Some(result),
+            _ => None,
+        }
+    }
+
     /// Auto-organize files without UI interaction
-    /// Automatically analyzes (press 'a') then organizes (press 's')
-    pub fn auto_organize(&mut self) -> io::Result<()> {
+    /// Automatically analyzes (press 'a') then organizes (press 's', or 'd' for a dry run)
+    pub fn auto_organize(&mut self, dry_run: bool) -> io::Result<()> {
         // println!("🔍 Analyzing files...");
 
         // Step 1: Analyze files (equivalent to pressing 'a')
@@ -1328,15 +1333,31 @@ impl FilenameTuiApp {
         }
 
         // Step 2: Start organization (equivalent to pressing 's')
-        self.start_organization()?;
+        // No TUI is drawn, so long copies can report on the terminal
+        self.progress = Some(transfer::print_progress);
+        self.start_organization(dry_run)?;
 
         // Display organization results
         if let FilenameAppState::Complete(result) = &self.state {
-            println!("\n✦ Organization Complete!\n");
-            println!("Summary:");
-            println!("   • Folders created: {}", result.folders_created);
-            println!("   • Files moved:     {}", result.files_moved);
+            if dry_run {
+                println!("\n[DRY RUN] Summary:");
+                println!("   • Files that would be moved: {}", result.files_moved);
+            } else {
+                println!("\n✦ Organization Complete!\n");
+                println!("Summary:");
+                println!("   • Folders created: {}", result.folders_created);
+                println!("   • Files moved:     {}", result.files_moved);
+            }
             println!("   • Files skipped:   {}", result.files_skipped);
+            if result.files_renamed > 0 {
+                println!("   • Renamed:         {}", result.files_renamed);
+            }
+            if result.files_overwritten > 0 {
+                println!("   • Overwritten:     {}", result.files_overwritten);
+            }
+            if result.duplicates_deleted > 0 {
+                println!("   • Duplicates deleted: {}", result.duplicates_deleted);
+            }
 
             // Show skipped files summary
             if !result.skipped_details.is_empty() {
@@ -1345,7 +1366,7 @@ impl FilenameTuiApp {
                 let single_files = result.skipped_details.iter()
                     .filter(|s| matches!(s.reason, SkipReason::SingleFile))
                     .count();
-                let system_files = result.skipped_details.iter()
+                let skip_patterns = result.skipped_details.iter()
                     .filter(|s| matches!(s.reason, SkipReason::SystemFile))
                     .count();
                 let already_organized = result.skipped_details.iter()
@@ -1355,8 +1376,8 @@ impl FilenameTuiApp {
                 if single_files > 0 {
                     println!("   • No similar matches: {}", single_files);
                 }
-                if system_files > 0 {
-                    println!("   • System files:       {}", system_files);
+                if skip_patterns > 0 {
+                    println!("   • Skip patterns:      {}", skip_patterns);
                 }
                 if already_organized > 0 {
                     println!("   • Already organized:  {}", already_organized);
@@ -1366,12 +1387,7 @@ impl FilenameTuiApp {
                 if result.skipped_details.len() <= 5 {
                     println!("\n   Files:");
                     for skip in &result.skipped_details {
-                        let reason_text = match skip.reason {
-                            SkipReason::SingleFile => "no matches",
-                            SkipReason::SystemFile => "system file",
-                            SkipReason::AlreadyOrganized => "already organized",
-                        };
-                        println!("   • {} ({})", skip.filename, reason_text);
+                        println!("   • {} ({})", skip.filename, skip.reason.describe());
                     }
                 } else {
                     println!("   (Use logs for full details)");
@@ -0,0 +1,216 @@
+// Which files every mode leaves alone: `skip_patterns` from the config plus the
+// .kondoignore of the directory being organized, both in .gitignore syntax
+use globset::{GlobBuilder, GlobMatcher};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::error::KondoError;
+
+/// Per-directory list of extra skip patterns, read by kondo and never organized
+pub const IGNORE_FILE: &str = ".kondoignore";
+
+/// `skip_patterns` when the config does not set any
+pub const DEFAULT_PATTERNS: &[&str] = &[
+    ".DS_Store",
+    "Thumbs.db",
+    ".git",
+    ".gitignore",
+    "desktop.ini",
+    ".localized",
+    "~$*",
+];
+
+/// Whether `path` is a directory's own .kondoignore
+pub fn is_ignore_file(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == IGNORE_FILE)
+}
+
+/// One line of a pattern list
+#[derive(Debug, Clone)]
+struct Pattern {
+    matcher: GlobMatcher,
+    /// `!pattern`: brings back what an earlier pattern skipped
+    negated: bool,
+    /// `pattern/`: only matches directories
+    dir_only: bool,
+}
+
+impl Pattern {
+    /// Parses one line; None for blank lines and comments
+    fn parse(line: &str) -> Result<Option<Self>, String> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(None);
+        }
+
+        let (negated, rest) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line),
+        };
+        let (dir_only, rest) = match rest.strip_suffix('/') {
+            Some(rest) => (true, rest),
+            None => (false, rest),
+        };
+        // A slash anywhere but the end anchors the pattern to the directory; without
+        // one it matches a name at any depth
+        let glob = match rest.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if rest.contains('/') => rest.to_string(),
+            None => format!("**/{}", rest),
+        };
+        if rest.is_empty() || glob.is_empty() {
+            return Err(format!("invalid skip pattern `{}`: nothing to match", line));
+        }
+
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| format!("invalid skip pattern `{}`: {}", line, e.kind()))?
+            .compile_matcher();
+        Ok(Some(Self {
+            matcher,
+            negated,
+            dir_only,
+        }))
+    }
+}
+
+/// Skip patterns compiled for one base directory. Patterns are matched against paths
+/// relative to it and the last matching pattern wins, as in a .gitignore.
+#[derive(Debug, Clone, Default)]
+pub struct SkipMatcher {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+impl SkipMatcher {
+    /// Compiles `patterns` for paths below `base`
+    pub fn new<S: AsRef<str>>(base: &Path, patterns: &[S]) -> Result<Self, String> {
+        let mut matcher = Self {
+            base: base.to_path_buf(),
+            patterns: Vec::new(),
+        };
+        matcher.extend(patterns.iter().map(AsRef::as_ref))?;
+        Ok(matcher)
+    }
+
+    /// `patterns` followed by the lines of the .kondoignore in `base`, if it has one
+    pub fn for_directory<S: AsRef<str>>(base: &Path, patterns: &[S]) -> io::Result<Self> {
+        let mut matcher = Self::new(base, patterns).map_err(KondoError::ConfigInvalid)?;
+
+        let path = base.join(IGNORE_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => matcher
+                .extend(content.lines())
+                .map_err(|e| KondoError::InvalidInput(format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        Ok(matcher)
+    }
+
+    fn extend<'a>(&mut self, lines: impl Iterator<Item = &'a str>) -> Result<(), String> {
+        for line in lines {
+            self.patterns.extend(Pattern::parse(line)?);
+        }
+        Ok(())
+    }
+
+    /// Whether `path` is skipped, by itself or because a folder it is in is. Paths
+    /// outside the base directory are matched by their name alone.
+    pub fn is_skipped(&self, path: &Path, is_dir: bool) -> bool {
+        if self.patterns.is_empty() {
+            return false;
+        }
+        let relative = match path.strip_prefix(&self.base) {
+            Ok(relative) => relative,
+            Err(_) => match path.file_name() {
+                Some(name) => Path::new(name),
+                None => return false,
+            },
+        };
+
+        let mut folder = PathBuf::new();
+        let mut components = relative
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .peekable();
+        while let Some(component) = components.next() {
+            folder.push(component);
+            let last = components.peek().is_none();
+            if self.matches(&folder, is_dir || !last) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        self.patterns
+            .iter()
+            .rev()
+            .find(|pattern| (is_dir || !pattern.dir_only) && pattern.matcher.is_match(relative))
+            .is_some_and(|pattern| !pattern.negated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_syntax() {
+        let base = Path::new("/data");
+        let matcher = SkipMatcher::new(
+            base,
+            &[
+                "# comments and blank lines are ignored",
+                "",
+                ".git",
+                "*.tmp",
+                "!keep.tmp",
+                "/top.txt",
+                "build/",
+                "notes/*.md",
+                "~$*",
+            ],
+        )
+        .unwrap();
+        let skipped = |path: &str| matcher.is_skipped(&base.join(path), false);
+
+        assert!(skipped(".git"));
+        assert!(!skipped("my.github.notes.txt"));
+        assert!(skipped("a.tmp"));
+        assert!(skipped("sub/b.tmp"));
+        assert!(!skipped("keep.tmp"));
+        assert!(skipped("top.txt"));
+        assert!(!skipped("sub/top.txt"));
+        assert!(!skipped("build"));
+        assert!(matcher.is_skipped(&base.join("build"), true));
+        assert!(skipped("build/out.o"));
+        assert!(skipped("notes/a.md"));
+        assert!(!skipped("notes/deeper/a.md"));
+        assert!(skipped("~$report.docx"));
+        assert!(skipped(".git/config"));
+        assert!(matcher.is_skipped(Path::new("/elsewhere/c.tmp"), false));
+
+        assert!(SkipMatcher::new(base, &["[z-a"]).is_err());
+        assert!(SkipMatcher::new(base, &["!"]).is_err());
+    }
+
+    #[test]
+    fn test_kondoignore_adds_to_config_patterns() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join(IGNORE_FILE), "*.log\n!important.log\n").unwrap();
+
+        let matcher = SkipMatcher::for_directory(dir.path(), &["*.tmp"]).unwrap();
+        assert!(matcher.is_skipped(&dir.path().join("a.tmp"), false));
+        assert!(matcher.is_skipped(&dir.path().join("a.log"), false));
+        assert!(!matcher.is_skipped(&dir.path().join("important.log"), false));
+
+        fs::write(dir.path().join(IGNORE_FILE), "[\n").unwrap();
+        let err = SkipMatcher::for_directory(dir.path(), &["*.tmp"]).unwrap_err();
+        assert!(err.to_string().contains(IGNORE_FILE));
+    }
+}
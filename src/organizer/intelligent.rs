@@ -1,3 +1,4 @@
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -19,6 +20,12 @@ use ratatui::{
     Terminal,
 };
 
+use super::error::FileError;
+use super::journal::Journal;
+use super::plan::{self, ExecuteOptions, Plan, PlanBuilder, PlanOptions, SkipReason};
+use super::template::sanitize_dirname;
+use super::transfer::TransferMode;
+use super::walk::{self, WalkOptions};
 
 /// Configuration for intelligent grouping
 #[derive(Debug, Clone)]
@@ -28,6 +35,7 @@ pub struct IntelligentConfig {
     pub max_clusters: usize,
     pub filename_similarity_weight: f64,
     pub content_similarity_weight: f64,
+    #[allow(dead_code)]
     pub similarity_threshold: f64,
     pub max_iterations: usize,
 }
@@ -52,20 +60,25 @@ pub struct FileFeatures {
     pub path: PathBuf,
     pub filename_vector: Vec<f64>,
     pub content_vector: Option<Vec<f64>>,
+    #[allow(dead_code)]
     pub is_text: bool,
 }
 
 /// Result of clustering operation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ClusterResult {
     pub groups: Vec<FileGroup>,
+    /// Files inside folders Kondo created earlier, left where they are
+    pub already_organized: Vec<PathBuf>,
 }
 
 /// A group of similar files
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct FileGroup {
     pub files: Vec<PathBuf>,
     pub suggested_name: String,
+    #[allow(dead_code)]
+    #[serde(skip)]
     pub centroid: Vec<f64>,
 }
 
@@ -83,6 +96,7 @@ type ProgressCallback = Box<dyn Fn(String) + Send>;
 pub fn organize_files_intelligently(
     directory: &Path,
     config: &IntelligentConfig,
+    walk_options: &WalkOptions,
     progress_callback: Option<ProgressCallback>,
 ) -> Result<ClusterResult, io::Error> {
     let send_progress = |msg: String| {
@@ -94,10 +108,13 @@ pub fn organize_files_intelligently(
     send_progress("🔍 Scanning directory for files...".to_string());
 
     // Step 1: Traverse directory and collect files
-    let files = collect_files(directory)?;
+    let (files, already_organized) = collect_files(directory, walk_options)?;
 
     if files.is_empty() {
-        return Ok(ClusterResult { groups: Vec::new() });
+        return Ok(ClusterResult {
+            groups: Vec::new(),
+            already_organized,
+        });
     }
 
     send_progress(format!("📊 Found {} files to analyze", files.len()));
@@ -116,32 +133,34 @@ pub fn organize_files_intelligently(
 
     send_progress(format!("✓ Created {} groups", groups.len()));
 
-    Ok(ClusterResult { groups })
+    Ok(ClusterResult {
+        groups,
+        already_organized,
+    })
 }
 
-/// Recursively collect all files from directory (excluding hidden files and directories)
-fn collect_files(directory: &Path) -> Result<Vec<PathBuf>, io::Error> {
-    let mut files = Vec::new();
-
-    if directory.is_dir() {
-        for entry in fs::read_dir(directory)? {
-            let entry = entry?;
-            let path = entry.path();
-
-            // Skip hidden files/directories
-            if let Some(name) = path.file_name() {
-                if name.to_string_lossy().starts_with('.') {
-                    continue;
-                }
-            }
-
-            if path.is_file() {
-                files.push(path);
-            }
-        }
+/// Collect files to cluster (excluding hidden files), plus files in already organized folders
+fn collect_files(
+    directory: &Path,
+    walk_options: &WalkOptions,
+) -> Result<(Vec<PathBuf>, Vec<PathBuf>), io::Error> {
+    if !directory.is_dir() {
+        return Ok((Vec::new(), Vec::new()));
     }
 
-    Ok(files)
+    let walk = walk::collect_files(directory, walk_options)?;
+    let files = walk
+        .files
+        .into_iter()
+        .filter(|path| {
+            // Skip hidden files
+            !path
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'))
+        })
+        .collect();
+
+    Ok((files, walk.organized))
 }
 
 /// Extract features from files
@@ -353,13 +372,10 @@ fn compute_tfidf_vector(text: &str, model: &TfIdfModel) -> Vec<f64> {
     }
 
     // Apply IDF
-    let tfidf: Vec<f64> = tf
-        .iter()
+    tf.iter()
         .zip(&model.idf)
         .map(|(t, i)| t * i)
-        .collect();
-
-    tfidf
+        .collect()
 }
 
 /// Perform K-means clustering
@@ -428,12 +444,10 @@ fn determine_k(vectors: &[Vec<f64>], config: &IntelligentConfig) -> usize {
     let n = vectors.len();
 
     // Simple heuristic: sqrt(n/2)
-    let k = ((n as f64 / 2.0).sqrt().ceil() as usize)
+    ((n as f64 / 2.0).sqrt().ceil() as usize)
         .max(2)
         .min(config.max_clusters)
-        .min(n);
-
-    k
+        .min(n)
 }
 
 /// K-means clustering algorithm
@@ -570,7 +584,9 @@ fn generate_name_from_files(files: &[PathBuf]) -> String {
     let words = extract_common_words(&filenames);
     if !words.is_empty() {
         let mut name = words[0].clone();
-        name.get_mut(0..1).map(|s| s.make_ascii_uppercase());
+        if let Some(first) = name.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
         return name;
     }
 
@@ -617,7 +633,7 @@ fn extract_common_words(filenames: &[String]) -> Vec<String> {
     }
 
     let mut words: Vec<(String, usize)> = word_counts.into_iter().collect();
-    words.sort_by(|a, b| b.1.cmp(&a.1));
+    words.sort_by_key(|w| std::cmp::Reverse(w.1));
 
     words.into_iter().map(|(w, _)| w).take(1).collect()
 }
@@ -645,28 +661,22 @@ fn compute_centroid(indices: &[usize], features: &[FileFeatures]) -> Vec<f64> {
     centroid
 }
 
-/// Move files into their organized groups
-pub fn move_files_to_groups(
-    base_path: &Path,
-    result: &ClusterResult,
-    dry_run: bool,
-) -> Result<Vec<String>, io::Error> {
-    let mut log = Vec::new();
+/// Build the plan that moves every clustered file into its group directory
+pub fn plan_group_moves(base_path: &Path, result: &ClusterResult, plan_options: &PlanOptions) -> Plan {
+    let mut builder =
+        PlanBuilder::new("intelligent", base_path).with_conflict(plan_options.conflict.clone());
+    let output_root = plan_options.output_root(base_path);
+
+    for path in &result.already_organized {
+        builder.add_skip(path.clone(), SkipReason::AlreadyOrganized, None);
+    }
 
-    for (i, group) in result.groups.iter().enumerate() {
+    for group in &result.groups {
         // Sanitize group name for directory
         let dir_name = sanitize_dirname(&group.suggested_name);
-        let group_dir = base_path.join(&dir_name);
+        let group_dir = output_root.join(&dir_name);
+        builder.ensure_dir(&group_dir);
 
-        if !dry_run {
-            // Create directory if it doesn't exist
-            fs::create_dir_all(&group_dir)?;
-            log.push(format!("Created directory: {}", group_dir.display()));
-        } else {
-            log.push(format!("[DRY RUN] Would create: {}", group_dir.display()));
-        }
-
-        // Move each file to the group directory
         for file_path in &group.files {
             if let Some(filename) = file_path.file_name() {
                 let dest_path = group_dir.join(filename);
@@ -676,69 +686,50 @@ pub fn move_files_to_groups(
                     continue;
                 }
 
-                if !dry_run {
-                    // Handle file name conflicts
-                    let final_dest = handle_conflict(&dest_path)?;
-                    fs::rename(file_path, &final_dest)?;
-                    log.push(format!(
-                        "  Moved: {} → {}",
-                        file_path.display(),
-                        final_dest.display()
-                    ));
-                } else {
-                    log.push(format!(
-                        "  [DRY RUN] Would move: {} → {}",
-                        file_path.display(),
-                        dest_path.display()
-                    ));
-                }
+                builder.add_move(
+                    file_path.clone(),
+                    dest_path,
+                    &dir_name,
+                    format!(
+                        "clustered with {} other file(s) as \"{}\"",
+                        group.files.len().saturating_sub(1),
+                        group.suggested_name
+                    ),
+                    Some(&group.suggested_name),
+                    None,
+                );
             }
         }
     }
 
-    Ok(log)
+    builder.build()
 }
 
-/// Sanitize directory name
-fn sanitize_dirname(name: &str) -> String {
-    name.chars()
-        .map(|c| {
-            if c.is_alphanumeric() || c == '_' || c == '-' {
-                c
-            } else {
-                '_'
-            }
-        })
-        .collect::<String>()
-        .trim_matches('_')
-        .to_string()
+/// What moving files into their groups did
+#[derive(Debug, Clone)]
+pub struct GroupMoves {
+    pub log: Vec<String>,
+    pub files_moved: usize,
+    pub errors: Vec<FileError>,
 }
 
-/// Handle file name conflicts by appending numbers
-fn handle_conflict(path: &Path) -> Result<PathBuf, io::Error> {
-    if !path.exists() {
-        return Ok(path.to_path_buf());
-    }
-
-    let parent = path.parent().unwrap_or(Path::new("."));
-    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
-    let ext = path
-        .extension()
-        .map(|e| format!(".{}", e.to_string_lossy()))
-        .unwrap_or_default();
-
-    for i in 1..1000 {
-        let new_name = format!("{}_{}{}", stem, i, ext);
-        let new_path = parent.join(new_name);
-        if !new_path.exists() {
-            return Ok(new_path);
-        }
-    }
-
-    Err(io::Error::new(
-        io::ErrorKind::AlreadyExists,
-        "Too many file conflicts",
-    ))
+/// Move files into their organized groups, journaling real moves when `options` has a journal
+pub fn move_files_to_groups(
+    base_path: &Path,
+    result: &ClusterResult,
+    plan_options: &PlanOptions,
+    options: &ExecuteOptions,
+) -> Result<GroupMoves, io::Error> {
+    let plan = plan_group_moves(base_path, result, plan_options);
+    let report = plan::execute(&plan, options);
+
+    let mut log = report.log_lines();
+    log.extend(report.warnings.iter().cloned());
+    Ok(GroupMoves {
+        log,
+        files_moved: report.files_moved(),
+        errors: report.errors(),
+    })
 }
 
 // TUI
@@ -748,7 +739,7 @@ enum AppState {
     Analyzing,
     Complete(ClusterResult),
     Moving,
-    Moved(Vec<String>),
+    Moved(GroupMoves),
 }
 
 pub struct IntelligentTuiApp {
@@ -757,6 +748,11 @@ pub struct IntelligentTuiApp {
     state: AppState,
     progress_message: String,
     log_messages: Vec<String>,
+    journal: Option<Journal>,
+    plan_options: PlanOptions,
+    transfer: TransferMode,
+    /// `--dry-run`: 'm' only previews the moves
+    dry_run: bool,
 }
 
 impl IntelligentTuiApp {
@@ -767,6 +763,42 @@ impl IntelligentTuiApp {
             state: AppState::Ready,
             progress_message: String::new(),
             log_messages: Vec::new(),
+            journal: None,
+            plan_options: PlanOptions::default(),
+            transfer: TransferMode::default(),
+            dry_run: false,
+        }
+    }
+
+    /// Only offer previews, for `--dry-run`
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Controls which files are collected and where groups are created
+    pub fn with_plan_options(mut self, plan_options: PlanOptions) -> Self {
+        self.plan_options = plan_options;
+        self
+    }
+
+    /// Copy or link files into their groups instead of moving them
+    pub fn with_transfer(mut self, transfer: TransferMode) -> Self {
+        self.transfer = transfer;
+        self
+    }
+
+    /// Journal real moves so the run can be undone with `kondo undo`
+    pub fn with_journal(mut self, journal: Journal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// What moving the files did, once they have been moved
+    pub fn moves(&self) -> Option<&GroupMoves> {
+        match &self.state {
+            AppState::Moved(moves) => Some(moves),
+            _ => None,
         }
     }
 
@@ -807,13 +839,13 @@ impl IntelligentTuiApp {
                                 self.start_analysis()?;
                             }
                         }
-                        KeyCode::Char('m') => {
+                        KeyCode::Char('m') if !self.dry_run => {
                             if let AppState::Complete(result) = &self.state {
                                 let result_clone = result.clone();
                                 self.move_files(&result_clone)?;
                             }
                         }
-                        KeyCode::Char('d') => {
+                        KeyCode::Char('m') | KeyCode::Char('d') => {
                             if let AppState::Complete(result) = &self.state {
                                 let result_clone = result.clone();
                                 self.dry_run_move(&result_clone)?;
@@ -832,7 +864,7 @@ impl IntelligentTuiApp {
         self.progress_message = "Initializing ML clustering...".to_string();
         self.log_messages.clear();
 
-        match organize_files_intelligently(&self.base_path, &self.config, None) {
+        match organize_files_intelligently(&self.base_path, &self.config, &self.plan_options.walk, None) {
             Ok(result) => {
                 self.state = AppState::Complete(result);
                 Ok(())
@@ -847,9 +879,18 @@ impl IntelligentTuiApp {
 
     fn dry_run_move(&mut self, result: &ClusterResult) -> io::Result<()> {
         self.log_messages.clear();
-        match move_files_to_groups(&self.base_path, result, true) {
-            Ok(log) => {
-                self.log_messages = log;
+        match move_files_to_groups(
+            &self.base_path,
+            result,
+            &self.plan_options,
+            &ExecuteOptions {
+                dry_run: true,
+                transfer: self.transfer,
+                ..Default::default()
+            },
+        ) {
+            Ok(moves) => {
+                self.log_messages = moves.log;
             }
             Err(e) => {
                 self.log_messages.push(format!("Error: {}", e));
@@ -862,16 +903,23 @@ impl IntelligentTuiApp {
         self.state = AppState::Moving;
         self.log_messages.clear();
 
-        match move_files_to_groups(&self.base_path, result, false) {
-            Ok(log) => {
-                self.state = AppState::Moved(log.clone());
-                self.log_messages = log;
+        match move_files_to_groups(
+            &self.base_path,
+            result,
+            &self.plan_options,
+            &ExecuteOptions {
+                journal: self.journal.as_ref(),
+                transfer: self.transfer,
+                ..Default::default()
+            },
+        ) {
+            Ok(moves) => {
+                self.log_messages = moves.log.clone();
+                self.state = AppState::Moved(moves);
                 Ok(())
             }
             Err(e) => {
-                self.state = AppState::Complete(ClusterResult {
-                    groups: result.groups.clone(),
-                });
+                self.state = AppState::Complete(result.clone());
                 self.log_messages.push(format!("Error: {}", e));
                 Err(e)
             }
@@ -1065,9 +1113,9 @@ impl IntelligentTuiApp {
 
         // Sort groups by file count
         let mut sorted_groups: Vec<_> = result.groups.iter().collect();
-        sorted_groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()));
+        sorted_groups.sort_by_key(|g| std::cmp::Reverse(g.files.len()));
 
-                        for (_i, group) in sorted_groups.iter().enumerate().take(12) {
+        for group in sorted_groups.iter().take(12) {
             // let icon = get_group_icon(&group.suggested_name);
 
             // Truncate long names
@@ -1103,7 +1151,11 @@ impl IntelligentTuiApp {
 
         lines.push(Line::from(""));
         lines.push(Line::from(Span::styled(
-            " Press 'm' to move files | 'd' for dry run preview",
+            if self.dry_run {
+                " Press 'd' for dry run preview (--dry-run: nothing is moved)"
+            } else {
+                " Press 'm' to move files | 'd' for dry run preview"
+            },
             Style::default().fg(Color::Yellow),
         )));
 
@@ -1267,11 +1319,16 @@ fn draw_info_panel(&self, f: &mut ratatui::Frame, area: Rect) {
                 Span::raw("Handling conflicts and organizing structure..."),
             ])),
         ],
-        AppState::Moved(log) => {
+        AppState::Moved(moves) => {
+            let summary = if moves.errors.is_empty() {
+                format!("All {} operations completed successfully", moves.log.len())
+            } else {
+                format!("{} files moved, {} failed", moves.files_moved, moves.errors.len())
+            };
             vec![
                 ListItem::new(Line::from(vec![
                     Span::styled("", Style::default().fg(Color::Green)),
-                    Span::raw(format!("All {} operations completed successfully", log.len())),
+                    Span::raw(summary),
                 ])),
                 ListItem::new(Line::from(vec![
                     Span::styled("", Style::default().fg(Color::Cyan)),
@@ -1297,6 +1354,7 @@ fn draw_info_panel(&self, f: &mut ratatui::Frame, area: Rect) {
         let controls = match &self.state {
             AppState::Ready => " 's' Start Analysis | 'q' Quit",
             AppState::Analyzing => " Analyzing... Please wait",
+            AppState::Complete(_) if self.dry_run => " 'd' Dry Run | 'q' Quit",
             AppState::Complete(_) => " 'm' Move Files | 'd' Dry Run | 'q' Quit",
             AppState::Moving => " Moving files... Please wait",
             AppState::Moved(_) => " 'q' Quit",
@@ -1330,9 +1388,9 @@ fn draw_info_panel(&self, f: &mut ratatui::Frame, area: Rect) {
 
                 // Sort groups by file count
                 let mut sorted_groups: Vec<_> = result.groups.iter().collect();
-                sorted_groups.sort_by(|a, b| b.files.len().cmp(&a.files.len()));
+                sorted_groups.sort_by_key(|g| std::cmp::Reverse(g.files.len()));
 
-                for (_i, group) in sorted_groups.iter().enumerate() {
+                for group in &sorted_groups {
                     // let icon = get_group_icon(&group.suggested_name);
                     self.log_messages.push(format!(
                         "  {:30} → {} files",
@@ -0,0 +1,737 @@
+// Persistent move journal so that any organizer run can be undone
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use super::transfer::{self, TransferMode};
+
+/// Kind of operation recorded in the journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JournalOp {
+    /// A file was moved from `source` to `destination`
+    Move,
+    /// A copy of `source` was created at `destination`
+    Copy,
+    /// A hard link to `source` was created at `destination`
+    Hardlink,
+    /// A symbolic link to `source` was created at `destination`
+    Symlink,
+    /// A directory was created at `destination`
+    CreateDir,
+    /// `source` was deleted because it was identical to `destination`
+    DeleteDuplicate,
+    /// `source` was replaced by a hard link to `destination`, which it was identical to
+    LinkDuplicate,
+    /// `source` was moved to `destination` to make room for the file that overwrote it
+    SetAside,
+    /// The entry of the run with the same `source` and `destination` was undone, while
+    /// others failed; undoing the run again skips it
+    Reverted,
+    /// The run was undone; its entries must not be replayed again
+    Undone,
+}
+
+impl JournalOp {
+    /// True for operations that put a file at `destination`
+    pub fn is_transfer(&self) -> bool {
+        matches!(
+            self,
+            JournalOp::Move | JournalOp::Copy | JournalOp::Hardlink | JournalOp::Symlink
+        )
+    }
+}
+
+impl From<TransferMode> for JournalOp {
+    fn from(mode: TransferMode) -> Self {
+        match mode {
+            TransferMode::Move => JournalOp::Move,
+            TransferMode::Copy => JournalOp::Copy,
+            TransferMode::Hardlink => JournalOp::Hardlink,
+            TransferMode::Symlink => JournalOp::Symlink,
+        }
+    }
+}
+
+/// One line of the journal file (JSON Lines format)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub run_id: String,
+    pub mode: String,
+    pub timestamp: String,
+    pub op: JournalOp,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<PathBuf>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub destination: Option<PathBuf>,
+
+    /// Size of the moved file, used to detect later changes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+
+    /// Modification time of the moved file in milliseconds since the epoch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_ms: Option<u64>,
+}
+
+/// Runs started by this process so far, so that runs within the same second get
+/// different ids
+static RUNS_STARTED: AtomicUsize = AtomicUsize::new(0);
+
+/// Append-only journal for a single organizer run.
+/// Safe to share between threads (the categorize mode moves files in parallel).
+pub struct Journal {
+    run_id: String,
+    mode: String,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Opens (or creates) the journal file and starts a new run
+    pub fn start_run(path: &Path, mode: &str) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let run_id = format!(
+            "{}-{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            std::process::id(),
+            RUNS_STARTED.fetch_add(1, Ordering::Relaxed) + 1
+        );
+
+        Ok(Self {
+            run_id,
+            mode: mode.to_string(),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// Records a completed move, copy or link. Must be called after the file is at `destination`.
+    pub fn record_transfer(&self, source: &Path, destination: &Path, transfer: TransferMode) -> io::Result<()> {
+        // A symlink has no content of its own to compare later
+        let (size, modified_ms) = match transfer {
+            TransferMode::Symlink => (None, None),
+            _ => file_fingerprint(destination),
+        };
+        self.append(JournalEntry {
+            run_id: self.run_id.clone(),
+            mode: self.mode.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            op: transfer.into(),
+            source: Some(absolute(source)),
+            destination: Some(absolute(destination)),
+            size,
+            modified_ms,
+        })
+    }
+
+    /// Records a file deleted as a duplicate. Undo restores it by copying `duplicate_of`,
+    /// so that file's fingerprint is what gets checked later.
+    pub fn record_deleted_duplicate(&self, source: &Path, duplicate_of: &Path) -> io::Result<()> {
+        let (size, modified_ms) = file_fingerprint(duplicate_of);
+        self.append(JournalEntry {
+            run_id: self.run_id.clone(),
+            mode: self.mode.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            op: JournalOp::DeleteDuplicate,
+            source: Some(absolute(source)),
+            destination: Some(absolute(duplicate_of)),
+            size,
+            modified_ms,
+        })
+    }
+
+    /// Records a duplicate replaced by a hard link. Undo turns it back into a copy of
+    /// `duplicate_of`, so that file's fingerprint is what gets checked later.
+    pub fn record_linked_duplicate(&self, source: &Path, duplicate_of: &Path) -> io::Result<()> {
+        let (size, modified_ms) = file_fingerprint(duplicate_of);
+        self.append(JournalEntry {
+            run_id: self.run_id.clone(),
+            mode: self.mode.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            op: JournalOp::LinkDuplicate,
+            source: Some(absolute(source)),
+            destination: Some(absolute(duplicate_of)),
+            size,
+            modified_ms,
+        })
+    }
+
+    /// Records an existing file moved out of the way of one that replaces it. Undo moves
+    /// it back once the replacement has been undone.
+    pub fn record_set_aside(&self, source: &Path, aside: &Path) -> io::Result<()> {
+        let (size, modified_ms) = file_fingerprint(aside);
+        self.append(JournalEntry {
+            run_id: self.run_id.clone(),
+            mode: self.mode.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            op: JournalOp::SetAside,
+            source: Some(absolute(source)),
+            destination: Some(absolute(aside)),
+            size,
+            modified_ms,
+        })
+    }
+
+    /// Records a directory created by this run so undo can remove it again
+    pub fn record_dir(&self, path: &Path) -> io::Result<()> {
+        self.append(JournalEntry {
+            run_id: self.run_id.clone(),
+            mode: self.mode.clone(),
+            timestamp: Local::now().to_rfc3339(),
+            op: JournalOp::CreateDir,
+            source: None,
+            destination: Some(absolute(path)),
+            size: None,
+            modified_ms: None,
+        })
+    }
+
+    fn append(&self, entry: JournalEntry) -> io::Result<()> {
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::other("journal lock poisoned"))?;
+        write_entry(&mut file, &entry)
+    }
+}
+
+/// Writes one entry as a single line so concurrent appends never interleave
+fn write_entry(file: &mut File, entry: &JournalEntry) -> io::Result<()> {
+    let mut line =
+        serde_json::to_string(entry).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    line.push('\n');
+    file.write_all(line.as_bytes())?;
+    file.flush()
+}
+
+/// Creates `path` and any missing parents, returning the directories that
+/// did not exist before (outermost first) so they can be journaled.
+pub fn create_dirs(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut missing = Vec::new();
+    let mut current = Some(path);
+
+    while let Some(dir) = current {
+        if dir.as_os_str().is_empty() || dir.exists() {
+            break;
+        }
+        missing.push(dir.to_path_buf());
+        current = dir.parent();
+    }
+
+    missing.reverse();
+    for dir in &missing {
+        // Another thread may have created it in the meantime
+        if let Err(e) = fs::create_dir(dir) {
+            if e.kind() != io::ErrorKind::AlreadyExists {
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(missing)
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Size and modification time (ms since epoch) of a file, if it exists
+pub fn file_fingerprint(path: &Path) -> (Option<u64>, Option<u64>) {
+    match fs::metadata(path) {
+        Ok(meta) => {
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            (Some(meta.len()), modified)
+        }
+        Err(_) => (None, None),
+    }
+}
+
+// Reading and Undo
+
+/// Reads all entries from the journal, ignoring malformed lines
+pub fn read_entries(path: &Path) -> io::Result<Vec<JournalEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let reader = BufReader::new(File::open(path)?);
+    let mut entries = Vec::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(entry) = serde_json::from_str::<JournalEntry>(&line) {
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Summary of a recorded run
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub mode: String,
+    pub started: String,
+    pub files_moved: usize,
+    pub undone: bool,
+}
+
+/// Lists runs in the order they were recorded
+pub fn list_runs(path: &Path) -> io::Result<Vec<RunSummary>> {
+    let entries = read_entries(path)?;
+    let mut runs: Vec<RunSummary> = Vec::new();
+
+    for entry in entries {
+        let index = match runs.iter().position(|r| r.run_id == entry.run_id) {
+            Some(i) => i,
+            None => {
+                runs.push(RunSummary {
+                    run_id: entry.run_id.clone(),
+                    mode: entry.mode.clone(),
+                    started: entry.timestamp.clone(),
+                    files_moved: 0,
+                    undone: false,
+                });
+                runs.len() - 1
+            }
+        };
+
+        match entry.op {
+            op if op.is_transfer() => runs[index].files_moved += 1,
+            JournalOp::Undone => runs[index].undone = true,
+            _ => {}
+        }
+    }
+
+    Ok(runs)
+}
+
+/// Directories created by runs that have not been undone
+pub fn created_dirs(path: &Path) -> io::Result<HashSet<PathBuf>> {
+    let entries = read_entries(path)?;
+
+    let undone: HashSet<&str> = entries
+        .iter()
+        .filter(|e| e.op == JournalOp::Undone)
+        .map(|e| e.run_id.as_str())
+        .collect();
+
+    Ok(entries
+        .iter()
+        .filter(|e| e.op == JournalOp::CreateDir && !undone.contains(e.run_id.as_str()))
+        .filter_map(|e| e.destination.clone())
+        .collect())
+}
+
+/// Records that `entry` was undone, so undoing its run again skips it
+fn mark_reverted(journal: Option<&mut File>, entry: &JournalEntry) -> io::Result<()> {
+    match journal {
+        Some(file) => write_entry(
+            file,
+            &JournalEntry {
+                timestamp: Local::now().to_rfc3339(),
+                op: JournalOp::Reverted,
+                size: None,
+                modified_ms: None,
+                ..entry.clone()
+            },
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Result of undoing a run
+#[derive(Debug, Default, Serialize)]
+pub struct UndoResult {
+    pub run_id: String,
+    pub files_restored: usize,
+    /// Copies and links removed again (their originals never moved)
+    pub copies_removed: usize,
+    /// Deleted or hard-linked duplicates recreated from the file they matched
+    pub duplicates_restored: usize,
+    pub dirs_removed: usize,
+    /// Files that were modified after the run and were left in place
+    pub changed: Vec<PathBuf>,
+    /// Files that no longer exist at their organized location
+    pub vanished: Vec<PathBuf>,
+    pub errors: Vec<String>,
+    /// False when some files could not be restored: the run then stays in the journal
+    /// and undoing it again retries only those
+    pub complete: bool,
+}
+
+/// Replays a run in reverse: moves files back, removes copies and links, puts back the
+/// files they overwrote, recreates deleted duplicates and unlinks hard-linked ones, then
+/// removes directories the run created.
+/// With `run_id = None` the most recent run that has not been undone yet is used.
+/// The run is only marked undone once every file is back.
+pub fn undo_run(journal_path: &Path, run_id: Option<&str>, dry_run: bool) -> io::Result<UndoResult> {
+    let entries = read_entries(journal_path)?;
+
+    let undone: HashSet<&str> = entries
+        .iter()
+        .filter(|e| e.op == JournalOp::Undone)
+        .map(|e| e.run_id.as_str())
+        .collect();
+
+    let target = match run_id {
+        Some(id) => entries
+            .iter()
+            .find(|e| e.run_id == id)
+            .map(|e| e.run_id.clone())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::NotFound, format!("No run with id '{}'", id))
+            })?,
+        None => entries
+            .iter()
+            .rev()
+            .find(|e| e.op != JournalOp::Undone && !undone.contains(e.run_id.as_str()))
+            .map(|e| e.run_id.clone())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Nothing to undo"))?,
+    };
+
+    if undone.contains(target.as_str()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("Run '{}' has already been undone", target),
+        ));
+    }
+
+    let run_entries: Vec<&JournalEntry> = entries.iter().filter(|e| e.run_id == target).collect();
+    let mut result = UndoResult {
+        run_id: target.clone(),
+        ..Default::default()
+    };
+    // A dry run leaves organized files in place; the names they would free count as free
+    let mut freed: HashSet<PathBuf> = HashSet::new();
+    // Left over from an earlier undo that failed part of the way
+    let reverted: HashSet<(&Path, &Path)> = run_entries
+        .iter()
+        .filter(|e| e.op == JournalOp::Reverted)
+        .filter_map(|e| Some((e.source.as_deref()?, e.destination.as_deref()?)))
+        .collect();
+    let mut journal = match dry_run {
+        true => None,
+        false => Some(OpenOptions::new().append(true).open(journal_path)?),
+    };
+
+    // Move files back, newest first
+    for entry in run_entries
+        .iter()
+        .rev()
+        .filter(|e| {
+            e.op.is_transfer()
+                || matches!(
+                    e.op,
+                    JournalOp::DeleteDuplicate | JournalOp::LinkDuplicate | JournalOp::SetAside
+                )
+        })
+    {
+        let (Some(source), Some(destination)) = (&entry.source, &entry.destination) else {
+            continue;
+        };
+        if reverted.contains(&(source.as_path(), destination.as_path())) {
+            continue;
+        }
+
+        // Dangling symlinks still count as present
+        if fs::symlink_metadata(destination).is_err() {
+            result.vanished.push(destination.clone());
+            continue;
+        }
+
+        let (size, modified_ms) = file_fingerprint(destination);
+        let size_changed = entry.size.is_some() && size != entry.size;
+        let mtime_changed = entry.modified_ms.is_some() && modified_ms != entry.modified_ms;
+        if size_changed || mtime_changed {
+            result.changed.push(destination.clone());
+            continue;
+        }
+
+        if entry.op == JournalOp::DeleteDuplicate {
+            if source.exists() {
+                result.errors.push(format!(
+                    "Cannot restore duplicate '{}': it already exists",
+                    source.display()
+                ));
+                continue;
+            }
+            if !dry_run {
+                if let Err(e) = transfer::transfer_file(destination, source, TransferMode::Copy, None) {
+                    result.errors.push(format!(
+                        "Failed to restore '{}': {}",
+                        source.display(),
+                        e
+                    ));
+                    continue;
+                }
+            }
+            mark_reverted(journal.as_mut(), entry)?;
+            result.duplicates_restored += 1;
+            continue;
+        }
+
+        if entry.op == JournalOp::LinkDuplicate {
+            if !source.exists() {
+                result.vanished.push(source.clone());
+                continue;
+            }
+            // A copy renamed over the link gives the file its own content again
+            if !dry_run {
+                if let Err(e) = transfer::replace_file(destination, source, TransferMode::Copy, None) {
+                    result.errors.push(format!(
+                        "Failed to unlink '{}': {}",
+                        source.display(),
+                        e
+                    ));
+                    continue;
+                }
+            }
+            mark_reverted(journal.as_mut(), entry)?;
+            result.duplicates_restored += 1;
+            continue;
+        }
+
+        if !matches!(entry.op, JournalOp::Move | JournalOp::SetAside) {
+            if !dry_run {
+                if let Err(e) = fs::remove_file(destination) {
+                    result.errors.push(format!(
+                        "Failed to remove '{}': {}",
+                        destination.display(),
+                        e
+                    ));
+                    continue;
+                }
+            }
+            mark_reverted(journal.as_mut(), entry)?;
+            freed.insert(destination.clone());
+            result.copies_removed += 1;
+            continue;
+        }
+
+        if source.exists() && !freed.contains(source) {
+            result.errors.push(format!(
+                "Cannot restore '{}': '{}' already exists",
+                destination.display(),
+                source.display()
+            ));
+            continue;
+        }
+
+        if dry_run {
+            freed.insert(destination.clone());
+            result.files_restored += 1;
+            continue;
+        }
+
+        if let Some(parent) = source.parent() {
+            if let Err(e) = fs::create_dir_all(parent) {
+                result.errors.push(format!("Failed to recreate '{}': {}", parent.display(), e));
+                continue;
+            }
+        }
+
+        match transfer::move_file(destination, source, None) {
+            Ok(_) => {
+                mark_reverted(journal.as_mut(), entry)?;
+                result.files_restored += 1;
+            }
+            Err(e) => result.errors.push(format!(
+                "Failed to restore '{}': {}",
+                destination.display(),
+                e
+            )),
+        }
+    }
+    // Directories that stay because they are not empty do not keep the run open
+    result.complete = result.errors.is_empty();
+
+    // Remove created directories, innermost (last created) first
+    for entry in run_entries.iter().rev().filter(|e| e.op == JournalOp::CreateDir) {
+        let Some(dir) = &entry.destination else {
+            continue;
+        };
+
+        if !dir.is_dir() {
+            continue;
+        }
+
+        let is_empty = fs::read_dir(dir)
+            .map(|mut d| d.next().is_none())
+            .unwrap_or(false);
+
+        if !is_empty && !dry_run {
+            result
+                .errors
+                .push(format!("Kept non-empty directory: {}", dir.display()));
+            continue;
+        }
+
+        if dry_run {
+            result.dirs_removed += 1;
+            continue;
+        }
+
+        match fs::remove_dir(dir) {
+            Ok(_) => result.dirs_removed += 1,
+            Err(e) => result
+                .errors
+                .push(format!("Failed to remove '{}': {}", dir.display(), e)),
+        }
+    }
+
+    if let Some(file) = journal.as_mut().filter(|_| result.complete) {
+        let mode = run_entries
+            .first()
+            .map(|e| e.mode.clone())
+            .unwrap_or_default();
+        write_entry(
+            file,
+            &JournalEntry {
+                run_id: target,
+                mode,
+                timestamp: Local::now().to_rfc3339(),
+                op: JournalOp::Undone,
+                source: None,
+                destination: None,
+                size: None,
+                modified_ms: None,
+            },
+        )?;
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_undo_restores_files_and_removes_dirs() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let source = dir.path().join("photo.jpg");
+        fs::write(&source, b"jpeg").unwrap();
+
+        let journal = Journal::start_run(&journal_path, "categorize").unwrap();
+        let target_dir = dir.path().join("Images");
+        for created in create_dirs(&target_dir).unwrap() {
+            journal.record_dir(&created).unwrap();
+        }
+        let destination = target_dir.join("photo.jpg");
+        fs::rename(&source, &destination).unwrap();
+        journal.record_transfer(&source, &destination, TransferMode::Move).unwrap();
+
+        let result = undo_run(&journal_path, None, false).unwrap();
+        assert_eq!(result.files_restored, 1);
+        assert_eq!(result.dirs_removed, 1);
+        assert!(source.exists());
+        assert!(!target_dir.exists());
+
+        // A second undo has nothing left to do
+        assert!(undo_run(&journal_path, None, false).is_err());
+    }
+
+    #[test]
+    fn test_runs_started_together_are_undone_separately() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let first = Journal::start_run(&journal_path, "categorize").unwrap();
+        let second = Journal::start_run(&journal_path, "categorize").unwrap();
+        assert_ne!(first.run_id(), second.run_id());
+
+        for (journal, name) in [(&first, "a.txt"), (&second, "b.txt")] {
+            let source = dir.path().join(name);
+            let destination = dir.path().join(format!("moved-{}", name));
+            fs::write(&destination, name).unwrap();
+            journal.record_transfer(&source, &destination, TransferMode::Move).unwrap();
+        }
+
+        let result = undo_run(&journal_path, None, false).unwrap();
+        assert_eq!(result.run_id, second.run_id());
+        assert_eq!(result.files_restored, 1);
+        assert!(dir.path().join("b.txt").exists());
+        assert!(dir.path().join("moved-a.txt").exists());
+    }
+
+    #[test]
+    fn test_failed_restores_are_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let journal = Journal::start_run(&journal_path, "categorize").unwrap();
+        for name in ["a.txt", "b.txt"] {
+            let destination = dir.path().join(format!("moved-{}", name));
+            fs::write(&destination, name).unwrap();
+            journal
+                .record_transfer(&dir.path().join(name), &destination, TransferMode::Move)
+                .unwrap();
+        }
+
+        // Something new took the original name of a.txt
+        fs::write(dir.path().join("a.txt"), b"newcomer").unwrap();
+        let result = undo_run(&journal_path, None, false).unwrap();
+        assert_eq!(result.files_restored, 1);
+        assert_eq!(result.errors.len(), 1);
+        assert!(!result.complete);
+
+        fs::remove_file(dir.path().join("a.txt")).unwrap();
+        let result = undo_run(&journal_path, None, false).unwrap();
+        assert_eq!(result.run_id, journal.run_id());
+        assert_eq!(result.files_restored, 1);
+        assert!(result.vanished.is_empty());
+        assert!(result.complete);
+        assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"a.txt");
+        assert!(undo_run(&journal_path, None, false).is_err());
+    }
+
+    #[test]
+    fn test_undo_reports_vanished_and_changed() {
+        let dir = tempfile::tempdir().unwrap();
+        let journal_path = dir.path().join("journal.jsonl");
+        let journal = Journal::start_run(&journal_path, "filename").unwrap();
+
+        let gone_dest = dir.path().join("gone.txt");
+        fs::write(&gone_dest, b"a").unwrap();
+        journal
+            .record_transfer(&dir.path().join("gone_src.txt"), &gone_dest, TransferMode::Move)
+            .unwrap();
+        fs::remove_file(&gone_dest).unwrap();
+
+        let changed_dest = dir.path().join("changed.txt");
+        fs::write(&changed_dest, b"a").unwrap();
+        journal
+            .record_transfer(&dir.path().join("changed_src.txt"), &changed_dest, TransferMode::Move)
+            .unwrap();
+        fs::write(&changed_dest, b"much longer content").unwrap();
+
+        let result = undo_run(&journal_path, Some(journal.run_id()), false).unwrap();
+        assert_eq!(result.files_restored, 0);
+        assert_eq!(result.vanished.len(), 1);
+        assert_eq!(result.changed.len(), 1);
+        assert!(changed_dest.exists());
+    }
+}
@@ -0,0 +1,233 @@
+// Capture date, camera and GPS presence from photo EXIF and MP4/MOV headers
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use exif::{In, Reader, Tag, Value};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Seconds between the MP4 epoch (1904-01-01) and the Unix epoch
+const MP4_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// `ftyp` brands of HEIF/AVIF stills, which carry EXIF rather than a movie header
+const HEIF_BRANDS: &[&[u8; 4]] = &[
+    b"heic", b"heix", b"heim", b"heis", b"hevc", b"hevx", b"mif1", b"msf1", b"avif",
+];
+
+/// What a photo or video says about itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MediaInfo {
+    /// When it was taken, in the camera's local time
+    pub captured: Option<NaiveDateTime>,
+    /// Camera make and model, e.g. "Canon EOS R5"
+    pub camera: Option<String>,
+    /// Whether a location is recorded
+    pub has_gps: bool,
+}
+
+/// Reads EXIF from JPEG, HEIC, TIFF (and raw formats built on it), PNG and WebP, or the
+/// `mvhd` creation time from MP4/MOV. None for other files or when nothing is recorded.
+pub fn read_media_info(path: &Path) -> Option<MediaInfo> {
+    let mut reader = BufReader::new(File::open(path).ok()?);
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header).ok()?;
+    reader.seek(SeekFrom::Start(0)).ok()?;
+
+    let info = if &header[4..8] == b"ftyp" && !HEIF_BRANDS.iter().any(|b| header[8..12] == b[..]) {
+        MediaInfo {
+            captured: movie_creation_time(&mut reader),
+            ..Default::default()
+        }
+    } else {
+        read_exif(&mut reader)?
+    };
+
+    (info != MediaInfo::default()).then_some(info)
+}
+
+fn read_exif(reader: &mut BufReader<File>) -> Option<MediaInfo> {
+    let exif = Reader::new().read_from_container(reader).ok()?;
+    let text = |tag: Tag| match exif.get_field(tag, In::PRIMARY).map(|f| &f.value) {
+        Some(Value::Ascii(parts)) => parts
+            .first()
+            .map(|bytes| String::from_utf8_lossy(bytes).trim_matches(['\0', ' ']).to_string())
+            .filter(|s| !s.is_empty()),
+        _ => None,
+    };
+
+    let captured = [Tag::DateTimeOriginal, Tag::DateTimeDigitized, Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| text(tag).and_then(|value| parse_exif_datetime(&value)));
+
+    let camera = match (text(Tag::Make), text(Tag::Model)) {
+        // Most models already start with the make, e.g. "Canon" + "Canon EOS R5"
+        (Some(make), Some(model)) => {
+            let brand = make.split_whitespace().next().unwrap_or_default().to_lowercase();
+            if model.to_lowercase().starts_with(&brand) {
+                Some(model)
+            } else {
+                Some(format!("{} {}", make, model))
+            }
+        }
+        (make, model) => model.or(make),
+    };
+
+    Some(MediaInfo {
+        captured,
+        camera,
+        has_gps: exif.get_field(Tag::GPSLatitude, In::PRIMARY).is_some(),
+    })
+}
+
+/// Parses "2024:03:15 10:15:00"; cameras without a set clock write zeros or blanks
+fn parse_exif_datetime(value: &str) -> Option<NaiveDateTime> {
+    let date = exif::DateTime::from_ascii(value.as_bytes()).ok()?;
+    NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?.and_hms_opt(
+        date.hour.into(),
+        date.minute.into(),
+        date.second.into(),
+    )
+}
+
+/// Creation time from `moov/mvhd`, converted from UTC to local time
+fn movie_creation_time<R: Read + Seek>(reader: &mut R) -> Option<NaiveDateTime> {
+    let end = reader.seek(SeekFrom::End(0)).ok()?;
+    let (moov_start, moov_end) = find_box(reader, 0, end, b"moov")?;
+    let (mvhd_start, _) = find_box(reader, moov_start, moov_end, b"mvhd")?;
+
+    reader.seek(SeekFrom::Start(mvhd_start)).ok()?;
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version).ok()?;
+    let seconds = if version[0] == 1 {
+        let mut time = [0u8; 8];
+        reader.read_exact(&mut time).ok()?;
+        u64::from_be_bytes(time)
+    } else {
+        let mut time = [0u8; 4];
+        reader.read_exact(&mut time).ok()?;
+        u32::from_be_bytes(time).into()
+    };
+
+    // Zero means the recorder did not know the time
+    if seconds == 0 {
+        return None;
+    }
+    let utc = DateTime::from_timestamp(i64::try_from(seconds).ok()? - MP4_EPOCH_OFFSET, 0)?;
+    Some(utc.with_timezone(&Local).naive_local())
+}
+
+/// Content range of the first `kind` box between `start` and `end`. Boxes are skipped
+/// by seeking, so large `mdat` payloads before `moov` are never read. Sizes come from
+/// the file, so they are only compared against what is left, never added up.
+pub(super) fn find_box<R: Read + Seek>(reader: &mut R, start: u64, end: u64, kind: &[u8; 4]) -> Option<(u64, u64)> {
+    let mut position = start;
+    while end.saturating_sub(position) >= 8 {
+        reader.seek(SeekFrom::Start(position)).ok()?;
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header).ok()?;
+
+        let mut size = u64::from(u32::from_be_bytes([header[0], header[1], header[2], header[3]]));
+        let mut header_len = 8;
+        if size == 1 {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large).ok()?;
+            size = u64::from_be_bytes(large);
+            header_len = 16;
+        } else if size == 0 {
+            size = end - position;
+        }
+        if size < header_len || size > end - position {
+            return None;
+        }
+
+        if &header[4..8] == kind {
+            return Some((position + header_len, position + size));
+        }
+        position += size;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn mp4_box(kind: &[u8; 4], content: &[u8]) -> Vec<u8> {
+        let mut data = ((content.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(content);
+        data
+    }
+
+    #[test]
+    fn test_exif_from_tiff() {
+        use exif::experimental::Writer;
+        use exif::{Field, Rational};
+
+        let ascii = |tag: Tag, text: &str| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![text.as_bytes().to_vec()]),
+        };
+        let fields = [
+            ascii(Tag::Make, "Canon"),
+            ascii(Tag::Model, "Canon EOS R5"),
+            ascii(Tag::DateTimeOriginal, "2024:03:15 10:15:00"),
+            Field {
+                tag: Tag::GPSLatitude,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![Rational::from((52, 1)); 3]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("photo.tif");
+        std::fs::write(&path, tiff.into_inner()).unwrap();
+
+        let info = read_media_info(&path).unwrap();
+        assert_eq!(info.camera.as_deref(), Some("Canon EOS R5"));
+        assert_eq!(
+            info.captured,
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap().and_hms_opt(10, 15, 0)
+        );
+        assert!(info.has_gps);
+    }
+
+    #[test]
+    fn test_mvhd_creation_time() {
+        // 2024-03-15 10:15:00 UTC in seconds since 1904
+        let seconds = (1_710_497_700 + MP4_EPOCH_OFFSET) as u32;
+        let mut mvhd = vec![0u8; 4];
+        mvhd.extend_from_slice(&seconds.to_be_bytes());
+        mvhd.extend_from_slice(&[0u8; 12]);
+
+        let mut movie = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        movie.extend(mp4_box(b"mdat", &[0u8; 64]));
+        movie.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+
+        let expected = DateTime::from_timestamp(1_710_497_700, 0)
+            .unwrap()
+            .with_timezone(&Local)
+            .naive_local();
+        assert_eq!(movie_creation_time(&mut Cursor::new(movie)), Some(expected));
+
+        // A 64-bit size that runs past the end of the file is malformed, not a panic
+        let mut malformed = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        malformed.extend_from_slice(&1u32.to_be_bytes());
+        malformed.extend_from_slice(b"free");
+        malformed.extend_from_slice(&u64::MAX.to_be_bytes());
+        malformed.extend(mp4_box(b"moov", &mp4_box(b"mvhd", &mvhd)));
+        assert_eq!(movie_creation_time(&mut Cursor::new(malformed)), None);
+        assert_eq!(parse_exif_datetime("0000:00:00 00:00:00"), None);
+        assert_eq!(
+            parse_exif_datetime("2024:03:15 10:15:00"),
+            NaiveDate::from_ymd_opt(2024, 3, 15).unwrap().and_hms_opt(10, 15, 0)
+        );
+    }
+}
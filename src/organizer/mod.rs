@@ -1,7 +1,22 @@
 // organizer/mod.rs
 // Module declarations for the organizer functionality
 
+pub mod audio;
 pub mod categorise;
+pub mod conflict;
+pub mod date;
+pub mod dedupe;
+pub mod error;
 pub mod filename;
+pub mod ignore;
 pub mod intelligent;
+pub mod journal;
+pub mod media;
+pub mod plan;
+pub mod rules;
+pub mod sniff;
+pub mod template;
+pub mod transfer;
+pub mod walk;
+pub mod watch;
 
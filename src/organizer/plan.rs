@@ -0,0 +1,1303 @@
+// Two-phase organization: every mode builds a Plan, one executor applies it
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use super::conflict::{self, ConflictOptions, ConflictPolicy};
+use super::error::{FileError, KondoError};
+use super::journal::{self, Journal};
+use super::transfer::{self, ProgressFn, TransferMode};
+use super::walk::{WalkOptions, TRASH_DIR};
+
+/// Why a file is left where it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    SingleFile,        // Only one file in its group
+    SystemFile,        // Matched by a skip pattern
+    AlreadyOrganized,  // Already in a folder Kondo created
+    InvalidName,       // Filename is not valid UTF-8
+    NameConflict,      // No free destination name could be found
+    DestinationExists, // Conflict policy left the existing file alone
+    ExistingNewer,     // keep-newer: the existing file is at least as new
+    ExistingLarger,    // keep-larger: the existing file is at least as large
+    IdenticalExists,   // Identical file already there, but originals must be kept
+    NoDate,            // None of the configured date sources gave a date
+}
+
+impl SkipReason {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            SkipReason::SingleFile => "no similar matches",
+            SkipReason::SystemFile => "matches a skip pattern",
+            SkipReason::AlreadyOrganized => "already organized",
+            SkipReason::InvalidName => "invalid filename",
+            SkipReason::NameConflict => "no free destination name",
+            SkipReason::DestinationExists => "destination already exists",
+            SkipReason::ExistingNewer => "existing file is newer",
+            SkipReason::ExistingLarger => "existing file is larger",
+            SkipReason::IdenticalExists => "identical file already exists",
+            SkipReason::NoDate => "no date found",
+        }
+    }
+}
+
+/// Information about a skipped file
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub filename: String,
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// How a taken destination was resolved for a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conflict {
+    /// The file gets a free name built from the rename template
+    Renamed,
+    /// The existing file at the destination is replaced
+    Overwrite,
+}
+
+/// A single step of a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// Create a directory (and any missing parents)
+    CreateDir { path: PathBuf },
+
+    /// Move a file into the folder of `group`
+    Move {
+        source: PathBuf,
+        destination: PathBuf,
+        /// Folder/category label the file is sorted into
+        group: String,
+        /// Human readable explanation of the decision
+        reason: String,
+        /// Name the grouping algorithm proposed for the folder
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        suggested_name: Option<String>,
+        /// Similarity score that led to the grouping
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        score: Option<f64>,
+        /// Size and mtime of the source when planned, checked before moving
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modified_ms: Option<u64>,
+        /// Set when the natural destination was already taken
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conflict: Option<Conflict>,
+    },
+
+    /// Delete a file because `duplicate_of` has exactly the same content.
+    /// Runs after every move, and only when files are moved.
+    DeleteDuplicate {
+        source: PathBuf,
+        duplicate_of: PathBuf,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modified_ms: Option<u64>,
+    },
+
+    /// Leave a file alone, optionally parking it in a skip folder
+    Skip {
+        source: PathBuf,
+        reason: SkipReason,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        destination: Option<PathBuf>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        size: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        modified_ms: Option<u64>,
+    },
+}
+
+impl Action {
+    /// Source, destination and recorded fingerprint of every action that moves a file
+    fn transfer(&self) -> Option<(&Path, &Path, Fingerprint)> {
+        match self {
+            Action::CreateDir { .. } | Action::DeleteDuplicate { .. } => None,
+            Action::Move {
+                source,
+                destination,
+                size,
+                modified_ms,
+                ..
+            } => Some((source, destination, (*size, *modified_ms))),
+            Action::Skip {
+                source,
+                destination,
+                size,
+                modified_ms,
+                ..
+            } => destination
+                .as_deref()
+                .map(|dest| (source.as_path(), dest, (*size, *modified_ms))),
+        }
+    }
+}
+
+/// (size, mtime in ms) of a source file
+type Fingerprint = (Option<u64>, Option<u64>);
+
+/// Checks a source against the fingerprint recorded in the plan
+fn check_source(source: &Path, expected: Fingerprint) -> Result<(), KondoError> {
+    if !source.exists() {
+        return Err(KondoError::SourceVanished);
+    }
+
+    let (size, modified_ms) = journal::file_fingerprint(source);
+    if expected.0.is_some() && size != expected.0 {
+        return Err(KondoError::SourceChanged(
+            "source changed size since the plan was made".to_string(),
+        ));
+    }
+    if expected.1.is_some() && modified_ms != expected.1 {
+        return Err(KondoError::SourceChanged(
+            "source was modified since the plan was made".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The complete list of actions a mode wants to perform
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Plan {
+    pub mode: String,
+    pub base_path: PathBuf,
+    /// How files reach their destination when the plan is applied
+    #[serde(default)]
+    pub transfer: TransferMode,
+    /// Applies to destinations that were taken after the plan was made
+    #[serde(default)]
+    pub conflict_policy: ConflictPolicy,
+    #[serde(default = "default_rename_template")]
+    pub rename_template: String,
+    pub actions: Vec<Action>,
+}
+
+fn default_rename_template() -> String {
+    conflict::DEFAULT_RENAME_TEMPLATE.to_string()
+}
+
+impl Plan {
+    pub fn new(mode: &str, base_path: &Path) -> Self {
+        Self {
+            mode: mode.to_string(),
+            base_path: base_path.to_path_buf(),
+            transfer: TransferMode::default(),
+            conflict_policy: ConflictPolicy::default(),
+            rename_template: default_rename_template(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn conflict_options(&self) -> ConflictOptions {
+        ConflictOptions {
+            policy: self.conflict_policy,
+            rename_template: self.rename_template.clone(),
+        }
+    }
+
+    /// Reads a plan written by `save`, JSON or TOML depending on the extension.
+    /// Missing CreateDir actions are added so hand-edited destinations work.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let mut plan: Plan = if is_toml(path) {
+            toml::from_str(&content).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("TOML parse error: {}", e),
+                )
+            })?
+        } else {
+            serde_json::from_str(&content).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("JSON parse error: {}", e),
+                )
+            })?
+        };
+
+        conflict::validate_rename_template(&plan.rename_template)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        plan.add_missing_dirs();
+        Ok(plan)
+    }
+
+    /// Writes the plan as TOML for `.toml` paths and as JSON otherwise
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let content = if is_toml(path) {
+            toml::to_string_pretty(self).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("TOML serialize error: {}", e),
+                )
+            })?
+        } else {
+            serde_json::to_string_pretty(self).map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("JSON serialize error: {}", e),
+                )
+            })?
+        };
+        fs::write(path, content)
+    }
+
+    /// Lists every source that is missing or no longer matches the plan
+    pub fn validate(&self) -> Vec<FileError> {
+        self.actions
+            .iter()
+            .filter_map(|action| match action {
+                Action::DeleteDuplicate {
+                    source,
+                    size,
+                    modified_ms,
+                    ..
+                } => Some((source.as_path(), (*size, *modified_ms))),
+                _ => action
+                    .transfer()
+                    .map(|(source, _, expected)| (source, expected)),
+            })
+            .filter_map(|(source, expected)| {
+                check_source(source, expected).err().map(|error| FileError {
+                    path: source.to_path_buf(),
+                    error,
+                })
+            })
+            .collect()
+    }
+
+    fn add_missing_dirs(&mut self) {
+        let mut planned: HashSet<PathBuf> = self
+            .actions
+            .iter()
+            .filter_map(|a| match a {
+                Action::CreateDir { path } => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+
+        let missing: Vec<PathBuf> = self
+            .actions
+            .iter()
+            .filter_map(Action::transfer)
+            .filter_map(|(_, dest, _)| dest.parent())
+            .filter(|parent| !parent.is_dir() && planned.insert(parent.to_path_buf()))
+            .map(Path::to_path_buf)
+            .collect();
+
+        let dirs = missing.into_iter().map(|path| Action::CreateDir { path });
+        self.actions.splice(0..0, dirs);
+    }
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("toml"))
+}
+
+/// Inputs shared by every planner
+#[derive(Debug, Clone, Default)]
+pub struct PlanOptions {
+    /// Which files are collected
+    pub walk: WalkOptions,
+    /// Where organized folders are created; the scanned directory when None
+    pub destination_root: Option<PathBuf>,
+    /// What happens when a destination is already taken
+    pub conflict: ConflictOptions,
+}
+
+impl PlanOptions {
+    /// Directory the organized folders of a run on `base_path` go into
+    pub fn output_root(&self, base_path: &Path) -> PathBuf {
+        self.destination_root
+            .clone()
+            .unwrap_or_else(|| base_path.to_path_buf())
+    }
+}
+
+/// Expands a leading `~` to the home directory
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => {
+            match dirs::home_dir() {
+                Some(home) => home.join(rest.trim_start_matches(['/', '\\'])),
+                None => PathBuf::from(path),
+            }
+        }
+        _ => PathBuf::from(path),
+    }
+}
+
+/// Helper used by planners to emit directory creation once, to keep track of
+/// destinations already claimed by earlier actions in the plan and to apply the
+/// conflict policy when a destination is taken
+pub struct PlanBuilder {
+    plan: Plan,
+    planned_dirs: HashSet<PathBuf>,
+    /// Claimed destination → the source that goes there
+    reserved: HashMap<PathBuf, PathBuf>,
+    conflict: ConflictOptions,
+}
+
+/// What `add_move` does with a taken destination
+enum Resolution {
+    Move(PathBuf, Conflict),
+    Skip(SkipReason),
+    Delete(PathBuf),
+}
+
+impl PlanBuilder {
+    pub fn new(mode: &str, base_path: &Path) -> Self {
+        Self {
+            plan: Plan::new(mode, base_path),
+            planned_dirs: HashSet::new(),
+            reserved: HashMap::new(),
+            conflict: ConflictOptions::default(),
+        }
+    }
+
+    pub fn with_conflict(mut self, conflict: ConflictOptions) -> Self {
+        self.conflict = conflict;
+        self
+    }
+
+    /// True if the path exists on disk or was already claimed by this plan
+    pub fn is_taken(&self, path: &Path) -> bool {
+        self.reserved.contains_key(path) || path.exists()
+    }
+
+    /// `path` itself if free, otherwise the first free name from the rename template
+    pub fn free_name(&self, path: &Path) -> Option<PathBuf> {
+        self.conflict.free_name(path, &|p| self.is_taken(p))
+    }
+
+    /// Emits a CreateDir action unless the directory exists or is already planned
+    pub fn ensure_dir(&mut self, path: &Path) {
+        if path.is_dir() || !self.planned_dirs.insert(path.to_path_buf()) {
+            return;
+        }
+        self.plan.actions.push(Action::CreateDir {
+            path: path.to_path_buf(),
+        });
+    }
+
+    pub fn add_move(
+        &mut self,
+        source: PathBuf,
+        destination: PathBuf,
+        group: &str,
+        reason: String,
+        suggested_name: Option<&str>,
+        score: Option<f64>,
+    ) {
+        let (destination, conflict) = if self.is_taken(&destination) {
+            match self.resolve_conflict(&source, &destination) {
+                Resolution::Move(path, conflict) => (path, Some(conflict)),
+                Resolution::Skip(reason) => return self.add_skip(source, reason, None),
+                Resolution::Delete(duplicate_of) => {
+                    let (size, modified_ms) = journal::file_fingerprint(&source);
+                    self.plan.actions.push(Action::DeleteDuplicate {
+                        source,
+                        duplicate_of,
+                        size,
+                        modified_ms,
+                    });
+                    return;
+                }
+            }
+        } else {
+            (destination, None)
+        };
+
+        if let Some(parent) = destination.parent() {
+            self.ensure_dir(parent);
+        }
+        self.reserved.insert(destination.clone(), source.clone());
+        let (size, modified_ms) = journal::file_fingerprint(&source);
+        self.plan.actions.push(Action::Move {
+            source,
+            destination,
+            group: group.to_string(),
+            reason,
+            suggested_name: suggested_name.map(str::to_string),
+            score,
+            size,
+            modified_ms,
+            conflict,
+        });
+    }
+
+    fn resolve_conflict(&self, source: &Path, destination: &Path) -> Resolution {
+        let rename = || match self.free_name(destination) {
+            Some(path) => Resolution::Move(path, Conflict::Renamed),
+            None => Resolution::Skip(SkipReason::NameConflict),
+        };
+        // A folder can neither be replaced nor compared with a file
+        if destination.is_dir() {
+            return rename();
+        }
+
+        // Files moved by the same run never replace each other
+        let planned = self.reserved.get(destination);
+        let (source_size, source_modified) = journal::file_fingerprint(source);
+        let (existing_size, existing_modified) = journal::file_fingerprint(destination);
+
+        match self.conflict.policy {
+            ConflictPolicy::Rename => rename(),
+            ConflictPolicy::Skip => Resolution::Skip(SkipReason::DestinationExists),
+            ConflictPolicy::Overwrite | ConflictPolicy::KeepNewer | ConflictPolicy::KeepLarger
+                if planned.is_some() =>
+            {
+                Resolution::Skip(SkipReason::DestinationExists)
+            }
+            ConflictPolicy::Overwrite => {
+                Resolution::Move(destination.to_path_buf(), Conflict::Overwrite)
+            }
+            ConflictPolicy::KeepNewer if source_modified > existing_modified => {
+                Resolution::Move(destination.to_path_buf(), Conflict::Overwrite)
+            }
+            ConflictPolicy::KeepNewer => Resolution::Skip(SkipReason::ExistingNewer),
+            ConflictPolicy::KeepLarger if source_size > existing_size => {
+                Resolution::Move(destination.to_path_buf(), Conflict::Overwrite)
+            }
+            ConflictPolicy::KeepLarger => Resolution::Skip(SkipReason::ExistingLarger),
+            ConflictPolicy::DeleteIfIdentical => {
+                let occupant = planned.map(PathBuf::as_path).unwrap_or(destination);
+                if same_content(source, occupant) {
+                    Resolution::Delete(destination.to_path_buf())
+                } else {
+                    rename()
+                }
+            }
+        }
+    }
+
+    pub fn add_skip(&mut self, source: PathBuf, reason: SkipReason, destination: Option<PathBuf>) {
+        let (size, modified_ms) = match &destination {
+            Some(dest) => {
+                if let Some(parent) = dest.parent() {
+                    self.ensure_dir(parent);
+                }
+                self.reserved.insert(dest.clone(), source.clone());
+                journal::file_fingerprint(&source)
+            }
+            None => (None, None),
+        };
+        self.plan.actions.push(Action::Skip {
+            source,
+            reason,
+            destination,
+            size,
+            modified_ms,
+        });
+    }
+
+    pub fn build(mut self) -> Plan {
+        self.plan.conflict_policy = self.conflict.policy;
+        self.plan.rename_template = self.conflict.rename_template;
+        self.plan
+    }
+}
+
+/// True if both files exist and have identical bytes
+fn same_content(a: &Path, b: &Path) -> bool {
+    let size = |path: &Path| fs::metadata(path).map(|m| m.len()).ok();
+    if size(a).is_none() || size(a) != size(b) {
+        return false;
+    }
+    match (transfer::hash_file(a), transfer::hash_file(b)) {
+        (Ok(x), Ok(y)) => x == y,
+        _ => false,
+    }
+}
+
+// Executor
+
+/// How a plan is executed
+#[derive(Default)]
+pub struct ExecuteOptions<'a> {
+    /// Only report what would happen
+    pub dry_run: bool,
+    /// Journal that records real moves for undo
+    pub journal: Option<&'a Journal>,
+    /// Move the files, or copy/link them and leave the originals in place
+    pub transfer: TransferMode,
+    /// Reports copy progress when a large file has to be copied across filesystems
+    pub progress: Option<ProgressFn>,
+    /// Called with every outcome as soon as it happens, e.g. to stream events
+    pub on_outcome: Option<OutcomeFn>,
+}
+
+/// Receives outcomes while a plan runs; called from worker threads
+pub type OutcomeFn = fn(&Outcome);
+
+/// What happened to a single action. Serialized as `{"event": "moved", ...}` with
+/// these field names, which scripts rely on.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Outcome {
+    DirCreated {
+        path: PathBuf,
+    },
+    Moved {
+        source: PathBuf,
+        destination: PathBuf,
+        group: String,
+    },
+    /// Moved under a new name because the destination was taken
+    Renamed {
+        source: PathBuf,
+        destination: PathBuf,
+        group: String,
+    },
+    /// Moved and replaced the file that was at the destination
+    Overwritten {
+        source: PathBuf,
+        destination: PathBuf,
+        group: String,
+    },
+    /// Deleted because an identical file already exists
+    DeletedDuplicate {
+        source: PathBuf,
+        duplicate_of: PathBuf,
+    },
+    Skipped {
+        source: PathBuf,
+        reason: SkipReason,
+        parked: Option<PathBuf>,
+    },
+    Failed {
+        path: PathBuf,
+        error: KondoError,
+    },
+}
+
+impl Outcome {
+    /// One-line description, identical wording for every mode
+    pub fn describe(&self, dry_run: bool, transfer: TransferMode) -> String {
+        let prefix = if dry_run { "[DRY RUN] Would " } else { "" };
+        match self {
+            Outcome::DirCreated { path } => format!(
+                "{}{} {}",
+                prefix,
+                if dry_run { "create:" } else { "Created:" },
+                path.display()
+            ),
+            Outcome::Moved {
+                source,
+                destination,
+                ..
+            }
+            | Outcome::Renamed {
+                source,
+                destination,
+                ..
+            }
+            | Outcome::Overwritten {
+                source,
+                destination,
+                ..
+            } => {
+                let verb = if dry_run {
+                    format!("{}:", transfer.verb())
+                } else {
+                    format!("{}:", transfer.past_tense())
+                };
+                let note = match self {
+                    Outcome::Renamed { .. } => " (renamed, destination taken)",
+                    Outcome::Overwritten { .. } => " (existing file moved to kondo-trash)",
+                    _ => "",
+                };
+                format!(
+                    "{}{} {} → {}{}",
+                    prefix,
+                    verb,
+                    source.display(),
+                    destination.display(),
+                    note
+                )
+            }
+            Outcome::DeletedDuplicate {
+                source,
+                duplicate_of,
+            } => format!(
+                "{}{} {} (identical to {})",
+                prefix,
+                if dry_run { "delete duplicate:" } else { "Deleted duplicate:" },
+                source.display(),
+                duplicate_of.display()
+            ),
+            Outcome::Skipped {
+                source,
+                reason,
+                parked,
+            } => match parked {
+                Some(dest) => format!(
+                    "Skipped ({}): {} → {}",
+                    reason.describe(),
+                    source.display(),
+                    dest.display()
+                ),
+                None => format!("Skipped ({}): {}", reason.describe(), source.display()),
+            },
+            Outcome::Failed { path, error } => format!("Failed: {}: {}", path.display(), error),
+        }
+    }
+}
+
+/// Result of executing (or dry-running) a plan
+#[derive(Debug, Default)]
+pub struct ExecutionReport {
+    pub dry_run: bool,
+    pub transfer: TransferMode,
+    pub outcomes: Vec<Outcome>,
+    /// Problems that did not stop an action, e.g. a journal write failure
+    pub warnings: Vec<String>,
+}
+
+impl ExecutionReport {
+    pub fn dirs_created(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::DirCreated { .. }))
+            .count()
+    }
+
+    /// Files that reached a destination, including renamed and overwriting ones
+    pub fn files_moved(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| {
+                matches!(
+                    o,
+                    Outcome::Moved { .. } | Outcome::Renamed { .. } | Outcome::Overwritten { .. }
+                )
+            })
+            .count()
+    }
+
+    pub fn files_renamed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::Renamed { .. }))
+            .count()
+    }
+
+    pub fn files_overwritten(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::Overwritten { .. }))
+            .count()
+    }
+
+    pub fn duplicates_deleted(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::DeletedDuplicate { .. }))
+            .count()
+    }
+
+    pub fn files_skipped(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::Skipped { .. }))
+            .count()
+    }
+
+    pub fn files_failed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|o| matches!(o, Outcome::Failed { .. }))
+            .count()
+    }
+
+    /// Number of moved files per group label
+    pub fn group_counts(&self) -> HashMap<String, usize> {
+        let mut counts = HashMap::new();
+        for outcome in &self.outcomes {
+            if let Outcome::Moved { group, .. }
+            | Outcome::Renamed { group, .. }
+            | Outcome::Overwritten { group, .. } = outcome
+            {
+                *counts.entry(group.clone()).or_insert(0) += 1;
+            }
+        }
+        counts
+    }
+
+    /// Files left where they were, and why
+    pub fn skipped_details(&self) -> Vec<SkippedFile> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| match o {
+                Outcome::Skipped { source, reason, .. } => Some(SkippedFile {
+                    filename: source
+                        .file_name()
+                        .map(|n| n.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                    path: source.clone(),
+                    reason: *reason,
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Files that failed, and why
+    pub fn errors(&self) -> Vec<FileError> {
+        self.outcomes
+            .iter()
+            .filter_map(|o| match o {
+                Outcome::Failed { path, error } => Some(FileError {
+                    path: path.clone(),
+                    error: error.clone(),
+                }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Human readable log of every outcome
+    pub fn log_lines(&self) -> Vec<String> {
+        self.outcomes
+            .iter()
+            .map(|o| o.describe(self.dry_run, self.transfer))
+            .collect()
+    }
+}
+
+/// Applies a plan. Directories are created first, then files are moved in parallel,
+/// and duplicates are deleted last once the copies they match are in place.
+/// A dry run walks the exact same actions without touching the filesystem.
+pub fn execute(plan: &Plan, options: &ExecuteOptions) -> ExecutionReport {
+    let conflict = plan.conflict_options();
+    let trash = plan.base_path.join(TRASH_DIR);
+    let warnings = Mutex::new(Vec::new());
+    let mut outcomes = Vec::new();
+
+    for action in &plan.actions {
+        if let Action::CreateDir { path } = action {
+            outcomes.extend(create_dir(path, options, &warnings).into_iter().map(|o| notify(o, options)));
+        }
+    }
+
+    let file_outcomes: Vec<Outcome> = plan
+        .actions
+        .par_iter()
+        .filter_map(|action| match action {
+            Action::CreateDir { .. } | Action::DeleteDuplicate { .. } => None,
+            Action::Move {
+                source,
+                destination,
+                group,
+                size,
+                modified_ms,
+                conflict: planned,
+                ..
+            } => {
+                let overwrite = (*planned == Some(Conflict::Overwrite)).then_some(trash.as_path());
+                let expected = (*size, *modified_ms);
+                let placed = move_file(source, destination, expected, overwrite, &conflict, options, &warnings);
+                let (source, group) = (source.clone(), group.clone());
+                Some(match placed {
+                    Ok(Placed::At(path)) if path != *destination || *planned == Some(Conflict::Renamed) => {
+                        Outcome::Renamed {
+                            source,
+                            destination: path,
+                            group,
+                        }
+                    }
+                    Ok(Placed::At(path)) => Outcome::Moved {
+                        source,
+                        destination: path,
+                        group,
+                    },
+                    Ok(Placed::Replaced) => Outcome::Overwritten {
+                        source,
+                        destination: destination.clone(),
+                        group,
+                    },
+                    Ok(Placed::Blocked) => Outcome::Skipped {
+                        source,
+                        reason: SkipReason::DestinationExists,
+                        parked: None,
+                    },
+                    Err(error) => Outcome::Failed {
+                        path: source,
+                        error,
+                    },
+                })
+            }
+            Action::Skip {
+                source,
+                reason,
+                destination,
+                size,
+                modified_ms,
+            } => Some(match destination {
+                Some(dest) => match move_file(source, dest, (*size, *modified_ms), None, &conflict, options, &warnings) {
+                    Ok(placed) => Outcome::Skipped {
+                        source: source.clone(),
+                        reason: *reason,
+                        parked: match placed {
+                            Placed::At(path) => Some(path),
+                            _ => None,
+                        },
+                    },
+                    Err(error) => Outcome::Failed {
+                        path: source.clone(),
+                        error,
+                    },
+                },
+                None => Outcome::Skipped {
+                    source: source.clone(),
+                    reason: *reason,
+                    parked: None,
+                },
+            }),
+        })
+        .map(|outcome| notify(outcome, options))
+        .collect();
+
+    outcomes.extend(file_outcomes);
+
+    for action in &plan.actions {
+        if let Action::DeleteDuplicate {
+            source,
+            duplicate_of,
+            size,
+            modified_ms,
+        } = action
+        {
+            let expected = (*size, *modified_ms);
+            let outcome = delete_duplicate(source, duplicate_of, expected, options, &warnings);
+            outcomes.push(notify(outcome, options));
+        }
+    }
+
+    ExecutionReport {
+        dry_run: options.dry_run,
+        transfer: options.transfer,
+        outcomes,
+        warnings: warnings.into_inner().unwrap_or_default(),
+    }
+}
+
+fn notify(outcome: Outcome, options: &ExecuteOptions) -> Outcome {
+    if let Some(on_outcome) = options.on_outcome {
+        on_outcome(&outcome);
+    }
+    outcome
+}
+
+/// Journal failures never abort a run, they are reported as warnings
+fn record(result: io::Result<()>, warnings: &Mutex<Vec<String>>) {
+    if let Err(e) = result {
+        if let Ok(mut w) = warnings.lock() {
+            w.push(format!("Failed to write journal entry: {}", e));
+        }
+    }
+}
+
+fn create_dir(path: &Path, options: &ExecuteOptions, warnings: &Mutex<Vec<String>>) -> Vec<Outcome> {
+    if options.dry_run {
+        return vec![Outcome::DirCreated {
+            path: path.to_path_buf(),
+        }];
+    }
+
+    match journal::create_dirs(path) {
+        Ok(created) => created
+            .into_iter()
+            .map(|dir| {
+                if let Some(journal) = options.journal {
+                    record(journal.record_dir(&dir), warnings);
+                }
+                Outcome::DirCreated { path: dir }
+            })
+            .collect(),
+        Err(e) => vec![Outcome::Failed {
+            path: path.to_path_buf(),
+            error: e.into(),
+        }],
+    }
+}
+
+/// Free names tried when destinations keep being taken while a plan runs
+const MAX_PLACEMENT_ATTEMPTS: usize = 100;
+
+/// Where `move_file` put a file
+enum Placed {
+    /// At the planned destination, or at a free name if that was taken in the meantime
+    At(PathBuf),
+    /// Over the existing file at the planned destination
+    Replaced,
+    /// Nowhere: the destination was taken in the meantime and the policy keeps existing files
+    Blocked,
+}
+
+/// Moves (or copies/links) one file. An existing file is only replaced when the plan
+/// chose to overwrite it, and is then moved into the `overwrite` trash folder. Every
+/// other transfer refuses to clobber atomically, so when another thread or process
+/// takes the destination first the file is renamed again (or left alone, depending on
+/// the policy) instead of overwriting anything.
+fn move_file(
+    source: &Path,
+    destination: &Path,
+    expected: Fingerprint,
+    overwrite: Option<&Path>,
+    conflict: &ConflictOptions,
+    options: &ExecuteOptions,
+    warnings: &Mutex<Vec<String>>,
+) -> Result<Placed, KondoError> {
+    check_source(source, expected)?;
+
+    let exists = |path: &Path| fs::symlink_metadata(path).is_ok();
+    if let Some(trash) = overwrite.filter(|_| exists(destination)) {
+        if destination.is_dir() {
+            return Err(KondoError::DestinationIsDir(destination.to_path_buf()));
+        }
+        if !options.dry_run {
+            replace_existing(source, destination, trash, conflict, options, warnings)?;
+        }
+        return Ok(Placed::Replaced);
+    }
+
+    let renames = matches!(
+        conflict.policy,
+        ConflictPolicy::Rename | ConflictPolicy::DeleteIfIdentical
+    );
+    let mut target = destination.to_path_buf();
+
+    for _ in 0..MAX_PLACEMENT_ATTEMPTS {
+        let placed = if options.dry_run {
+            match exists(&target) {
+                true => Err(io::Error::from(io::ErrorKind::AlreadyExists)),
+                false => Ok(()),
+            }
+        } else {
+            transfer::transfer_file(source, &target, options.transfer, options.progress)
+        };
+
+        match placed {
+            Ok(()) => {
+                if let Some(journal) = options.journal.filter(|_| !options.dry_run) {
+                    record(journal.record_transfer(source, &target, options.transfer), warnings);
+                }
+                return Ok(Placed::At(target));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if !renames {
+                    return Ok(Placed::Blocked);
+                }
+                target = conflict
+                    .free_name(destination, &exists)
+                    .ok_or_else(|| KondoError::ConflictExhausted(destination.to_path_buf()))?;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    // Other processes kept taking every name that was tried
+    Err(KondoError::ConflictExhausted(destination.to_path_buf()))
+}
+
+/// Puts `source` in place of the file at `destination`. The existing file is moved into
+/// `trash` under its path relative to the base directory rather than deleted, and the
+/// journal records where, so undo can put it back.
+fn replace_existing(
+    source: &Path,
+    destination: &Path,
+    trash: &Path,
+    conflict: &ConflictOptions,
+    options: &ExecuteOptions,
+    warnings: &Mutex<Vec<String>>,
+) -> Result<(), KondoError> {
+    let base = trash.parent().unwrap_or(trash);
+    let relative = match destination.strip_prefix(base) {
+        Ok(relative) => relative,
+        Err(_) => Path::new(destination.file_name().unwrap_or_default()),
+    };
+    let wanted = trash.join(relative);
+    let aside = conflict
+        .free_name(&wanted, &|path| fs::symlink_metadata(path).is_ok())
+        .ok_or(KondoError::ConflictExhausted(wanted))?;
+
+    for dir in journal::create_dirs(aside.parent().unwrap_or(trash))? {
+        if let Some(journal) = options.journal {
+            record(journal.record_dir(&dir), warnings);
+        }
+    }
+    transfer::move_file(destination, &aside, options.progress)?;
+
+    if let Err(e) = transfer::transfer_file(source, destination, options.transfer, options.progress) {
+        // Put the existing file back, so a failure changes nothing
+        let _ = transfer::move_file(&aside, destination, None);
+        return Err(e.into());
+    }
+    if let Some(journal) = options.journal {
+        record(journal.record_set_aside(destination, &aside), warnings);
+        record(journal.record_transfer(source, destination, options.transfer), warnings);
+    }
+    Ok(())
+}
+
+fn delete_duplicate(
+    source: &Path,
+    duplicate_of: &Path,
+    expected: Fingerprint,
+    options: &ExecuteOptions,
+    warnings: &Mutex<Vec<String>>,
+) -> Outcome {
+    let failed = |error: KondoError| Outcome::Failed {
+        path: source.to_path_buf(),
+        error,
+    };
+    if let Err(error) = check_source(source, expected) {
+        return failed(error);
+    }
+
+    // Copy and link modes promise to leave the originals alone
+    if options.transfer != TransferMode::Move {
+        return Outcome::Skipped {
+            source: source.to_path_buf(),
+            reason: SkipReason::IdenticalExists,
+            parked: None,
+        };
+    }
+
+    // In a dry run the planned moves have not happened, so the copy may not be there yet
+    if !options.dry_run && !same_content(source, duplicate_of) {
+        return failed(KondoError::NotIdentical(duplicate_of.to_path_buf()));
+    }
+
+    if !options.dry_run {
+        if let Err(e) = fs::remove_file(source) {
+            return failed(e.into());
+        }
+        if let Some(journal) = options.journal {
+            record(journal.record_deleted_duplicate(source, duplicate_of), warnings);
+        }
+    }
+
+    Outcome::DeletedDuplicate {
+        source: source.to_path_buf(),
+        duplicate_of: duplicate_of.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dry_run_matches_apply() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), b"a").unwrap();
+        fs::write(dir.path().join("b.txt"), b"b").unwrap();
+
+        let mut builder = PlanBuilder::new("test", dir.path());
+        for name in ["a.txt", "b.txt"] {
+            builder.add_move(
+                dir.path().join(name),
+                dir.path().join("Docs").join(name),
+                "Docs",
+                "test".to_string(),
+                None,
+                None,
+            );
+        }
+        let plan = builder.build();
+
+        let preview = execute(
+            &plan,
+            &ExecuteOptions {
+                dry_run: true,
+                ..Default::default()
+            },
+        );
+        assert!(dir.path().join("a.txt").exists());
+
+        let applied = execute(&plan, &ExecuteOptions::default());
+        assert_eq!(preview.files_moved(), applied.files_moved());
+        assert_eq!(preview.dirs_created(), applied.dirs_created());
+        assert_eq!(applied.files_moved(), 2);
+        assert!(dir.path().join("Docs").join("b.txt").exists());
+    }
+
+    #[test]
+    fn test_reserved_destinations_are_taken() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = PlanBuilder::new("test", dir.path());
+        let dest = dir.path().join("Docs").join("a.txt");
+        assert!(!builder.is_taken(&dest));
+        builder.add_move(dir.path().join("a.txt"), dest.clone(), "Docs", String::new(), None, None);
+        assert!(builder.is_taken(&dest));
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let dir = tempfile::tempdir().unwrap();
+        let docs = dir.path().join("Docs");
+        fs::create_dir(&docs).unwrap();
+        fs::write(docs.join("a.txt"), b"existing").unwrap();
+        fs::write(dir.path().join("a.txt"), b"existing").unwrap();
+
+        let run = |policy: ConflictPolicy| {
+            let mut builder = PlanBuilder::new("test", dir.path()).with_conflict(ConflictOptions {
+                policy,
+                ..Default::default()
+            });
+            builder.add_move(
+                dir.path().join("a.txt"),
+                docs.join("a.txt"),
+                "Docs",
+                String::new(),
+                None,
+                None,
+            );
+            execute(&builder.build(), &ExecuteOptions::default())
+        };
+
+        let report = run(ConflictPolicy::KeepLarger);
+        assert!(matches!(
+            report.outcomes[0],
+            Outcome::Skipped {
+                reason: SkipReason::ExistingLarger,
+                ..
+            }
+        ));
+
+        fs::write(dir.path().join("a.txt"), b"incoming").unwrap();
+        let report = run(ConflictPolicy::DeleteIfIdentical);
+        assert_eq!(report.files_renamed(), 1);
+        assert!(docs.join("a_1.txt").exists());
+
+        fs::write(dir.path().join("a.txt"), b"existing").unwrap();
+        let report = run(ConflictPolicy::DeleteIfIdentical);
+        assert_eq!(report.duplicates_deleted(), 1);
+        assert!(!dir.path().join("a.txt").exists());
+
+        fs::write(dir.path().join("a.txt"), b"replacement").unwrap();
+        let report = run(ConflictPolicy::Overwrite);
+        assert_eq!(report.files_overwritten(), 1);
+        assert_eq!(fs::read(docs.join("a.txt")).unwrap(), b"replacement");
+        assert_eq!(fs::read(dir.path().join(TRASH_DIR).join("Docs").join("a.txt")).unwrap(), b"existing");
+    }
+
+    #[test]
+    fn test_overwritten_files_come_back_on_undo() {
+        for transfer in [TransferMode::Move, TransferMode::Copy] {
+            let dir = tempfile::tempdir().unwrap();
+            let docs = dir.path().join("Docs");
+            fs::create_dir(&docs).unwrap();
+            fs::write(docs.join("a.txt"), b"existing").unwrap();
+            fs::write(dir.path().join("a.txt"), b"incoming").unwrap();
+
+            let mut builder = PlanBuilder::new("test", dir.path()).with_conflict(ConflictOptions {
+                policy: ConflictPolicy::Overwrite,
+                ..Default::default()
+            });
+            builder.add_move(dir.path().join("a.txt"), docs.join("a.txt"), "Docs", String::new(), None, None);
+            let journal_path = dir.path().join("journal.jsonl");
+            let journal = Journal::start_run(&journal_path, "test").unwrap();
+            let options = ExecuteOptions {
+                journal: Some(&journal),
+                transfer,
+                ..Default::default()
+            };
+            assert_eq!(execute(&builder.build(), &options).files_overwritten(), 1);
+            assert_eq!(fs::read(docs.join("a.txt")).unwrap(), b"incoming");
+
+            let preview = journal::undo_run(&journal_path, None, true).unwrap();
+            assert!(preview.errors.is_empty(), "{:?}", preview.errors);
+
+            let undone = journal::undo_run(&journal_path, None, false).unwrap();
+            assert!(undone.errors.is_empty(), "{:?}", undone.errors);
+            assert_eq!(fs::read(docs.join("a.txt")).unwrap(), b"existing");
+            assert_eq!(fs::read(dir.path().join("a.txt")).unwrap(), b"incoming");
+            assert!(!dir.path().join(TRASH_DIR).exists());
+        }
+    }
+
+    #[test]
+    fn test_parallel_moves_to_one_name_lose_nothing() {
+        const FILES: usize = 2000;
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("Images");
+        fs::create_dir(&dest).unwrap();
+
+        // Every action claims the same destination, as if other processes raced for it
+        let mut plan = Plan::new("test", dir.path());
+        for i in 0..FILES {
+            let source = dir.path().join(i.to_string()).join("photo.jpg");
+            fs::create_dir(source.parent().unwrap()).unwrap();
+            fs::write(&source, i.to_string()).unwrap();
+            plan.actions.push(Action::Move {
+                source,
+                destination: dest.join("photo.jpg"),
+                group: "Images".to_string(),
+                reason: String::new(),
+                suggested_name: None,
+                score: None,
+                size: None,
+                modified_ms: None,
+                conflict: None,
+            });
+        }
+
+        let report = execute(&plan, &ExecuteOptions::default());
+        assert_eq!(report.files_moved(), FILES);
+        assert_eq!(report.files_renamed(), FILES - 1);
+
+        let contents: HashSet<String> = fs::read_dir(&dest)
+            .unwrap()
+            .map(|entry| fs::read_to_string(entry.unwrap().path()).unwrap())
+            .collect();
+        assert_eq!(contents.len(), FILES);
+    }
+
+    #[test]
+    fn test_saved_plan_round_trips_and_detects_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        fs::write(&source, b"a").unwrap();
+
+        let mut builder = PlanBuilder::new("test", dir.path());
+        builder.add_move(
+            source.clone(),
+            dir.path().join("Docs").join("a.txt"),
+            "Docs",
+            "test".to_string(),
+            Some("Docs"),
+            Some(0.8),
+        );
+        let plan = builder.build();
+
+        for name in ["plan.json", "plan.toml"] {
+            let path = dir.path().join(name);
+            plan.save(&path).unwrap();
+            let loaded = Plan::load(&path).unwrap();
+            assert_eq!(loaded.actions.len(), plan.actions.len());
+            assert!(loaded.validate().is_empty());
+        }
+
+        fs::write(&source, b"changed").unwrap();
+        let loaded = Plan::load(&dir.path().join("plan.json")).unwrap();
+        assert_eq!(loaded.validate().len(), 1);
+        assert_eq!(execute(&loaded, &ExecuteOptions::default()).files_failed(), 1);
+        assert!(source.exists());
+    }
+}
@@ -0,0 +1,353 @@
+// Ordered `[[rules]]` for categorize mode, checked before the extension map
+use globset::{GlobBuilder, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use super::plan::expand_home;
+use super::template::PathTemplate;
+
+/// One `[[rules]]` entry as written in kondo.toml. Every condition that is set must
+/// hold for the rule to match.
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RuleConfig {
+    pub name: String,
+
+    /// Shell-style pattern for the file name, e.g. "invoice*"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub glob: Option<String>,
+
+    /// Regular expression for the file name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub regex: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extensions: Vec<String>,
+
+    /// Sizes such as "500KB" or "2GB" (powers of 1024)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<String>,
+
+    /// Only files last modified longer ago than this, e.g. "30d"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_age: Option<String>,
+
+    /// Only files modified more recently than this, e.g. "12h"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_age: Option<String>,
+
+    /// MIME type guessed from the extension, e.g. "application/pdf" or "video/*"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mime: Option<String>,
+
+    /// Folder matching files go to: relative to the output root, or absolute
+    pub destination: String,
+
+    /// Optional sub-path below `destination`, using the same placeholders as categories
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_template: Option<String>,
+}
+
+/// A validated rule, ready to match files
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub destination: String,
+    pub template: Option<PathTemplate>,
+    glob: Option<(String, GlobMatcher)>,
+    regex: Option<Regex>,
+    extensions: Vec<String>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    min_age: Option<Duration>,
+    max_age: Option<Duration>,
+    mime: Option<String>,
+}
+
+/// What rules look at for one file
+pub struct FileFacts {
+    pub name: String,
+    pub extension: String,
+    pub size: Option<u64>,
+    pub age: Option<Duration>,
+    pub mime: String,
+}
+
+impl FileFacts {
+    pub fn new(path: &Path, metadata: Option<&fs::Metadata>) -> Self {
+        let modified = metadata.and_then(|m| m.modified().ok());
+        Self {
+            name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            extension: path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            size: metadata.map(|m| m.len()),
+            age: modified.and_then(|t| SystemTime::now().duration_since(t).ok()),
+            mime: mime_guess::from_path(path)
+                .first_raw()
+                .unwrap_or("application/octet-stream")
+                .to_string(),
+        }
+    }
+}
+
+impl Rule {
+    pub fn compile(config: &RuleConfig) -> Result<Self, String> {
+        let context = |e: String| format!("rule '{}': {}", config.name, e);
+
+        if config.destination.trim().is_empty() {
+            return Err(context("destination must not be empty".to_string()));
+        }
+
+        let glob = match &config.glob {
+            Some(pattern) => {
+                let matcher = GlobBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| context(format!("invalid glob '{}': {}", pattern, e)))?
+                    .compile_matcher();
+                Some((pattern.clone(), matcher))
+            }
+            None => None,
+        };
+        let regex = match &config.regex {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .map_err(|e| context(format!("invalid regex '{}': {}", pattern, e)))?,
+            ),
+            None => None,
+        };
+        let size = |value: &Option<String>| value.as_deref().map(parse_size).transpose();
+        let age = |value: &Option<String>| value.as_deref().map(parse_age).transpose();
+        let template = config
+            .path_template
+            .as_deref()
+            .map(PathTemplate::parse)
+            .transpose()
+            .map_err(|e| context(e.to_string()))?;
+
+        let rule = Rule {
+            name: config.name.clone(),
+            destination: config.destination.clone(),
+            template,
+            glob,
+            regex,
+            extensions: config
+                .extensions
+                .iter()
+                .map(|e| e.trim_start_matches('.').to_lowercase())
+                .collect(),
+            min_size: size(&config.min_size).map_err(context)?,
+            max_size: size(&config.max_size).map_err(context)?,
+            min_age: age(&config.min_age).map_err(context)?,
+            max_age: age(&config.max_age).map_err(context)?,
+            mime: config.mime.as_ref().map(|m| m.to_lowercase()),
+        };
+
+        if let (Some(min), Some(max)) = (rule.min_size, rule.max_size) {
+            if min > max {
+                return Err(context("min_size is larger than max_size".to_string()));
+            }
+        }
+        if let (Some(min), Some(max)) = (rule.min_age, rule.max_age) {
+            if min > max {
+                return Err(context("min_age is larger than max_age".to_string()));
+            }
+        }
+
+        Ok(rule)
+    }
+
+    /// Folder the rule sends files to, before any `path_template`
+    pub fn destination_dir(&self, output_root: &Path) -> PathBuf {
+        output_root.join(expand_home(&self.destination))
+    }
+
+    /// Ok if every condition holds, otherwise the first one that does not
+    pub fn check(&self, facts: &FileFacts) -> Result<(), String> {
+        if let Some((pattern, matcher)) = &self.glob {
+            if !matcher.is_match(&facts.name) {
+                return Err(format!("name does not match glob '{}'", pattern));
+            }
+        }
+        if let Some(regex) = &self.regex {
+            if !regex.is_match(&facts.name) {
+                return Err(format!("name does not match regex '{}'", regex));
+            }
+        }
+        if !self.extensions.is_empty() && !self.extensions.contains(&facts.extension) {
+            return Err(format!(
+                "extension '{}' is not one of {}",
+                facts.extension,
+                self.extensions.join(", ")
+            ));
+        }
+        if self.min_size.is_some() || self.max_size.is_some() {
+            let size = facts.size.ok_or("size is unknown")?;
+            if self.min_size.is_some_and(|min| size < min) {
+                return Err(format!("size {} bytes is below min_size", size));
+            }
+            if self.max_size.is_some_and(|max| size > max) {
+                return Err(format!("size {} bytes is above max_size", size));
+            }
+        }
+        if self.min_age.is_some() || self.max_age.is_some() {
+            let age = facts.age.ok_or("modification time is unknown")?;
+            if self.min_age.is_some_and(|min| age < min) {
+                return Err("modified more recently than min_age".to_string());
+            }
+            if self.max_age.is_some_and(|max| age > max) {
+                return Err("modified longer ago than max_age".to_string());
+            }
+        }
+        if let Some(mime) = &self.mime {
+            let matches = match mime.strip_suffix("/*") {
+                Some(kind) => facts.mime.split('/').next() == Some(kind),
+                None => facts.mime == *mime,
+            };
+            if !matches {
+                return Err(format!("MIME type {} is not {}", facts.mime, mime));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Compiles rules in order, reporting the first invalid one
+pub fn compile_rules(configs: &[RuleConfig]) -> Result<Vec<Rule>, String> {
+    configs.iter().map(Rule::compile).collect()
+}
+
+/// The first rule that matches wins
+pub fn first_match<'a>(rules: &'a [Rule], facts: &FileFacts) -> Option<&'a Rule> {
+    rules.iter().find(|rule| rule.check(facts).is_ok())
+}
+
+/// Parses "2GB", "500 KiB" or a plain byte count
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}'", value))?;
+
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => return Err(format!("invalid size unit in '{}' (use B, KB, MB, GB or TB)", value)),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Parses "30d", "12h", "2w" and similar into a duration
+pub fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid age '{}'", value))?;
+
+    let seconds: u64 = match unit.trim().to_lowercase().as_str() {
+        "s" => 1,
+        "m" | "min" => 60,
+        "h" => 60 * 60,
+        "d" | "" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        "y" => 365 * 24 * 60 * 60,
+        _ => return Err(format!("invalid age unit in '{}' (use s, m, h, d, w or y)", value)),
+    };
+    number
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or_else(|| format!("invalid age '{}': too long", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn facts(name: &str, size: u64, mime: &str) -> FileFacts {
+        FileFacts {
+            name: name.to_string(),
+            extension: Path::new(name)
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            size: Some(size),
+            age: Some(Duration::from_secs(3600)),
+            mime: mime.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = compile_rules(&[
+            RuleConfig {
+                name: "Invoices".to_string(),
+                glob: Some("invoice*".to_string()),
+                extensions: vec!["pdf".to_string()],
+                destination: "Finance".to_string(),
+                ..Default::default()
+            },
+            RuleConfig {
+                name: "Large videos".to_string(),
+                mime: Some("video/*".to_string()),
+                min_size: Some("2GB".to_string()),
+                destination: "Large".to_string(),
+                ..Default::default()
+            },
+            RuleConfig {
+                name: "Recent".to_string(),
+                max_age: Some("1d".to_string()),
+                destination: "Recent".to_string(),
+                ..Default::default()
+            },
+        ])
+        .unwrap();
+
+        let name = |f: &FileFacts| first_match(&rules, f).map(|r| r.name.clone());
+        assert_eq!(name(&facts("Invoice-2024.PDF", 10, "application/pdf")).as_deref(), Some("Invoices"));
+        assert_eq!(name(&facts("movie.mkv", 3 << 30, "video/x-matroska")).as_deref(), Some("Large videos"));
+        assert_eq!(name(&facts("clip.mkv", 1 << 20, "video/x-matroska")).as_deref(), Some("Recent"));
+
+        let rule = &rules[0];
+        assert!(rule
+            .check(&facts("receipt.pdf", 10, "application/pdf"))
+            .unwrap_err()
+            .contains("glob"));
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        let rule = |min_size: &str| RuleConfig {
+            name: "r".to_string(),
+            min_size: Some(min_size.to_string()),
+            destination: "Out".to_string(),
+            ..Default::default()
+        };
+        assert!(compile_rules(&[rule("10 parsecs")]).is_err());
+        assert!(compile_rules(&[rule("1.5 MB")]).is_ok());
+        assert_eq!(parse_size("2GB").unwrap(), 2 << 30);
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert!(parse_age("99999999999999y").is_err());
+    }
+}
@@ -0,0 +1,210 @@
+// Detects common file formats from their first bytes
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Bytes read from the start of a file; enough for every signature below
+const HEADER_LEN: usize = 4096;
+
+/// A format recognised by its signature
+#[derive(Debug, PartialEq, Eq)]
+pub struct Format {
+    pub name: &'static str,
+    pub mime: &'static str,
+    /// Extensions the format is stored under, preferred one first
+    pub extensions: &'static [&'static str],
+}
+
+impl Format {
+    /// Whether `extension` (lowercase, no dot) is a normal name for this format
+    pub fn accepts(&self, extension: &str) -> bool {
+        self.extensions.contains(&extension)
+    }
+}
+
+const fn format(
+    name: &'static str,
+    mime: &'static str,
+    extensions: &'static [&'static str],
+) -> Format {
+    Format {
+        name,
+        mime,
+        extensions,
+    }
+}
+
+const PNG: Format = format("PNG image", "image/png", &["png"]);
+const JPEG: Format = format("JPEG image", "image/jpeg", &["jpg", "jpeg", "jpe", "jfif"]);
+const GIF: Format = format("GIF image", "image/gif", &["gif"]);
+const WEBP: Format = format("WebP image", "image/webp", &["webp"]);
+const TIFF: Format = format(
+    "TIFF image",
+    "image/tiff",
+    &["tiff", "tif", "cr2", "nef", "orf", "sr2", "dng", "arw"],
+);
+const PSD: Format = format("Photoshop document", "image/vnd.adobe.photoshop", &["psd"]);
+const PDF: Format = format("PDF document", "application/pdf", &["pdf", "ai"]);
+const ZIP: Format = format(
+    "ZIP archive",
+    "application/zip",
+    &[
+        "zip", "jar", "apk", "epub", "cbz", "odt", "ods", "odp", "docx", "xlsx", "pptx", "xpi",
+        "whl", "sketch", "fig",
+    ],
+);
+const DOCX: Format = format(
+    "Word document",
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+    &["docx", "docm", "dotx"],
+);
+const XLSX: Format = format(
+    "Excel spreadsheet",
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+    &["xlsx", "xlsm", "xltx"],
+);
+const PPTX: Format = format(
+    "PowerPoint presentation",
+    "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+    &["pptx", "pptm", "potx"],
+);
+const GZIP: Format = format("gzip archive", "application/gzip", &["gz", "tgz"]);
+const BZIP2: Format = format("bzip2 archive", "application/x-bzip2", &["bz2", "tbz2"]);
+const XZ: Format = format("xz archive", "application/x-xz", &["xz", "txz"]);
+const SEVEN_ZIP: Format = format("7-Zip archive", "application/x-7z-compressed", &["7z"]);
+const RAR: Format = format("RAR archive", "application/vnd.rar", &["rar", "cbr"]);
+const ELF: Format = format(
+    "ELF executable",
+    "application/x-executable",
+    &["run", "appimage", "so", "o", "elf", "bin"],
+);
+const WINDOWS_EXE: Format = format(
+    "Windows executable",
+    "application/vnd.microsoft.portable-executable",
+    &["exe", "dll", "sys", "scr"],
+);
+const MP4: Format = format(
+    "MPEG-4 media",
+    "video/mp4",
+    &["mp4", "m4v", "m4a", "mov", "3gp", "heic", "avif"],
+);
+const MATROSKA: Format = format("Matroska video", "video/x-matroska", &["mkv", "webm"]);
+const AVI: Format = format("AVI video", "video/x-msvideo", &["avi"]);
+const MP3: Format = format("MP3 audio", "audio/mpeg", &["mp3"]);
+const FLAC: Format = format("FLAC audio", "audio/flac", &["flac"]);
+const OGG: Format = format("Ogg media", "audio/ogg", &["ogg", "oga", "ogv", "opus"]);
+const WAV: Format = format("WAV audio", "audio/wav", &["wav"]);
+const SQLITE: Format = format(
+    "SQLite database",
+    "application/vnd.sqlite3",
+    &["sqlite", "db", "sqlite3"],
+);
+
+/// Reads the start of `path` and identifies its format, if it is one we know
+pub fn sniff_file(path: &Path) -> io::Result<Option<&'static Format>> {
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    File::open(path)?
+        .take(HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(sniff(&header))
+}
+
+/// Identifies a format from the first bytes of a file
+pub fn sniff(header: &[u8]) -> Option<&'static Format> {
+    let at = |offset: usize, magic: &[u8]| header.get(offset..offset + magic.len()) == Some(magic);
+
+    let format = if at(0, b"\x89PNG\r\n\x1a\n") {
+        &PNG
+    } else if at(0, b"\xff\xd8\xff") {
+        &JPEG
+    } else if at(0, b"GIF87a") || at(0, b"GIF89a") {
+        &GIF
+    } else if at(0, b"RIFF") && at(8, b"WEBP") {
+        &WEBP
+    } else if at(0, b"RIFF") && at(8, b"WAVE") {
+        &WAV
+    } else if at(0, b"RIFF") && at(8, b"AVI ") {
+        &AVI
+    } else if at(0, b"II*\0") || at(0, b"MM\0*") {
+        &TIFF
+    } else if at(0, b"8BPS") {
+        &PSD
+    } else if at(0, b"%PDF-") {
+        &PDF
+    } else if at(0, b"PK\x03\x04") {
+        sniff_zip(header)
+    } else if at(0, b"\x1f\x8b") {
+        &GZIP
+    } else if at(0, b"BZh") {
+        &BZIP2
+    } else if at(0, b"\xfd7zXZ\0") {
+        &XZ
+    } else if at(0, b"7z\xbc\xaf\x27\x1c") {
+        &SEVEN_ZIP
+    } else if at(0, b"Rar!\x1a\x07") {
+        &RAR
+    } else if at(0, b"\x7fELF") {
+        &ELF
+    } else if at(0, b"MZ") && is_pe(header) {
+        &WINDOWS_EXE
+    } else if at(4, b"ftyp") {
+        &MP4
+    } else if at(0, b"\x1a\x45\xdf\xa3") {
+        &MATROSKA
+    } else if at(0, b"ID3") || (header.len() >= 2 && header[0] == 0xff && header[1] & 0xe6 == 0xe2) {
+        &MP3
+    } else if at(0, b"fLaC") {
+        &FLAC
+    } else if at(0, b"OggS") {
+        &OGG
+    } else if at(0, b"SQLite format 3\0") {
+        &SQLITE
+    } else {
+        return None;
+    };
+
+    Some(format)
+}
+
+/// Office Open XML files are ZIP archives with a telltale folder inside
+fn sniff_zip(header: &[u8]) -> &'static Format {
+    let contains = |needle: &[u8]| header.windows(needle.len()).any(|w| w == needle);
+
+    if contains(b"word/") {
+        &DOCX
+    } else if contains(b"xl/") {
+        &XLSX
+    } else if contains(b"ppt/") {
+        &PPTX
+    } else {
+        &ZIP
+    }
+}
+
+/// "MZ" alone is too common; require the PE header it points to
+fn is_pe(header: &[u8]) -> bool {
+    let offset = match header.get(0x3c..0x40) {
+        Some(bytes) => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize,
+        None => return false,
+    };
+    header.get(offset..offset + 4) == Some(b"PE\0\0")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_common_signatures() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Some(&PNG));
+        assert_eq!(sniff(b"%PDF-1.7\n"), Some(&PDF));
+        assert_eq!(sniff(b"\0\0\0\x18ftypmp42"), Some(&MP4));
+        assert_eq!(sniff(b"PK\x03\x04\x14\0\0\0\0\0word/document.xml"), Some(&DOCX));
+        assert_eq!(sniff(b"\x7fELF\x02\x01\x01"), Some(&ELF));
+        assert_eq!(sniff(b"ID3\x04\0"), Some(&MP3));
+        assert_eq!(sniff(b"MZ just some text"), None);
+        assert_eq!(sniff(b"hello world"), None);
+        assert!(JPEG.accepts("jpeg"));
+        assert!(!PDF.accepts("jpg"));
+    }
+}
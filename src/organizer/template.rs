@@ -0,0 +1,430 @@
+// Destination path templates such as "{category}/{year}/{month}"
+use chrono::{DateTime, Datelike, Local};
+use std::fmt;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+use super::audio::{self, AudioTags};
+use super::media::{self, MediaInfo};
+
+/// Placeholders a template may use, with what they expand to
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("category", "category key, e.g. images"),
+    ("folder", "category folder name, e.g. Images"),
+    ("ext", "lowercase extension without the dot"),
+    ("year", "year the file was last modified"),
+    ("month", "month the file was last modified (01-12)"),
+    ("day", "day the file was last modified (01-31)"),
+    ("created_year", "year the file was created"),
+    ("created_month", "month the file was created (01-12)"),
+    ("created_day", "day the file was created (01-31)"),
+    ("size", "size bucket: tiny, small, medium, large or huge"),
+    ("initial", "first letter of the file name, uppercased (# for anything else)"),
+    ("parent", "name of the folder the file is in"),
+    ("camera", "camera make and model from EXIF"),
+    ("exif_year", "year the photo or video was taken, else the modified year"),
+    ("exif_month", "month the photo or video was taken (01-12), else the modified month"),
+    ("exif_day", "day the photo or video was taken (01-31), else the modified day"),
+    ("gps", "\"gps\" if the photo records a location, otherwise \"no-gps\""),
+    ("artist", "track artist from the audio tags, else the album artist or \"Unknown Artist\""),
+    ("album_artist", "album artist from the audio tags, else the track artist or \"Unknown Artist\""),
+    ("album", "album from the audio tags, else \"Unknown Album\""),
+    ("title", "title from the audio tags, else the file name without extension"),
+    ("track", "track number from the audio tags, else 0"),
+    ("disc", "disc number from the audio tags, else 1"),
+    ("genre", "genre from the audio tags, else \"Unknown Genre\""),
+];
+
+/// Placeholders that need the file's EXIF or movie header to be read
+const MEDIA_PLACEHOLDERS: &[&str] = &["camera", "exif_year", "exif_month", "exif_day", "gps"];
+
+/// Placeholders that need the file's audio tags to be read
+const AUDIO_PLACEHOLDERS: &[&str] = &["artist", "album_artist", "album", "title", "track", "disc", "genre"];
+
+const UNKNOWN_ARTIST: &str = "Unknown Artist";
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Literal(String),
+    /// A placeholder, with the width numbers are zero-padded to (`{track:02}`)
+    Placeholder { name: String, width: Option<usize> },
+}
+
+/// A parsed `path_template`, relative to the folder the category is sorted into. A
+/// template whose last part ends in `.{ext}`, such as `{track:02} - {title}.{ext}`,
+/// names the file as well as its folder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTemplate {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateError(String);
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl TemplateError {
+    pub fn new(message: String) -> Self {
+        Self(message)
+    }
+}
+
+/// Everything a template can refer to about one file
+pub struct TemplateContext<'a> {
+    pub path: &'a Path,
+    pub category: &'a str,
+    pub folder: &'a str,
+    pub extension: &'a str,
+    pub metadata: Option<&'a fs::Metadata>,
+}
+
+impl PathTemplate {
+    /// Parses a template; unknown placeholders, absolute paths and `..` are errors
+    pub fn parse(template: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                segments.push(Segment::Literal(rest[..start].to_string()));
+            }
+            let end = rest[start..].find('}').ok_or_else(|| {
+                TemplateError(format!("unclosed '{{' in path template '{}'", template))
+            })?;
+            let (name, width) = match rest[start + 1..start + end].split_once(':') {
+                Some((name, spec)) => (name, Some(parse_width(name, spec, template)?)),
+                None => (&rest[start + 1..start + end], None),
+            };
+            if !PLACEHOLDERS.iter().any(|(known, _)| *known == name) {
+                return Err(TemplateError(format!(
+                    "unknown placeholder {{{}}} in path template '{}' (expected one of: {})",
+                    name,
+                    template,
+                    PLACEHOLDERS
+                        .iter()
+                        .map(|(known, _)| *known)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )));
+            }
+            segments.push(Segment::Placeholder {
+                name: name.to_string(),
+                width,
+            });
+            rest = &rest[start + end + 1..];
+        }
+        if rest.contains('}') {
+            return Err(TemplateError(format!(
+                "unmatched '}}' in path template '{}'",
+                template
+            )));
+        }
+        if !rest.is_empty() {
+            segments.push(Segment::Literal(rest.to_string()));
+        }
+
+        let literal: String = segments
+            .iter()
+            .filter_map(|s| match s {
+                Segment::Literal(text) => Some(text.as_str()),
+                Segment::Placeholder { .. } => None,
+            })
+            .collect();
+        let escapes = Path::new(template).is_absolute()
+            || template.starts_with(['/', '\\'])
+            || Path::new(&literal)
+                .components()
+                .any(|c| matches!(c, Component::ParentDir));
+        if escapes {
+            return Err(TemplateError(format!(
+                "path template '{}' must stay inside the category folder",
+                template
+            )));
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// Leading folders that contain no placeholders, e.g. "Photos" for "Photos/{year}"
+    pub fn static_prefix(&self) -> PathBuf {
+        let text = match self.segments.first() {
+            Some(Segment::Literal(text)) if self.segments.len() == 1 => text.as_str(),
+            Some(Segment::Literal(text)) => text.rsplit_once(['/', '\\']).map_or("", |(dir, _)| dir),
+            _ => "",
+        };
+        PathBuf::from(text)
+    }
+
+    /// Whether the template ends in `.{ext}` and so gives files a new name
+    fn names_file(&self) -> bool {
+        match self.segments.as_slice() {
+            [.., Segment::Literal(text), Segment::Placeholder { name, .. }] => {
+                name == "ext" && text.ends_with('.')
+            }
+            _ => false,
+        }
+    }
+
+    /// Where one file goes, relative to the template root: the expanded folder plus
+    /// the file's own name, or the expanded path when the template names the file
+    pub fn destination(&self, context: &TemplateContext) -> PathBuf {
+        let expanded = self.expand(context);
+        if self.names_file() {
+            expanded
+        } else {
+            expanded.join(context.path.file_name().unwrap_or_default())
+        }
+    }
+
+    /// Expands the template for one file. Placeholder values never add path
+    /// separators, and ones that come out empty become "unknown". Photo metadata and
+    /// audio tags are only read when a placeholder needs them.
+    pub fn expand(&self, context: &TemplateContext) -> PathBuf {
+        let uses = |names: &[&str]| {
+            self.segments.iter().any(|s| {
+                matches!(s, Segment::Placeholder { name, .. } if names.contains(&name.as_str()))
+            })
+        };
+        let media = uses(MEDIA_PLACEHOLDERS)
+            .then(|| media::read_media_info(context.path))
+            .flatten();
+        let audio = uses(AUDIO_PLACEHOLDERS)
+            .then(|| audio::read_audio_tags(context.path))
+            .flatten();
+
+        let expanded: String = self
+            .segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.clone(),
+                Segment::Placeholder { name, width } => {
+                    let value = sanitize(&context.value(name, media.as_ref(), audio.as_ref()));
+                    match width {
+                        Some(width) if value.chars().all(|c| c.is_ascii_digit()) => {
+                            format!("{:0>width$}", value, width = width)
+                        }
+                        _ => value,
+                    }
+                }
+            })
+            .collect();
+
+        expanded
+            .split(['/', '\\'])
+            .filter(|part| !part.is_empty() && *part != ".")
+            .collect()
+    }
+}
+
+impl TemplateContext<'_> {
+    fn value(&self, name: &str, media: Option<&MediaInfo>, audio: Option<&AudioTags>) -> String {
+        let modified = self.metadata.and_then(|m| m.modified().ok());
+        let taken = media
+            .and_then(|m| m.captured)
+            .or_else(|| modified.map(|t| DateTime::<Local>::from(t).naive_local()));
+        // Not every filesystem records creation time
+        let created = self
+            .metadata
+            .and_then(|m| m.created().ok())
+            .or(modified);
+
+        match name {
+            "category" => self.category.to_string(),
+            "folder" => self.folder.to_string(),
+            "ext" => self.extension.to_string(),
+            "year" => date_part(modified, |d| format!("{:04}", d.year())),
+            "month" => date_part(modified, |d| format!("{:02}", d.month())),
+            "day" => date_part(modified, |d| format!("{:02}", d.day())),
+            "created_year" => date_part(created, |d| format!("{:04}", d.year())),
+            "created_month" => date_part(created, |d| format!("{:02}", d.month())),
+            "created_day" => date_part(created, |d| format!("{:02}", d.day())),
+            "size" => self
+                .metadata
+                .map(|m| size_bucket(m.len()).to_string())
+                .unwrap_or_default(),
+            "initial" => self
+                .path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .and_then(|n| n.chars().next())
+                .map(|c| {
+                    if c.is_alphanumeric() {
+                        c.to_uppercase().to_string()
+                    } else {
+                        "#".to_string()
+                    }
+                })
+                .unwrap_or_default(),
+            "camera" => media.and_then(|m| m.camera.clone()).unwrap_or_default(),
+            "exif_year" => taken.map(|d| format!("{:04}", d.year())).unwrap_or_default(),
+            "exif_month" => taken.map(|d| format!("{:02}", d.month())).unwrap_or_default(),
+            "exif_day" => taken.map(|d| format!("{:02}", d.day())).unwrap_or_default(),
+            "gps" => match media.is_some_and(|m| m.has_gps) {
+                true => "gps".to_string(),
+                false => "no-gps".to_string(),
+            },
+            "artist" => tag_or(audio.and_then(|a| a.artist.as_ref().or(a.album_artist.as_ref())), UNKNOWN_ARTIST),
+            "album_artist" => tag_or(audio.and_then(|a| a.album_artist.as_ref().or(a.artist.as_ref())), UNKNOWN_ARTIST),
+            "album" => tag_or(audio.and_then(|a| a.album.as_ref()), "Unknown Album"),
+            "title" => match audio.and_then(|a| a.title.as_ref()).map(|t| sanitize_tag(t)) {
+                Some(title) if !title.is_empty() => title,
+                _ => self
+                    .path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            },
+            "track" => audio.and_then(|a| a.track).unwrap_or(0).to_string(),
+            "disc" => audio.and_then(|a| a.disc).unwrap_or(1).to_string(),
+            "genre" => tag_or(audio.and_then(|a| a.genre.as_ref()), "Unknown Genre"),
+            "parent" => self
+                .path
+                .parent()
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+}
+
+/// A cleaned tag value, or `fallback` when the tag is missing or nothing is left of it
+fn tag_or(value: Option<&String>, fallback: &str) -> String {
+    value
+        .map(|v| sanitize_tag(v))
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| fallback.to_string())
+}
+
+fn date_part(time: Option<SystemTime>, format: impl Fn(DateTime<Local>) -> String) -> String {
+    time.map(|t| format(DateTime::<Local>::from(t)))
+        .unwrap_or_default()
+}
+
+/// Coarse size classes so large files can be sorted apart from small ones
+pub fn size_bucket(size: u64) -> &'static str {
+    const KIB: u64 = 1024;
+    const MIB: u64 = 1024 * KIB;
+    const GIB: u64 = 1024 * MIB;
+
+    match size {
+        s if s < 100 * KIB => "tiny",
+        s if s < 10 * MIB => "small",
+        s if s < 100 * MIB => "medium",
+        s if s < GIB => "large",
+        _ => "huge",
+    }
+}
+
+/// Folder-safe version of a free-form name: anything but letters, digits, `_` and `-`
+/// becomes `_`
+pub fn sanitize_dirname(name: &str) -> String {
+    replace_unsafe(name, &['_', '-'])
+}
+
+/// Tag values such as artist and album names, cleaned like `sanitize_dirname` but
+/// keeping the spaces and punctuation names commonly use. Leading and trailing dots
+/// are dropped so no hidden files or Windows-invalid names come out.
+fn sanitize_tag(value: &str) -> String {
+    replace_unsafe(value, &['_', '-', ' ', '.', ',', '\'', '&', '(', ')', '[', ']', '!', '+'])
+        .trim_matches(['.', ' ', '_'])
+        .to_string()
+}
+
+fn replace_unsafe(name: &str, allowed: &[char]) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_alphanumeric() || allowed.contains(&c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+/// Zero padding in a placeholder such as `{track:02}`
+fn parse_width(name: &str, spec: &str, template: &str) -> Result<usize, TemplateError> {
+    match spec.strip_prefix('0').map(str::parse::<usize>) {
+        Some(Ok(width)) if (1..=9).contains(&width) => Ok(width),
+        _ => Err(TemplateError(format!(
+            "invalid format ':{}' for {{{}}} in path template '{}' (expected zero padding such as :02)",
+            spec, name, template
+        ))),
+    }
+}
+
+fn sanitize(value: &str) -> String {
+    let cleaned = value.replace(['/', '\\'], "_");
+    match cleaned.trim() {
+        "" | "." | ".." => "unknown".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_unknown_placeholders_and_escapes() {
+        assert!(PathTemplate::parse("{category}/{year}/{month}").is_ok());
+        assert!(PathTemplate::parse("{folder}/{colour}")
+            .unwrap_err()
+            .to_string()
+            .contains("{colour}"));
+        assert!(PathTemplate::parse("{folder").is_err());
+        assert!(PathTemplate::parse("../{ext}").is_err());
+        assert!(PathTemplate::parse("/tmp/{ext}").is_err());
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("Inbox").join("report.PDF");
+        fs::create_dir(file.parent().unwrap()).unwrap();
+        fs::write(&file, b"x").unwrap();
+        let metadata = fs::metadata(&file).unwrap();
+        let context = TemplateContext {
+            path: &file,
+            category: "documents",
+            folder: "Documents",
+            extension: "pdf",
+            metadata: Some(&metadata),
+        };
+
+        let template = PathTemplate::parse("{folder}/{ext}/{parent}-{size}/{initial}").unwrap();
+        assert_eq!(template.expand(&context), Path::new("Documents/pdf/Inbox-tiny/R"));
+
+        let year = Local::now().year().to_string();
+        let dated = PathTemplate::parse("{year}").unwrap().expand(&context);
+        assert_eq!(dated, Path::new(&year));
+        let media = PathTemplate::parse("Images/{camera}/{exif_year}-{gps}").unwrap();
+        assert_eq!(
+            media.expand(&context),
+            Path::new(&format!("Images/unknown/{}-no-gps", year))
+        );
+        let music = PathTemplate::parse("Music/{artist}/{album}/{track:02} - {title}.{ext}").unwrap();
+        assert_eq!(
+            music.destination(&context),
+            Path::new("Music/Unknown Artist/Unknown Album/00 - report.pdf")
+        );
+        assert_eq!(template.destination(&context), Path::new("Documents/pdf/Inbox-tiny/R/report.PDF"));
+        assert!(PathTemplate::parse("{track:2}").is_err());
+        assert_eq!(sanitize_tag("AC/DC: Live?"), "AC_DC_ Live");
+        assert_eq!(template.static_prefix(), Path::new(""));
+        assert_eq!(
+            PathTemplate::parse("Sorted/Docs/{ext}").unwrap().static_prefix(),
+            Path::new("Sorted/Docs")
+        );
+    }
+}
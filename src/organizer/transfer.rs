@@ -0,0 +1,370 @@
+// Moving, copying and linking files, including across filesystems where rename is not possible
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File, FileTimes, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// How a file reaches its organized location
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferMode {
+    #[default]
+    Move,
+    /// Leave the original in place and organize a verified copy
+    Copy,
+    /// Organize a hard link to the original (same filesystem only)
+    Hardlink,
+    /// Organize a relative symbolic link to the original
+    Symlink,
+}
+
+impl TransferMode {
+    /// Past tense used in logs, e.g. "Copied"
+    pub fn past_tense(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "Moved",
+            TransferMode::Copy => "Copied",
+            TransferMode::Hardlink => "Hard linked",
+            TransferMode::Symlink => "Symlinked",
+        }
+    }
+
+    /// Verb used in dry-run logs, e.g. "copy"
+    pub fn verb(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::Hardlink => "hard link",
+            TransferMode::Symlink => "symlink",
+        }
+    }
+}
+
+impl fmt::Display for TransferMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::Hardlink => "hardlink",
+            TransferMode::Symlink => "symlink",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for TransferMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "move" => Ok(TransferMode::Move),
+            "copy" => Ok(TransferMode::Copy),
+            "hardlink" => Ok(TransferMode::Hardlink),
+            "symlink" => Ok(TransferMode::Symlink),
+            other => Err(format!(
+                "unknown transfer mode '{}' (expected move, copy, hardlink or symlink)",
+                other
+            )),
+        }
+    }
+}
+
+/// Callback for long copies: (file, bytes copied, total bytes)
+pub type ProgressFn = fn(&Path, u64, u64);
+
+/// Files at least this large report copy progress
+const LARGE_FILE: u64 = 16 * 1024 * 1024;
+
+const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// Moves `source` to `destination`. Renames when possible; across filesystems the file
+/// is copied, synced, verified against a checksum and only then is the source deleted.
+/// An existing destination is never overwritten, even if it appears mid-move: the
+/// error then has kind `AlreadyExists`.
+pub fn move_file(source: &Path, destination: &Path, progress: Option<ProgressFn>) -> io::Result<()> {
+    if fs::symlink_metadata(destination).is_ok() {
+        return Err(already_exists(destination));
+    }
+
+    match rename_no_clobber(source, destination) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+            copy_verify_delete(source, destination, progress)
+        }
+        result => result,
+    }
+}
+
+/// Renames without replacing an existing file. A plain rename silently replaces the
+/// destination, so the file is hard-linked at the new name first, which fails
+/// atomically if the name is taken (also by other processes), and then unlinked at
+/// the old one. Filesystems without hard links fall back to a checked rename, and so do
+/// symlinks, which some platforms follow when linking.
+fn rename_no_clobber(source: &Path, destination: &Path) -> io::Result<()> {
+    let linked = if fs::symlink_metadata(source)?.file_type().is_symlink() {
+        Err(io::Error::from(io::ErrorKind::Unsupported))
+    } else {
+        fs::hard_link(source, destination)
+    };
+
+    match linked {
+        Ok(()) => fs::remove_file(source).inspect_err(|_| {
+            let _ = fs::remove_file(destination);
+        }),
+        Err(e) if matches!(
+            e.kind(),
+            io::ErrorKind::AlreadyExists | io::ErrorKind::CrossesDevices | io::ErrorKind::NotFound
+        ) =>
+        {
+            Err(e)
+        }
+        Err(_) => {
+            if fs::symlink_metadata(destination).is_ok() {
+                return Err(already_exists(destination));
+            }
+            fs::rename(source, destination)
+        }
+    }
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::AlreadyExists,
+        format!("destination already exists: {}", path.display()),
+    )
+}
+
+/// Puts `source` at `destination` the way `mode` says. Never overwrites `destination`.
+pub fn transfer_file(
+    source: &Path,
+    destination: &Path,
+    mode: TransferMode,
+    progress: Option<ProgressFn>,
+) -> io::Result<()> {
+    match mode {
+        TransferMode::Move => move_file(source, destination, progress),
+        TransferMode::Copy => copy_verified(source, destination, progress),
+        TransferMode::Hardlink => fs::hard_link(source, destination),
+        TransferMode::Symlink => {
+            // Relative to the link's folder, so the whole tree can be moved together
+            let target = fs::canonicalize(source)?;
+            let link_dir = fs::canonicalize(destination.parent().unwrap_or(Path::new(".")))?;
+            symlink(&relative_path(&target, &link_dir), destination)
+        }
+    }
+}
+
+/// Like `transfer_file`, but replaces an existing file at `destination`. The file is first
+/// transferred next to it under a temporary name and then renamed over it, so a failure
+/// leaves the existing file untouched.
+pub fn replace_file(
+    source: &Path,
+    destination: &Path,
+    mode: TransferMode,
+    progress: Option<ProgressFn>,
+) -> io::Result<()> {
+    let name = destination.file_name().unwrap_or_default().to_string_lossy();
+    let temp = destination.with_file_name(format!(".{}.kondo-tmp", name));
+    transfer_file(source, &temp, mode, progress)?;
+
+    fs::rename(&temp, destination).inspect_err(|_| {
+        let _ = match mode {
+            TransferMode::Move => move_file(&temp, source, None),
+            _ => fs::remove_file(&temp),
+        };
+    })
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, link: &Path) -> io::Result<()> {
+    std::os::windows::fs::symlink_file(target, link)
+}
+
+/// Path that leads from directory `from` to `target`; both must be absolute
+fn relative_path(target: &Path, from: &Path) -> PathBuf {
+    let target: Vec<_> = target.components().collect();
+    let from: Vec<_> = from.components().collect();
+    let common = target
+        .iter()
+        .zip(&from)
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in common..from.len() {
+        relative.push("..");
+    }
+    for component in &target[common..] {
+        relative.push(component.as_os_str());
+    }
+    relative
+}
+
+/// Prints copy progress of large files on stderr
+pub fn print_progress(path: &Path, copied: u64, total: u64) {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let percent = (copied * 100).checked_div(total).unwrap_or(100);
+
+    eprint!("\r   Copying {}: {:3}%", name, percent);
+    if copied >= total {
+        eprintln!();
+    }
+}
+
+fn copy_verify_delete(source: &Path, destination: &Path, progress: Option<ProgressFn>) -> io::Result<()> {
+    copy_verified(source, destination, progress)?;
+
+    if let Err(e) = fs::remove_file(source) {
+        // Keep move semantics: never leave the file in both places
+        let _ = fs::remove_file(destination);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Copies to a new file, syncs it and checks the copy against the source checksum
+fn copy_verified(source: &Path, destination: &Path, progress: Option<ProgressFn>) -> io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    let mut reader = File::open(source)?;
+    let writer = OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(destination)?;
+
+    // From here on the destination is ours and is removed on any failure
+    let verified = copy_and_hash(&mut reader, writer, destination, &metadata, progress)
+        .and_then(|source_hash| {
+            if hash_file(destination)? == source_hash {
+                Ok(())
+            } else {
+                Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("checksum mismatch after copying to {}", destination.display()),
+                ))
+            }
+        });
+
+    if let Err(e) = verified {
+        let _ = fs::remove_file(destination);
+        return Err(e);
+    }
+
+    Ok(())
+}
+
+/// Copies the file while hashing what was read, then syncs the copy and restores
+/// permissions and timestamps
+fn copy_and_hash(
+    reader: &mut File,
+    mut writer: File,
+    destination: &Path,
+    metadata: &fs::Metadata,
+    progress: Option<ProgressFn>,
+) -> io::Result<blake3::Hash> {
+    let total = metadata.len();
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut copied = 0u64;
+    let progress = progress.filter(|_| total >= LARGE_FILE);
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        copied += read as u64;
+
+        if let Some(report) = progress {
+            report(destination, copied, total);
+        }
+    }
+
+    let mut times = FileTimes::new();
+    if let Ok(modified) = metadata.modified() {
+        times = times.set_modified(modified);
+    }
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    writer.set_times(times)?;
+    writer.set_permissions(metadata.permissions())?;
+    writer.sync_all()?;
+
+    Ok(hasher.finalize())
+}
+
+/// Streams the whole file through blake3
+pub fn hash_file(path: &Path) -> io::Result<blake3::Hash> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(File::open(path)?)?;
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_copy_fallback_preserves_content_and_mtime() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.bin");
+        let destination = dir.path().join("b.bin");
+        fs::write(&source, vec![7u8; CHUNK_SIZE + 10]).unwrap();
+
+        let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        copy_verify_delete(&source, &destination, None).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read(&destination).unwrap().len(), CHUNK_SIZE + 10);
+        assert_eq!(fs::metadata(&destination).unwrap().modified().unwrap(), modified);
+    }
+
+    #[test]
+    fn test_never_overwrites_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        let destination = dir.path().join("b.txt");
+        fs::write(&source, b"new").unwrap();
+        fs::write(&destination, b"old").unwrap();
+
+        assert!(move_file(&source, &destination, None).is_err());
+        assert!(copy_verify_delete(&source, &destination, None).is_err());
+        assert_eq!(fs::read(&destination).unwrap(), b"old");
+        assert!(source.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinks_are_relative() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("a.txt");
+        fs::write(&source, b"a").unwrap();
+        fs::create_dir(dir.path().join("Docs")).unwrap();
+        let link = dir.path().join("Docs").join("a.txt");
+
+        transfer_file(&source, &link, TransferMode::Symlink, None).unwrap();
+
+        assert_eq!(fs::read_link(&link).unwrap(), Path::new("../a.txt"));
+        assert_eq!(fs::read(&link).unwrap(), b"a");
+        assert!(source.exists());
+    }
+}
@@ -0,0 +1,203 @@
+// Directory traversal shared by every mode, optionally recursive
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::ignore::{self, SkipMatcher};
+
+/// Folder Kondo parks skipped files in
+pub const SKIP_DIR: &str = "kondo-skip";
+
+/// Folder `kondo dedupe` moves duplicate copies into
+pub const TRASH_DIR: &str = "kondo-trash";
+
+/// Per-directory settings file, read by kondo and never organized
+pub const DIRECTORY_CONFIG: &str = ".kondo.toml";
+
+/// Whether `path` is a directory's own .kondo.toml
+pub fn is_directory_config(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| name == DIRECTORY_CONFIG)
+}
+
+/// How deep to look and which folders Kondo produced on earlier runs
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Depth like `find -maxdepth`: 1 only lists the base directory, None is unlimited
+    pub max_depth: Option<usize>,
+    /// Folder names Kondo creates, such as category folders, `kondo-skip` and `kondo-trash`
+    pub organized_names: HashSet<String>,
+    /// Folders the journal recorded as created by Kondo (cluster and group dirs)
+    pub organized_dirs: HashSet<PathBuf>,
+    /// .gitignore-style patterns for files and folders to leave alone, joined by the
+    /// base directory's .kondoignore
+    pub skip_patterns: Vec<String>,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: Some(1),
+            organized_names: HashSet::from([SKIP_DIR.to_string(), TRASH_DIR.to_string()]),
+            organized_dirs: HashSet::new(),
+            skip_patterns: Vec::new(),
+        }
+    }
+}
+
+impl WalkOptions {
+    /// Descends into subdirectories up to `max_depth` levels (unlimited if None)
+    pub fn recursive(max_depth: Option<usize>) -> Self {
+        Self {
+            max_depth,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_organized_names<I: IntoIterator<Item = String>>(mut self, names: I) -> Self {
+        self.organized_names.extend(names);
+        self
+    }
+
+    pub fn with_organized_dirs<I: IntoIterator<Item = PathBuf>>(mut self, dirs: I) -> Self {
+        self.organized_dirs.extend(dirs);
+        self
+    }
+
+    pub fn with_skip_patterns(mut self, patterns: &[String]) -> Self {
+        self.skip_patterns.extend_from_slice(patterns);
+        self
+    }
+
+    fn is_organized(&self, dir: &Path) -> bool {
+        let by_name = dir
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| self.organized_names.contains(n));
+
+        by_name
+            || self.organized_dirs.contains(dir)
+            || std::path::absolute(dir).is_ok_and(|abs| self.organized_dirs.contains(&abs))
+    }
+}
+
+/// Files found under a base directory
+#[derive(Debug, Default)]
+pub struct Walk {
+    /// Files that still need organizing
+    pub files: Vec<PathBuf>,
+    /// Files inside folders Kondo already created; left alone on re-runs
+    pub organized: Vec<PathBuf>,
+    /// Files matched by the skip patterns; folders they match are not entered
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Whether `path` is one of kondo's own settings files, which are never organized
+pub fn is_settings_file(path: &Path) -> bool {
+    is_directory_config(path) || ignore::is_ignore_file(path)
+}
+
+/// Collects files below `base`. Hidden and symlinked directories are never entered,
+/// .kondo.toml and .kondoignore files are left out, and files inside organized
+/// folders or matched by the skip patterns are reported separately.
+pub fn collect_files(base: &Path, options: &WalkOptions) -> io::Result<Walk> {
+    let skip = SkipMatcher::for_directory(base, &options.skip_patterns)?;
+    let mut walk = Walk::default();
+    visit(base, 1, false, options, &skip, &mut walk)?;
+    Ok(walk)
+}
+
+/// Sorts the given files of `base` the way `collect_files` would, e.g. ones watch
+/// mode saw arrive
+pub fn split_files(base: &Path, files: Vec<PathBuf>, options: &WalkOptions) -> io::Result<Walk> {
+    let skip = SkipMatcher::for_directory(base, &options.skip_patterns)?;
+    let mut walk = Walk::default();
+    for path in files.into_iter().filter(|path| !is_settings_file(path)) {
+        if skip.is_skipped(&path, false) {
+            walk.skipped.push(path);
+        } else {
+            walk.files.push(path);
+        }
+    }
+    Ok(walk)
+}
+
+fn visit(
+    dir: &Path,
+    depth: usize,
+    organized: bool,
+    options: &WalkOptions,
+    skip: &SkipMatcher,
+    walk: &mut Walk,
+) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let path = entry.path();
+
+        if path.is_file() {
+            if is_settings_file(&path) {
+                continue;
+            }
+            if skip.is_skipped(&path, false) {
+                walk.skipped.push(path);
+            } else if organized {
+                walk.organized.push(path);
+            } else {
+                walk.files.push(path);
+            }
+            continue;
+        }
+
+        let is_real_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+        let hidden = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if !is_real_dir
+            || hidden
+            || options.max_depth.is_some_and(|max| depth >= max)
+            || skip.is_skipped(&path, true)
+        {
+            continue;
+        }
+
+        // Unreadable subdirectories are skipped rather than aborting the whole walk
+        let inside = organized || options.is_organized(&path);
+        let _ = visit(&path, depth + 1, inside, options, skip, walk);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depth_limit_and_organized_folders() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path();
+        fs::create_dir_all(base.join("sub/deeper")).unwrap();
+        fs::create_dir_all(base.join("Images")).unwrap();
+        fs::create_dir_all(base.join(".git")).unwrap();
+        for file in ["a.txt", ".kondo.toml", "sub/b.txt", "sub/deeper/c.txt", "Images/d.jpg", ".git/config"] {
+            fs::write(base.join(file), b"x").unwrap();
+        }
+
+        let top = collect_files(base, &WalkOptions::default()).unwrap();
+        assert_eq!(top.files, vec![base.join("a.txt")]);
+        assert!(top.organized.is_empty());
+
+        let options = WalkOptions::recursive(Some(2)).with_organized_names(["Images".to_string()]);
+        let walk = collect_files(base, &options).unwrap();
+        let mut files = walk.files.clone();
+        files.sort();
+        assert_eq!(files, vec![base.join("a.txt"), base.join("sub/b.txt")]);
+        assert_eq!(walk.organized, vec![base.join("Images/d.jpg")]);
+
+        let unlimited = collect_files(base, &WalkOptions::recursive(None)).unwrap();
+        assert_eq!(unlimited.files.len(), 4);
+    }
+}
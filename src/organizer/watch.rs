@@ -0,0 +1,257 @@
+// Watches a folder and organizes new files once they have stopped changing
+use notify::event::{EventKind, ModifyKind};
+use notify::{RecursiveMode, Watcher};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+
+use super::walk;
+
+/// Suffixes browsers and download tools give files while they are still being written
+const PARTIAL_SUFFIXES: &[&str] = &[
+    "part",
+    "partial",
+    "crdownload",
+    "download",
+    "opdownload",
+    "tmp",
+    "!qb",
+    "kondo-tmp",
+    "kondo-link",
+];
+
+/// Longest wait between checks for settled files
+const MAX_TICK: Duration = Duration::from_secs(1);
+
+/// Which mode organizes the files that arrive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchMode {
+    /// Categories and rules, as with `kondo categorize`
+    #[default]
+    Categorize,
+    /// Dated folders, as with `kondo date`
+    Date,
+}
+
+impl fmt::Display for WatchMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            WatchMode::Categorize => "categorize",
+            WatchMode::Date => "date",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for WatchMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "categorize" | "categorise" => Ok(WatchMode::Categorize),
+            "date" => Ok(WatchMode::Date),
+            other => Err(format!(
+                "unknown watch mode '{}' (expected categorize or date)",
+                other
+            )),
+        }
+    }
+}
+
+/// `[watch]` in kondo.toml
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchConfig {
+    #[serde(default)]
+    pub mode: WatchMode,
+
+    /// Seconds a new file must go unchanged before it is organized
+    #[serde(default = "default_settle_seconds")]
+    pub settle_seconds: u64,
+}
+
+fn default_settle_seconds() -> u64 {
+    5
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            mode: WatchMode::default(),
+            settle_seconds: default_settle_seconds(),
+        }
+    }
+}
+
+/// Whether `path` is an unfinished download such as `report.pdf.part`
+fn is_partial_download(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| PARTIAL_SUFFIXES.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Firefox keeps an empty `report.pdf` next to `report.pdf.part` until the download
+/// finishes and the partial file is renamed over it
+fn has_partial_sibling(path: &Path) -> bool {
+    let Some(name) = path.file_name().map(|n| n.to_string_lossy()) else {
+        return false;
+    };
+    PARTIAL_SUFFIXES
+        .iter()
+        .any(|suffix| path.with_file_name(format!("{}.{}", name, suffix)).exists())
+}
+
+type Stamp = (u64, Option<SystemTime>);
+
+fn stamp(path: &Path) -> Option<Stamp> {
+    let metadata = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((metadata.len(), metadata.modified().ok()))
+}
+
+struct Pending {
+    last_change: Instant,
+    stamp: Option<Stamp>,
+}
+
+/// Files seen changing, held back until they have been quiet for the settle time
+pub struct Settler {
+    settle: Duration,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl Settler {
+    pub fn new(settle: Duration) -> Self {
+        Self {
+            settle,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Records that `path` was created or changed at `now`
+    pub fn touch(&mut self, path: PathBuf, now: Instant) {
+        let stamp = stamp(&path);
+        self.pending.insert(
+            path,
+            Pending {
+                last_change: now,
+                stamp,
+            },
+        );
+    }
+
+    /// Takes the files that have been quiet for the settle time. Files that vanished,
+    /// are partial downloads or are not regular files are dropped; files whose size
+    /// or modification time still moved, or whose download is still running, wait
+    /// another round.
+    pub fn take_settled(&mut self, now: Instant) -> Vec<PathBuf> {
+        let mut settled = Vec::new();
+        self.pending.retain(|path, pending| {
+            if now.duration_since(pending.last_change) < self.settle {
+                return true;
+            }
+            let current = match stamp(path) {
+                Some(current) if !is_partial_download(path) => current,
+                _ => return false,
+            };
+            if pending.stamp != Some(current) || has_partial_sibling(path) {
+                pending.stamp = Some(current);
+                pending.last_change = now;
+                return true;
+            }
+            settled.push(path.clone());
+            false
+        });
+        settled.sort();
+        settled
+    }
+}
+
+/// What the watcher hands back to its caller
+pub enum WatchEvent {
+    /// New files directly in the watched folder that have stopped changing
+    Settled(Vec<PathBuf>),
+    /// The file system watcher reported a problem; watching continues
+    Error(String),
+}
+
+/// Watches the top level of `dir` until the watcher fails, calling `handle` with every
+/// batch of settled files. Files already there when watching starts are left alone.
+pub fn watch(dir: &Path, settle: Duration, mut handle: impl FnMut(WatchEvent)) -> io::Result<()> {
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(io::Error::other)?;
+    watcher
+        .watch(dir, RecursiveMode::NonRecursive)
+        .map_err(io::Error::other)?;
+
+    let tick = (settle / 2).clamp(Duration::from_millis(100), MAX_TICK);
+    let mut settler = Settler::new(settle);
+    loop {
+        match receiver.recv_timeout(tick) {
+            Ok(Ok(event)) => {
+                let arrived = matches!(
+                    event.kind,
+                    EventKind::Create(_)
+                        | EventKind::Modify(ModifyKind::Data(_) | ModifyKind::Name(_) | ModifyKind::Any)
+                );
+                if arrived {
+                    let now = Instant::now();
+                    for path in event.paths {
+                        if path.parent() == Some(dir) && !walk::is_settings_file(&path) {
+                            settler.touch(path, now);
+                        }
+                    }
+                }
+            }
+            Ok(Err(e)) => handle(WatchEvent::Error(e.to_string())),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(io::Error::other("file system watcher stopped"));
+            }
+        }
+
+        let settled = settler.take_settled(Instant::now());
+        if !settled.is_empty() {
+            handle(WatchEvent::Settled(settled));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_settle_after_downloads_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = dir.path().join("report.pdf");
+        let partial = dir.path().join("report.pdf.part");
+        let chrome = dir.path().join("Unconfirmed 123.crdownload");
+        fs::write(&report, b"").unwrap();
+        fs::write(&partial, b"half").unwrap();
+        fs::write(&chrome, b"half").unwrap();
+
+        let settle = Duration::from_secs(5);
+        let start = Instant::now();
+        let mut settler = Settler::new(settle);
+        for path in [&report, &partial, &chrome] {
+            settler.touch(path.clone(), start);
+        }
+
+        assert!(settler.take_settled(start + Duration::from_secs(1)).is_empty());
+        // The placeholder waits for its .part file, which itself is never organized
+        assert!(settler.take_settled(start + settle).is_empty());
+        assert_eq!(settler.pending.len(), 1);
+
+        fs::rename(&partial, &report).unwrap();
+        settler.touch(report.clone(), start + settle);
+        assert!(settler.take_settled(start + settle * 2 - Duration::from_secs(1)).is_empty());
+        assert_eq!(settler.take_settled(start + settle * 2), vec![report]);
+        assert!(settler.pending.is_empty());
+    }
+}
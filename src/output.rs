@@ -0,0 +1,250 @@
+// Machine-readable output: one JSON document per run, or NDJSON events as files are handled
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::organizer::dedupe::DuplicateGroup;
+use crate::organizer::error::{FileError, KondoError};
+use crate::organizer::plan::{ExecutionReport, Outcome, OutcomeFn, SkippedFile};
+
+/// Everything worked, or there was nothing to do
+pub const EXIT_SUCCESS: i32 = 0;
+/// The command could not run, or every file it tried failed
+pub const EXIT_FAILURE: i32 = 1;
+/// Some files were organized and others failed (2 is clap's usage error)
+pub const EXIT_PARTIAL: i32 = 3;
+
+/// `--output`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// One JSON document per directory once it is done
+    Json,
+    /// One JSON event per line as files are handled, ending with a summary line
+    Ndjson,
+}
+
+impl OutputFormat {
+    pub fn is_text(self) -> bool {
+        self == OutputFormat::Text
+    }
+
+    /// Executor sink that streams per-file events, for NDJSON only
+    pub fn outcome_sink(self) -> Option<OutcomeFn> {
+        match self {
+            OutputFormat::Ndjson => Some(print_event),
+            _ => None,
+        }
+    }
+}
+
+/// How a run went, reported as `status` and through the exit code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Success,
+    Partial,
+    Failed,
+}
+
+impl Status {
+    pub fn from_counts(succeeded: usize, failed: usize) -> Self {
+        match (succeeded, failed) {
+            (_, 0) => Status::Success,
+            (0, _) => Status::Failed,
+            _ => Status::Partial,
+        }
+    }
+
+    pub fn of_report(report: &ExecutionReport) -> Self {
+        Self::from_counts(
+            report.files_moved() + report.duplicates_deleted(),
+            report.files_failed(),
+        )
+    }
+
+    /// Status of two runs together, e.g. two target directories
+    pub fn combine(self, other: Status) -> Self {
+        if self == other {
+            self
+        } else {
+            Status::Partial
+        }
+    }
+
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Status::Success => EXIT_SUCCESS,
+            Status::Partial => EXIT_PARTIAL,
+            Status::Failed => EXIT_FAILURE,
+        }
+    }
+}
+
+/// The document printed for each run. Every key is always present: `result` is null
+/// when the run failed with `error`, which then holds `kind` and `message`.
+#[derive(Debug, Serialize)]
+pub struct Document<'a, T: Serialize> {
+    pub command: &'a str,
+    pub directory: Option<&'a Path>,
+    pub dry_run: bool,
+    pub status: Status,
+    pub result: Option<T>,
+    pub error: Option<KondoError>,
+    pub warnings: Vec<String>,
+}
+
+impl<'a, T: Serialize> Document<'a, T> {
+    pub fn new(command: &'a str, directory: Option<&'a Path>, dry_run: bool, status: Status, result: T) -> Self {
+        Self {
+            command,
+            directory,
+            dry_run,
+            status,
+            result: Some(result),
+            error: None,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+}
+
+impl<'a> Document<'a, ()> {
+    pub fn failed(command: &'a str, directory: Option<&'a Path>, dry_run: bool, error: KondoError) -> Self {
+        Self {
+            command,
+            directory,
+            dry_run,
+            status: Status::Failed,
+            result: None,
+            error: Some(error),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Tagged<'a, T: Serialize> {
+    event: &'a str,
+    #[serde(flatten)]
+    value: &'a T,
+}
+
+/// Prints the final document: pretty JSON, or a `summary` event line
+pub fn emit<T: Serialize>(format: OutputFormat, document: &Document<T>) {
+    match format {
+        OutputFormat::Ndjson => print_tagged("summary", document),
+        _ => match serde_json::to_string_pretty(document) {
+            Ok(json) => println!("{}", json),
+            Err(e) => eprintln!("✗ Could not serialize output: {}", e),
+        },
+    }
+}
+
+/// Prints one NDJSON line `{"event": ..., <fields of value>}`
+pub fn print_tagged<T: Serialize>(event: &str, value: &T) {
+    match serde_json::to_string(&Tagged { event, value }) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("✗ Could not serialize output: {}", e),
+    }
+}
+
+/// Streams an outcome as an NDJSON line; used as the executor's outcome sink
+pub fn print_event(outcome: &Outcome) {
+    match serde_json::to_string(outcome) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("✗ Could not serialize output: {}", e),
+    }
+}
+
+/// Counts of an executed plan, for modes without a result type of their own
+#[derive(Debug, Serialize)]
+pub struct PlanSummary {
+    pub files_moved: usize,
+    pub folders_created: usize,
+    pub files_skipped: usize,
+    pub files_failed: usize,
+    pub files_renamed: usize,
+    pub files_overwritten: usize,
+    pub duplicates_deleted: usize,
+    pub group_counts: HashMap<String, usize>,
+    pub skipped_details: Vec<SkippedFile>,
+    pub errors: Vec<FileError>,
+}
+
+impl From<&ExecutionReport> for PlanSummary {
+    fn from(report: &ExecutionReport) -> Self {
+        Self {
+            files_moved: report.files_moved(),
+            folders_created: report.dirs_created(),
+            files_skipped: report.files_skipped(),
+            files_failed: report.files_failed(),
+            files_renamed: report.files_renamed(),
+            files_overwritten: report.files_overwritten(),
+            duplicates_deleted: report.duplicates_deleted(),
+            group_counts: report.group_counts(),
+            skipped_details: report.skipped_details(),
+            errors: report.errors(),
+        }
+    }
+}
+
+/// Result of `kondo dedupe`
+#[derive(Debug, Serialize)]
+pub struct DedupeSummary<'a> {
+    pub action: String,
+    pub groups: &'a [DuplicateGroup],
+    pub duplicates: usize,
+    pub bytes_reclaimable: u64,
+    /// Moves into kondo-trash with `--action trash`
+    pub trash: Option<PlanSummary>,
+    /// Duplicates replaced with hard links with `--action hardlink`
+    pub linked: Vec<PathBuf>,
+    pub errors: Vec<FileError>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_and_event_fields() {
+        assert_eq!(Status::from_counts(3, 0), Status::Success);
+        assert_eq!(Status::from_counts(0, 0), Status::Success);
+        assert_eq!(Status::from_counts(2, 1), Status::Partial);
+        assert_eq!(Status::from_counts(0, 4), Status::Failed);
+        assert_eq!(Status::Success.combine(Status::Failed), Status::Partial);
+        assert_eq!(Status::Failed.combine(Status::Failed), Status::Failed);
+        assert_eq!(Status::Partial.exit_code(), EXIT_PARTIAL);
+
+        let moved = Outcome::Moved {
+            source: PathBuf::from("/in/a.jpg"),
+            destination: PathBuf::from("/in/Images/a.jpg"),
+            group: "Images".to_string(),
+        };
+        let json: serde_json::Value = serde_json::to_value(&moved).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "event": "moved",
+                "source": "/in/a.jpg",
+                "destination": "/in/Images/a.jpg",
+                "group": "Images",
+            })
+        );
+
+        let error = KondoError::ConfigInvalid("unknown granularity".to_string());
+        let document = Document::failed("date", None, true, error);
+        let json = serde_json::to_value(&document).unwrap();
+        assert_eq!(json["status"], "failed");
+        assert_eq!(json["result"], serde_json::Value::Null);
+        assert_eq!(json["error"]["kind"], "config_invalid");
+    }
+}
@@ -0,0 +1,151 @@
+// Where the kondo binary reads and writes its own files, checked end to end with a
+// throwaway home directory
+#![cfg(unix)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Runs kondo with nothing from the real environment but `vars`
+fn kondo(home: &Path, vars: &[(&str, &str)], args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_kondo"))
+        .env_clear()
+        .env("HOME", home)
+        .envs(vars.iter().copied())
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).trim().to_string()
+}
+
+/// Every path below `dir`, sorted
+fn tree(dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                pending.push(path.clone());
+            }
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+}
+
+#[test]
+fn test_config_path_follows_xdg_and_kondo_config() {
+    let home = tempfile::tempdir().unwrap();
+    let home = home.path();
+    let xdg = home.join("xdg").to_string_lossy().to_string();
+
+    let default = kondo(home, &[], &["config", "path"]);
+    assert_eq!(stdout(&default), home.join(".config/kondo/kondo.toml").display().to_string());
+
+    let moved = kondo(home, &[("XDG_CONFIG_HOME", &xdg)], &["config", "path"]);
+    assert_eq!(stdout(&moved), format!("{}/kondo/kondo.toml", xdg));
+
+    // Relative XDG paths are ignored, as the spec asks
+    let relative = kondo(home, &[("XDG_CONFIG_HOME", "relative")], &["config", "path"]);
+    assert_eq!(stdout(&relative), stdout(&default));
+
+    let file = home.join("elsewhere.toml");
+    fs::write(&file, "version = 3\n").unwrap();
+    let file = file.to_string_lossy().to_string();
+    let chosen = kondo(home, &[("KONDO_CONFIG", &file)], &["config", "path"]);
+    assert_eq!(stdout(&chosen), file);
+
+    let missing = kondo(home, &[("KONDO_CONFIG", "/no/such/kondo.toml")], &["config", "path"]);
+    assert_eq!(missing.status.code(), Some(1));
+}
+
+#[test]
+fn test_kondo_variables_set_single_values() {
+    let home = tempfile::tempdir().unwrap();
+    let home = home.path();
+    let target = tempfile::tempdir().unwrap();
+    fs::write(target.path().join("photo.jpg"), b"x").unwrap();
+    let dir = target.path().to_string_lossy().to_string();
+    let vars = [
+        ("KONDO_TRANSFER_MODE", "copy"),
+        ("KONDO_SIMILARITY_CONFIG__MIN_SIMILARITY_SCORE", "0.8"),
+    ];
+
+    let shown = stdout(&kondo(home, &vars, &["config", "show", "--effective", &dir]));
+    assert!(shown.contains("transfer_mode = \"copy\"  # $KONDO_TRANSFER_MODE"));
+    assert!(shown.contains(
+        "similarity_config.min_similarity_score = 0.8  # $KONDO_SIMILARITY_CONFIG__MIN_SIMILARITY_SCORE"
+    ));
+
+    // Copied, so the original stays where it was
+    let run = kondo(home, &vars, &["categorize", "--no-ui", &dir]);
+    assert!(run.status.success());
+    assert!(target.path().join("photo.jpg").exists());
+    assert!(target.path().join("Images/photo.jpg").exists());
+
+    let bad = kondo(home, &[("KONDO_TRANSFER_MODE", "teleport")], &["categorize", "--no-ui", &dir]);
+    assert_eq!(bad.status.code(), Some(1));
+
+    // Other tools share the prefix, so a name kondo does not know only warns
+    let unrelated = kondo(home, &[("KONDO_FOO", "1")], &["categorize", "--dry-run", "--no-ui", &dir]);
+    assert!(unrelated.status.success());
+    assert!(String::from_utf8_lossy(&unrelated.stderr).contains("KONDO_FOO"));
+}
+
+#[test]
+fn test_nothing_is_written_until_files_move() {
+    let home = tempfile::tempdir().unwrap();
+    let home = home.path();
+    let target = tempfile::tempdir().unwrap();
+    fs::write(target.path().join("photo.jpg"), b"x").unwrap();
+    let dir = target.path().to_string_lossy().to_string();
+    let file = target.path().join("photo.jpg").to_string_lossy().to_string();
+    let config_home = home.join("config").to_string_lossy().to_string();
+    let state_home = home.join("state").to_string_lossy().to_string();
+    let vars = [("XDG_CONFIG_HOME", config_home.as_str()), ("XDG_STATE_HOME", state_home.as_str())];
+
+    for args in [
+        vec!["categorize", "--dry-run", "--no-ui", &dir],
+        vec!["cluster", "--dry-run", "--no-ui", &dir],
+        vec!["explain", &file],
+        vec!["undo", "--list"],
+        vec!["config", "check"],
+    ] {
+        kondo(home, &vars, &args);
+        assert!(tree(home).is_empty(), "`kondo {}` wrote {:?}", args.join(" "), tree(home));
+    }
+    assert!(target.path().join("photo.jpg").exists());
+
+    // A real run keeps its log and undo journal in the state directory only
+    assert!(kondo(home, &vars, &["categorize", "--no-ui", &dir]).status.success());
+    assert!(home.join("state/kondo/kondo.log").is_file());
+    assert!(home.join("state/kondo/journal.jsonl").is_file());
+    assert!(!home.join("config").exists());
+
+    // Without the TUI, cluster only analyzes and has nothing to journal
+    let journal = home.join("state/kondo/journal.jsonl");
+    let before = fs::read_to_string(&journal).unwrap();
+    assert!(kondo(home, &vars, &["cluster", "--no-ui", &dir]).status.success());
+    assert_eq!(fs::read_to_string(&journal).unwrap(), before);
+
+    assert!(kondo(home, &vars, &["config", "init"]).status.success());
+    assert!(home.join("config/kondo/kondo.toml").is_file());
+    assert_eq!(kondo(home, &vars, &["config", "init"]).status.code(), Some(1));
+}
+
+#[test]
+fn test_missing_directory_is_reported_in_the_json_document() {
+    let home = tempfile::tempdir().unwrap();
+    let missing = home.path().join("missing").to_string_lossy().to_string();
+
+    let run = kondo(home.path(), &[], &["categorize", "--dry-run", "--output", "json", &missing]);
+    assert_eq!(run.status.code(), Some(1));
+    let document: serde_json::Value = serde_json::from_slice(&run.stdout).unwrap();
+    assert_eq!(document["status"], "failed");
+    assert_eq!(document["error"]["kind"], "invalid_input");
+}